@@ -10,7 +10,7 @@ fn log_ratio_various() {
         }
     }
     let s = format!("sum = {}", sum);
-    assert!(s.len() > 0);
+    assert!(!s.is_empty());
 }
 
 fn library_log_various() {
@@ -22,7 +22,7 @@ fn library_log_various() {
         }
     }
     let s = format!("sum = {}", sum);
-    assert!(s.len() > 0);
+    assert!(!s.is_empty());
 }
 
 /// Log of Ratios that are between one and two.
@@ -34,13 +34,13 @@ fn log_ratio_small_10k() {
         sum += approximate_log;
     }
     let s = format!("sum = {}", sum);
-    assert!(s.len() > 0);
+    assert!(!s.is_empty());
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
-    c.bench_function("10k log_ratio for small domain (1..2)", |b| b.iter(|| log_ratio_small_10k()));
-    c.bench_function("Many logarithms using log_ratio", |b| b.iter(|| log_ratio_various()));
-    c.bench_function("Many logarithms using std library ln", |b| b.iter(|| library_log_various()));
+    c.bench_function("10k log_ratio for small domain (1..2)", |b| b.iter(log_ratio_small_10k));
+    c.bench_function("Many logarithms using log_ratio", |b| b.iter(log_ratio_various));
+    c.bench_function("Many logarithms using std library ln", |b| b.iter(library_log_various));
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,323 @@
+use std::cmp::{min, max};
+use std::fmt::{Display, Formatter};
+use hilbert::Point;
+use super::single_linkage::{SingleLinkage, AdjacentPairDistance};
+
+/// Largest value of `noise_skip_by` that `SingleLinkage::auto` will choose.
+pub const MAX_AUTO_NOISE_SKIP_BY : u16 = 30;
+
+/// The low end of the sorted distances counts as smooth once the largest growth across `noise_skip_by + 1` positions
+/// is no more than this many times the average growth across as many positions.
+pub const SPIKINESS_TOLERANCE : f64 = 4.0;
+
+/// The knee counts as stable if nudging `noise_skip_by` or `lowest_index_for_checking_growth_ratio`
+/// moves the linkage distance by no more than this fraction.
+pub const KNEE_STABILITY_TOLERANCE : f64 = 0.1;
+
+/// A run is an outlier if it has no more than this fraction as many points as the run holding a typical point.
+pub const OUTLIER_FRACTION_OF_TYPICAL_RUN : f64 = 0.1;
+
+// ........................... ParameterChoice ..........................................
+
+/// The value that `SingleLinkage::auto` chose for one parameter, and why.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterChoice {
+    /// Name of the parameter, as in the `with_` method that sets it.
+    pub parameter : &'static str,
+
+    /// Value chosen.
+    pub value : u64,
+
+    /// Value that `SingleLinkage::new` would have used.
+    pub default_value : u64,
+
+    /// Explanation of the choice, citing the measurements on which it rests.
+    pub reason : String
+}
+
+// ........................... AutoConfiguration ..........................................
+
+/// A `SingleLinkage` configured from measurements of the data, with a record of those measurements and the choices they led to.
+#[derive(Clone, Debug)]
+pub struct AutoConfiguration {
+    /// The configured `SingleLinkage`.
+    pub finder : SingleLinkage,
+
+    /// Fraction of the distances between consecutive points in Hilbert order that are zero,
+    /// which estimates the fraction of points that duplicate another point.
+    pub duplicate_fraction : f64,
+
+    /// Largest growth across `noise_skip_by + 1` positions in the low end of the sorted distances,
+    /// as a multiple of the average growth. Values near one indicate a smooth curve.
+    pub spikiness : f64,
+
+    /// Relative spread of the linkage distances found when nudging `noise_skip_by` and `lowest_index_for_checking_growth_ratio`.
+    /// Values near zero indicate a well-defined knee.
+    pub knee_spread : f64,
+
+    /// Linkage distance found by `finder`.
+    pub linkage_square_distance : u64,
+
+    /// The parameters chosen, in the order they were derived.
+    pub choices : Vec<ParameterChoice>
+}
+
+impl Display for AutoConfiguration {
+    /// One line per parameter chosen, giving its value, its default and the reason it was chosen.
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        for choice in self.choices.iter() {
+            writeln!(f, "{} = {} (default {}): {}", choice.parameter, choice.value, choice.default_value, choice.reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl SingleLinkage {
+    /// Derive `lowest_index_for_checking_growth_ratio`, `noise_skip_by` and `outlier_cluster_size` from the data,
+    /// instead of using defaults based solely on the number of points. All other settings are kept.
+    ///
+    ///   - `lowest_index_for_checking_growth_ratio` - The distances of zero between duplicate points are passed over,
+    ///     along with half of the remaining distances.
+    ///   - `noise_skip_by` - The smallest value that smooths the low end of the sorted distances,
+    ///     where no knee is expected, so that no jump exceeds `SPIKINESS_TOLERANCE` times the average.
+    ///     The value is then raised, if need be, until nudging either derived parameter moves the linkage distance
+    ///     by no more than `KNEE_STABILITY_TOLERANCE`.
+    ///   - `outlier_cluster_size` - A tenth (`OUTLIER_FRACTION_OF_TYPICAL_RUN`) of the size of the Hilbert run holding the median point
+    ///     when split at the chosen linkage distance.
+    ///
+    /// A copy of the points is sorted in Hilbert order whether or not `need_to_sort_by_hilbert_curve` is set,
+    /// and the points themselves are left untouched.
+    /// If there are too few points to measure, the settings are left as they are, and the reasons say so.
+    ///
+    ///   - points - Points to analyze, which should be the same points later passed to `find`.
+    pub fn auto(self, points : &[Point]) -> AutoConfiguration {
+        let point_count = points.len();
+        let defaults = SingleLinkage::new(point_count as u32, self.get_bits_per_dimension());
+        let mut sorted_points = points.to_vec();
+        self.sort_by_hilbert_curve(&mut sorted_points);
+        let distances = AdjacentPairDistance::all_pairs(&sorted_points);
+        let square_distances : Vec<u64> = distances.iter().map(|pair| pair.square_distance).collect();
+        let mut sorted_distances = square_distances.clone();
+        sorted_distances.sort_unstable();
+        let dimensions = points.first().map_or(1, |p| p.dimensions());
+        let zero_count = sorted_distances.partition_point(|d| *d == 0);
+        let duplicate_fraction = if sorted_distances.is_empty() { 0.0 } else { zero_count as f64 / sorted_distances.len() as f64 };
+
+        // The search for the knee runs from lowest_index + noise_skip_by + 1 to point_count - minimum_cluster_count,
+        // so leave room for the largest noise_skip_by and a margin on either side.
+        let minimum_cluster_count = self.get_minimum_cluster_count() as usize;
+        let room = MAX_AUTO_NOISE_SKIP_BY as usize + 2;
+        if point_count < 4 * (minimum_cluster_count + room) {
+            let reason = format!("Only {} points, too few to measure, so the setting is unchanged.", point_count);
+            return AutoConfiguration {
+                finder : self,
+                duplicate_fraction,
+                spikiness : 1.0,
+                knee_spread : 0.0,
+                linkage_square_distance : 0,
+                choices : vec![
+                    choice("lowest_index_for_checking_growth_ratio", self.get_lowest_index_for_checking_growth_ratio() as u64,
+                        defaults.get_lowest_index_for_checking_growth_ratio() as u64, reason.clone()),
+                    choice("noise_skip_by", self.get_noise_skip_by() as u64, defaults.get_noise_skip_by() as u64, reason.clone()),
+                    choice("outlier_cluster_size", self.get_outlier_cluster_size() as u64, defaults.get_outlier_cluster_size() as u64, reason)
+                ]
+            };
+        }
+        let highest_lowest_index = point_count - minimum_cluster_count - room;
+        let mut choices = Vec::with_capacity(3);
+
+        // Duplicates: pass over their zero distances plus half the rest.
+        let lowest_index = min(zero_count + (point_count - zero_count) / 2, highest_lowest_index);
+        let reason = if zero_count == 0 {
+            "No two consecutive points coincide, so half the distances are passed over as usual.".to_string()
+        }
+        else {
+            format!("{:.1}% of the distances are zero because of duplicate points, so those are passed over along with half the rest.", 100.0 * duplicate_fraction)
+        };
+        choices.push(choice("lowest_index_for_checking_growth_ratio", lowest_index as u64, defaults.get_lowest_index_for_checking_growth_ratio() as u64, reason));
+
+        // Noise: the smallest skip that smooths the low end of the curve, which lies inside clusters and should have no knee.
+        // The lowest tenth of the nonzero distances is ignored, because ratios between tiny values are erratic.
+        // When duplicates crowd lowest_index up against its limit, the low end may be empty.
+        let low_end = min(zero_count + (sorted_distances.len() - zero_count) / 10, lowest_index) .. lowest_index;
+        let spikiness_by_skip : Vec<f64> = (0..=MAX_AUTO_NOISE_SKIP_BY)
+            .map(|skip| spikiness(&sorted_distances[low_end.clone()], skip as usize))
+            .collect();
+        let smooth_skip = spikiness_by_skip.iter()
+            .position(|s| *s <= SPIKINESS_TOLERANCE)
+            .unwrap_or_else(|| index_of_minimum(&spikiness_by_skip));
+
+        // Stability: raise the skip until nudging the parameters leaves the linkage distance nearly unchanged.
+        let configure = |skip : usize, index : usize| self.with_noise_skip_by(skip as u16).with_lowest_index_for_checking_growth_ratio(index as u32);
+        let knee_spread_of = |skip : usize| {
+            let linkage = configure(skip, lowest_index).find_linkage_square_distance(point_count, dimensions, &square_distances);
+            let nudged = [
+                (skip.saturating_sub(1), lowest_index),
+                (skip + 1, lowest_index),
+                (skip, lowest_index * 9 / 10),
+                (skip, min(lowest_index * 11 / 10, highest_lowest_index))
+            ];
+            let mut lowest = linkage;
+            let mut highest = linkage;
+            for (nudged_skip, nudged_index) in nudged.iter() {
+                let nudged_linkage = configure(*nudged_skip, *nudged_index).find_linkage_square_distance(point_count, dimensions, &square_distances);
+                lowest = min(lowest, nudged_linkage);
+                highest = max(highest, nudged_linkage);
+            }
+            (highest - lowest) as f64 / max(linkage, 1) as f64
+        };
+        let knee_spreads : Vec<f64> = (smooth_skip..=MAX_AUTO_NOISE_SKIP_BY as usize).map(knee_spread_of).collect();
+        let stable_offset = knee_spreads.iter()
+            .position(|spread| *spread <= KNEE_STABILITY_TOLERANCE)
+            .unwrap_or_else(|| index_of_minimum(&knee_spreads));
+        let noise_skip_by = smooth_skip + stable_offset;
+        let knee_spread = knee_spreads[stable_offset];
+        let spikiness = spikiness_by_skip[noise_skip_by];
+        let smoothing = if smooth_skip == 0 && spikiness_by_skip[0] <= SPIKINESS_TOLERANCE {
+            format!("The low end of the sorted distances is smooth (largest jump {:.1} times the average)", spikiness_by_skip[0])
+        }
+        else if spikiness_by_skip[smooth_skip] <= SPIKINESS_TOLERANCE {
+            format!("Skipping {} smooths the noisy low end of the sorted distances (largest jump {:.1} times the average, down from {:.1})",
+                smooth_skip, spikiness_by_skip[smooth_skip], spikiness_by_skip[0])
+        }
+        else {
+            format!("No skip fully smooths the noisy low end of the sorted distances, but {} does best (largest jump {:.1} times the average)",
+                smooth_skip, spikiness_by_skip[smooth_skip])
+        };
+        let stability = if stable_offset == 0 && knee_spread <= KNEE_STABILITY_TOLERANCE {
+            format!("and nudging the parameters moves the linkage distance by only {:.1}%.", 100.0 * knee_spread)
+        }
+        else if knee_spread <= KNEE_STABILITY_TOLERANCE {
+            format!("but the knee was unstable until raised to {}, where nudging the parameters moves the linkage distance by only {:.1}%.", noise_skip_by, 100.0 * knee_spread)
+        }
+        else {
+            format!("but the knee is unstable; {} minimizes how far nudging the parameters moves the linkage distance ({:.1}%).", noise_skip_by, 100.0 * knee_spread)
+        };
+        choices.push(choice("noise_skip_by", noise_skip_by as u64, defaults.get_noise_skip_by() as u64, format!("{}, {}", smoothing, stability)));
+
+        // Outliers: runs much smaller than the run holding a typical point.
+        let finder = configure(noise_skip_by, lowest_index);
+        let linkage_square_distance = finder.find_linkage_square_distance(point_count, dimensions, &square_distances);
+        let mut run_sizes : Vec<usize> = finder.find_runs(&distances, linkage_square_distance).iter().map(|run| run.len()).collect();
+        run_sizes.sort_unstable();
+        let mut points_so_far = 0;
+        let typical_run_size = run_sizes.iter()
+            .find(|size| { points_so_far += **size; 2 * points_so_far >= point_count })
+            .copied()
+            .unwrap_or(1);
+        let outlier_cluster_size = max(1, (typical_run_size as f64 * OUTLIER_FRACTION_OF_TYPICAL_RUN).round() as u64);
+        let outlier_cluster_size = min(outlier_cluster_size, u16::MAX as u64);
+        let reason = format!("Split at the chosen linkage distance, the median point lies in a run of {} points; runs of no more than {:.0}% of that are outliers.",
+            typical_run_size, 100.0 * OUTLIER_FRACTION_OF_TYPICAL_RUN);
+        choices.push(choice("outlier_cluster_size", outlier_cluster_size, defaults.get_outlier_cluster_size() as u64, reason));
+
+        AutoConfiguration {
+            finder : finder.with_outlier_cluster_size(outlier_cluster_size as u16),
+            duplicate_fraction,
+            spikiness,
+            knee_spread,
+            linkage_square_distance,
+            choices
+        }
+    }
+}
+
+fn choice(parameter : &'static str, value : u64, default_value : u64, reason : String) -> ParameterChoice {
+    ParameterChoice { parameter, value, default_value, reason }
+}
+
+/// Compare the growth in logarithm of sorted distances across `skip + 1` positions: the largest divided by the average.
+///
+/// Returns one if there are too few distances or they do not grow at all.
+fn spikiness(sorted_distances : &[u64], skip : usize) -> f64 {
+    let step = skip + 1;
+    if sorted_distances.len() <= step { return 1.0; }
+    let mut largest = 0.0_f64;
+    let mut total = 0.0;
+    for i in step..sorted_distances.len() {
+        let growth = (max(sorted_distances[i], 1) as f64 / max(sorted_distances[i - step], 1) as f64).ln();
+        largest = largest.max(growth);
+        total += growth;
+    }
+    if total <= 0.0 { return 1.0; }
+    largest / (total / (sorted_distances.len() - step) as f64)
+}
+
+/// Position of the lowest value, the earliest winning any tie.
+fn index_of_minimum(values : &[f64]) -> usize {
+    let mut lowest = 0;
+    for (i, value) in values.iter().enumerate() {
+        if *value < values[lowest] { lowest = i; }
+    }
+    lowest
+}
+
+#[cfg(test)]
+/// Tests of the measurements behind the automatic configuration.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use hilbert::Point;
+    use crate::clustering::single_linkage::SingleLinkage;
+
+    #[test]
+    fn spikiness() {
+        let smooth : Vec<u64> = (1..=100).map(|i| (1000.0 * 1.01_f64.powi(i)) as u64).collect();
+        asserting("Even growth").that(&(super::spikiness(&smooth, 0) < 1.1)).is_equal_to(true);
+        let mut spiky = smooth.clone();
+        for value in spiky.iter_mut().skip(50) { *value = *value * 13 / 10; }
+        let single_step = super::spikiness(&spiky, 0);
+        asserting(&format!("One jump stands out, got {}", single_step)).that(&(single_step > 10.0)).is_equal_to(true);
+        asserting("Skipping dilutes the jump").that(&(super::spikiness(&spiky, 9) < single_step)).is_equal_to(true);
+    }
+
+    /// Grid points in several well separated squares, every point doubled.
+    #[test]
+    fn auto_accounts_for_duplicates() {
+        let mut points = Vec::new();
+        for square in 0..20_u32 {
+            for x in 0..10_u32 {
+                for y in 0..10_u32 {
+                    for _ in 0..2 {
+                        points.push(Point::new(points.len(), &[(square % 5) * 1000 + x * 10, (square / 5) * 1000 + y * 10]));
+                    }
+                }
+            }
+        }
+        let configuration = SingleLinkage::new(points.len() as u32, 12).auto(&points);
+        asserting("Half the distances are zero").that(&(configuration.duplicate_fraction > 0.49)).is_equal_to(true);
+        asserting("Passes over duplicates and half the rest")
+            .that(&configuration.finder.get_lowest_index_for_checking_growth_ratio()).is_equal_to(3000);
+        asserting("Linkage distance below the gap between squares").that(&(configuration.linkage_square_distance < 900 * 900)).is_equal_to(true);
+        asserting("One reason per parameter").that(&configuration.to_string().lines().count()).is_equal_to(3);
+    }
+
+    /// Nearly every point is a duplicate, which pushes the start of the low end past lowest_index.
+    #[test]
+    fn auto_with_mostly_duplicates() {
+        let mut points : Vec<Point> = (0..181).map(|id| Point::new(id, &[500, 500])).collect();
+        for i in 0..19_u32 {
+            points.push(Point::new(points.len(), &[i * 200, (i * 37) % 4000]));
+        }
+        let configuration = SingleLinkage::new(points.len() as u32, 12).auto(&points);
+        asserting("Most distances are zero").that(&(configuration.duplicate_fraction > 0.85)).is_equal_to(true);
+        asserting("Noise skip within bounds")
+            .that(&(configuration.finder.get_noise_skip_by() <= super::MAX_AUTO_NOISE_SKIP_BY)).is_equal_to(true);
+        asserting("One reason per parameter").that(&configuration.to_string().lines().count()).is_equal_to(3);
+    }
+
+    /// Evenly spaced points on a line whose spacing doubles partway through the low end, 
+    /// a single jump that no skip smooths, so the largest skip is chosen.
+    #[test]
+    fn auto_with_unsmoothable_low_end() {
+        let mut points = Vec::new();
+        let mut x = 0_u32;
+        for id in 0..2000_usize {
+            points.push(Point::new(id, &[x]));
+            x += if id < 700 { 10 } else { 20 };
+        }
+        let configuration = SingleLinkage::new(points.len() as u32, 16).auto(&points);
+        asserting("Largest skip").that(&configuration.finder.get_noise_skip_by()).is_equal_to(super::MAX_AUTO_NOISE_SKIP_BY);
+        asserting("One reason per parameter").that(&configuration.to_string().lines().count()).is_equal_to(3);
+    }
+}
@@ -1,6 +1,15 @@
 use std::collections::{HashMap, hash_map::Entry};
+use std::hash::{Hash, BuildHasher};
+use std::fmt::{Debug, Display, Formatter};
 use super::cluster::{Chopped};
 use super::Clustering;
+use super::weights::PointWeights;
+#[cfg(feature = "parallel")]
+use super::parallel;
+
+/// How much larger than the number of members the largest category may be for `BCubed::compare_integers`
+/// to use arrays indexed by category.
+pub const DENSE_CATEGORY_FACTOR : usize = 4;
 
 /// The B-Cubed extrinsic measure of the similarity of two Clusterings. 
 /// 
@@ -93,6 +102,107 @@ pub struct BCubed {
     alpha : f64
 }
 
+// ........................... MemberLabels ..........................................
+
+/// Looks up the label (category) of a member, so that a gold standard need not be built into a `Clustering` to be compared.
+/// 
+/// Implemented for `Clustering`, for a `HashMap` from member to label, and for a slice or `Vec` of labels indexed by a `usize` member.
+/// Labels need not be `Copy`, so owned labels such as `String` may be used.
+pub trait MemberLabels<M : Chopped> {
+    type Label : Clone + Hash + Eq + Debug;
+
+    /// A copy of the label of the member, or None if it has none.
+    fn label_of(&self, member : M) -> Option<Self::Label>;
+
+    /// Number of members that have labels, or None if that cannot be known, as for a slice or `Vec` 
+    /// whose every index holds a label whether or not a member has that index.
+    fn labelled_count(&self) -> Option<usize>;
+}
+
+impl<C : Chopped, M : Chopped, G : Iterator<Item = C>> MemberLabels<M> for Clustering<C,M,G> {
+    type Label = C;
+    fn label_of(&self, member : M) -> Option<C> { self.get_category(member) }
+    fn labelled_count(&self) -> Option<usize> { Some(self.member_count()) }
+}
+
+impl<M : Chopped, L : Clone + Hash + Eq + Debug, S : BuildHasher> MemberLabels<M> for HashMap<M,L,S> {
+    type Label = L;
+    fn label_of(&self, member : M) -> Option<L> { self.get(&member).cloned() }
+    fn labelled_count(&self) -> Option<usize> { Some(self.len()) }
+}
+
+impl<L : Clone + Hash + Eq + Debug> MemberLabels<usize> for [L] {
+    type Label = L;
+    fn label_of(&self, member : usize) -> Option<L> { self.get(member).cloned() }
+    fn labelled_count(&self) -> Option<usize> { None }
+}
+
+impl<L : Clone + Hash + Eq + Debug> MemberLabels<usize> for Vec<L> {
+    type Label = L;
+    fn label_of(&self, member : usize) -> Option<L> { self.get(member).cloned() }
+    fn labelled_count(&self) -> Option<usize> { None }
+}
+
+// ........................... MissingMemberPolicy ..........................................
+
+/// How `BCubed::compare_partial` scores members present in only one of the two Clusterings,
+/// such as noise points dropped by a clusterer or items left out of a partially labelled gold standard.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MissingMemberPolicy {
+    /// Score only the members present in both Clusterings, as though the others had never been clustered.
+    Ignore,
+
+    /// Treat a member missing from either Clustering as though it were there, alone in a cluster of its own.
+    /// Dropping a noise point then costs recall but not precision.
+    Singleton,
+
+    /// Score a member missing from either Clustering as wrong in every respect: its own precision and recall are zero,
+    /// and it counts against the items clustered with it in the other Clustering. 
+    CountAsErrors
+}
+
+// ........................... PartialBCubed ..........................................
+
+/// A `BCubed` measure of two Clusterings that need not have the same members, and how much they have in common.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PartialBCubed {
+    pub bcubed : BCubed,
+
+    /// How the members present in only one Clustering were scored.
+    pub policy : MissingMemberPolicy,
+
+    /// Number of members present in both Clusterings.
+    pub common_count : usize,
+
+    /// Number of members present in the solution but not the gold standard.
+    pub solution_only_count : usize,
+
+    /// Number of members present in the gold standard but not the solution.
+    pub gold_only_count : usize
+}
+
+impl PartialBCubed {
+    /// Fraction of all members present in either Clustering that are present in both.
+    pub fn coverage(&self) -> f64 {
+        let all = self.common_count + self.solution_only_count + self.gold_only_count;
+        if all == 0 { 0.0 } else { self.common_count as f64 / all as f64 }
+    }
+
+    /// Fraction of the gold standard's members that the solution clustered.
+    pub fn gold_coverage(&self) -> f64 {
+        let gold = self.common_count + self.gold_only_count;
+        if gold == 0 { 0.0 } else { self.common_count as f64 / gold as f64 }
+    }
+}
+
+impl Display for PartialBCubed {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "Similarity {:.4} (precision {:.4}, recall {:.4}) with coverage {:.4}: {} members in both, {} only in the solution, {} only in the gold standard, scored by {:?}",
+            self.bcubed.similarity(), self.bcubed.get_precision(), self.bcubed.get_recall(), self.coverage(),
+            self.common_count, self.solution_only_count, self.gold_only_count, self.policy)
+    }
+}
+
 impl BCubed {
     /// Create a BCubed value, knowing all its components. 
     pub fn new(precision : f64, recall : f64, alpha : f64) -> Self {
@@ -118,37 +228,266 @@ impl BCubed {
 
     /// Compare two Clusterings and compute the BCubed value.
     /// 
+    /// The Clusterings must have the same members, but their categories and category generators may differ,
+    /// such as a solution with `usize` categories and a gold standard with `&str` labels.
+    /// 
     ///   - solution - The `Clustering` whose quality is to be assessed.
     ///   - gold_standard - The perfect `Clustering` whose categories are all properly assigned. 
     ///   - alpha - A value between zero and one. used to weight `precision` and `recall`. 
     ///        - If `alpha` is 0.5, `precision` and `recall` are weighted equally. 
     ///        - If `alpha` is zero, only `recall` is used. 
     ///        - If `alpha` is one, only `precision` is used. 
-    pub fn compare<C : Chopped, M : Chopped, G : Iterator<Item = C>>(solution : &Clustering<C,M,G>, gold_standard : &Clustering<C,M,G>, alpha : f64) -> Self {
+    pub fn compare<C1 : Chopped, C2 : Chopped, M : Chopped, G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(solution : &Clustering<C1,M,G1>, gold_standard : &Clustering<C2,M,G2>, alpha : f64) -> Self {
         BCubed::new(
             BCubed::compute_precision(solution, gold_standard), 
             BCubed::compute_recall(solution, gold_standard), 
             alpha)
     }
 
+    /// Compare two Clusterings whose members and categories are small integers, using arrays in place of maps where possible.
+    /// 
+    /// If the members are numbered from zero to one less than their count, and no category exceeds
+    /// `DENSE_CATEGORY_FACTOR` times the number of members, the comparison is made by `compare_dense`.
+    /// Otherwise it falls back to `compare`. The result is the same either way, apart from rounding in the last decimal place.
+    /// 
+    ///   - solution - The `Clustering` whose quality is to be assessed.
+    ///   - gold_standard - The perfect `Clustering` whose categories are all properly assigned. 
+    ///   - alpha - A value between zero and one. used to weight `precision` and `recall`. 
+    pub fn compare_integers<G1 : Iterator<Item = usize>, G2 : Iterator<Item = usize>>(solution : &Clustering<usize,usize,G1>, gold_standard : &Clustering<usize,usize,G2>, alpha : f64) -> Self {
+        match (Self::dense_labels(solution), Self::dense_labels(gold_standard)) {
+            (Some(solution_labels), Some(gold_labels)) if solution_labels.len() == gold_labels.len() => 
+                Self::compare_dense(&solution_labels, &gold_labels, alpha),
+            _ => Self::compare(solution, gold_standard, alpha)
+        }
+    }
+
+    /// The category of each member, indexed by member, if the members and categories are dense enough for `compare_dense`.
+    fn dense_labels<G : Iterator<Item = usize>>(clustering : &Clustering<usize,usize,G>) -> Option<Vec<usize>> {
+        let n = clustering.member_count();
+        let mut labels = vec![0_usize; n];
+        for cluster in clustering.get_clusters().values() {
+            if cluster.get_category() > DENSE_CATEGORY_FACTOR * n { return None; }
+            for member in cluster.get_members() {
+                // Members are distinct, so if none reaches n, they must be exactly 0..n.
+                if *member >= n { return None; }
+                labels[*member] = cluster.get_category();
+            }
+        }
+        Some(labels)
+    }
+
+    /// Compare two clusterings given as the category of each item, indexed by item, and compute the BCubed value.
+    /// 
+    /// This is the fast path for huge clusterings. No maps are used: the items are grouped by solution category
+    /// with a counting sort, and each group is tallied in an array with one slot per gold category, so memory is
+    /// proportional to the number of items plus the number of categories. With the `parallel` feature, the groups
+    /// are tallied concurrently, each thread with its own array. The result is the same as that of `compare`,
+    /// apart from rounding in the last decimal place, as the sum is taken in a different order.
+    /// 
+    ///   - solution_labels - Category of each item in the solution. Categories should be numbered from zero with few gaps,
+    ///     since arrays are sized to the largest.
+    ///   - gold_labels - Category of each item in the gold standard, likewise numbered. 
+    ///   - alpha - A value between zero and one. used to weight `precision` and `recall`. 
+    pub fn compare_dense(solution_labels : &[usize], gold_labels : &[usize], alpha : f64) -> Self {
+        if solution_labels.len() != gold_labels.len() {
+            panic!("The solution has {} items but the gold standard has {}", solution_labels.len(), gold_labels.len());
+        }
+        let n = solution_labels.len() as f64;
+        let table = DenseContingency::new(solution_labels, gold_labels);
+        #[cfg(feature = "parallel")]
+        let (precision_sum, recall_sum) = parallel::dense_bcubed_sums(&table);
+        #[cfg(not(feature = "parallel"))]
+        let (precision_sum, recall_sum) = {
+            let mut scratch = DenseScratch::new(table.category_count());
+            (0..table.cluster_count())
+                .map(|cluster| table.cluster_sums(cluster, &mut scratch))
+                .fold((0.0, 0.0), |(p1, r1), (p2, r2)| (p1 + p2, r1 + r2))
+        };
+        BCubed::new(precision_sum / n, recall_sum / n, alpha)
+    }
+
+    /// Compare two Clusterings and compute the BCubed value as `compare` does, tallying the clusters concurrently.
+    /// 
+    /// Each thread reuses a single map for all the clusters it tallies. The result is the same as that of `compare`,
+    /// apart from rounding in the last decimal place, as the sum is taken in a different order.
+    /// For Clusterings of integers, `compare_integers` is faster still.
+    #[cfg(feature = "parallel")]
+    pub fn compare_parallel<C1, C2, M, G1, G2>(solution : &Clustering<C1,M,G1>, gold_standard : &Clustering<C2,M,G2>, alpha : f64) -> Self 
+    where C1 : Chopped + Send + Sync, C2 : Chopped + Send + Sync, M : Chopped + Send + Sync, 
+          G1 : Iterator<Item = C1> + Sync, G2 : Iterator<Item = C2> + Sync
+    {
+        BCubed::new(
+            parallel::bcubed_precision(solution, gold_standard), 
+            parallel::bcubed_precision(gold_standard, solution), 
+            alpha)
+    }
+
+    /// Compare two Clusterings of weighted items and compute the BCubed value.
+    /// 
+    /// An item of weight W counts as W identical items, so the result equals that of `compare` 
+    /// applied to clusterings where each item is replaced by W copies of itself. 
+    /// This is the natural measure for pre-aggregated records or for representatives of duplicates (see `CollapsedPoints`).
+    /// 
+    ///   - solution - The `Clustering` whose quality is to be assessed.
+    ///   - gold_standard - The perfect `Clustering` whose categories are all properly assigned. 
+    ///   - alpha - A value between zero and one. used to weight `precision` and `recall`. 
+    ///   - weights - Weight of each item, by item id.
+    pub fn compare_weighted<C1 : Chopped, C2 : Chopped, G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(solution : &Clustering<C1,usize,G1>, gold_standard : &Clustering<C2,usize,G2>, alpha : f64, weights : &PointWeights) -> Self {
+        BCubed::new(
+            BCubed::compute_weighted_precision(solution, gold_standard, weights), 
+            BCubed::compute_weighted_precision(gold_standard, solution, weights), 
+            alpha)
+    }
+
+    /// Compare a Clustering to gold standard labels looked up by member and compute the BCubed value.
+    /// 
+    /// This gives the same result as `compare` with a gold standard `Clustering` built from the labels,
+    /// in a single pass over the solution that tallies how many members of each cluster carry each label.
+    /// 
+    ///   - solution - The `Clustering` whose quality is to be assessed.
+    ///   - gold_labels - The perfect label of every member of the solution, such as a `HashMap` or a slice indexed by member.
+    ///     It must label no other members, except that the slots of a slice or `Vec` that no member indexes are ignored.
+    ///   - alpha - A value between zero and one. used to weight `precision` and `recall`. 
+    pub fn compare_to_labels<C : Chopped, M : Chopped, G : Iterator<Item = C>, L : MemberLabels<M> + ?Sized>(solution : &Clustering<C,M,G>, gold_labels : &L, alpha : f64) -> Self {
+        let n = solution.member_count();
+        if let Some(labelled_count) = gold_labels.labelled_count() {
+            if labelled_count != n {
+                panic!("The gold standard labels {} items but the solution has {}", labelled_count, n);
+            }
+        }
+        let mut precision_sum = 0_f64;
+        // For each label, the sum of squares of its counts in each cluster, and its total count.
+        let mut label_tallies : HashMap<L::Label, (u64, u64)> = HashMap::new();
+        for cluster in solution.get_clusters().values() {
+            let mut counts : HashMap<L::Label, u64> = HashMap::new();
+            for m in cluster.get_members() {
+                let label = gold_labels.label_of(*m)
+                    .unwrap_or_else(|| panic!("Item {:?} from the Clustering has no gold standard label", *m));
+                *counts.entry(label).or_insert(0) += 1;
+            }
+            let mut sum_of_squares = 0_u64;
+            for (label, count) in counts {
+                sum_of_squares += count * count;
+                let tally = label_tallies.entry(label).or_insert((0, 0));
+                tally.0 += count * count;
+                tally.1 += count;
+            }
+            precision_sum += sum_of_squares as f64 / cluster.len() as f64;
+        }
+        let recall_sum : f64 = label_tallies.values().map(|(sum_of_squares, size)| *sum_of_squares as f64 / *size as f64).sum();
+        BCubed::new(precision_sum / n as f64, recall_sum / n as f64, alpha)
+    }
+
+    /// Compare two Clusterings that need not have the same members and compute the BCubed value,
+    /// scoring the members present in only one of them as directed by the policy.
+    /// 
+    /// Where `compare` panics on such members, this reports how many there are, so the score may be read alongside the coverage.
+    /// If both Clusterings have the same members, every policy gives the same result as `compare`.
+    /// 
+    ///   - solution - The `Clustering` whose quality is to be assessed.
+    ///   - gold_standard - The perfect `Clustering` whose categories are all properly assigned. 
+    ///   - alpha - A value between zero and one. used to weight `precision` and `recall`. 
+    ///   - policy - How to score members present in only one of the Clusterings.
+    pub fn compare_partial<C1 : Chopped, C2 : Chopped, M : Chopped, G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(solution : &Clustering<C1,M,G1>, gold_standard : &Clustering<C2,M,G2>, alpha : f64, policy : MissingMemberPolicy) -> PartialBCubed {
+        let (precision, solution_only_count, common_count) = Self::compute_partial_precision(solution, gold_standard, policy);
+        let (recall, gold_only_count, _) = Self::compute_partial_precision(gold_standard, solution, policy);
+        PartialBCubed {
+            bcubed : BCubed::new(precision, recall, alpha),
+            policy,
+            common_count,
+            solution_only_count,
+            gold_only_count
+        }
+    }
+
+    /// Compute the BCubed Precision when some members of either Clustering may be missing from the other.
+    /// 
+    /// A member missing from the gold standard falls in no gold category, so in `tally_squares` terms it matches nothing
+    /// but itself (`Singleton`) or nothing at all (`CountAsErrors`). A member missing from the solution is alone in a cluster
+    /// of its own, with a precision of one (`Singleton`) or zero (`CountAsErrors`). Under `Ignore`, both kinds are dropped
+    /// and the clusters shrink to their common members.
+    /// 
+    ///   - returns - The precision, the number of members only in the solution, and the number of members in both.
+    fn compute_partial_precision<C1 : Chopped, C2 : Chopped, M : Chopped, G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(solution : &Clustering<C1,M,G1>, gold_standard : &Clustering<C2,M,G2>, policy : MissingMemberPolicy) -> (f64, usize, usize) {
+        let mut weighted_sum = 0_f64;
+        let mut solution_only_count = 0_usize;
+        let mut tallies = HashMap::new();
+        for cluster in solution.get_clusters().values() {
+            let mut missing_count = 0_u64;
+            let sum_of_squares = Self::tally_squares(
+                cluster.get_members()
+                       .iter()
+                       .filter_map(|m| {
+                           let category = gold_standard.get_category(*m);
+                           if category.is_none() { missing_count += 1; }
+                           category
+                       }),
+                &mut tallies
+            );
+            solution_only_count += missing_count as usize;
+            let (sum_of_squares, magnitude) = match policy {
+                MissingMemberPolicy::Ignore => (sum_of_squares, cluster.len() as u64 - missing_count),
+                MissingMemberPolicy::Singleton => (sum_of_squares + missing_count, cluster.len() as u64),
+                MissingMemberPolicy::CountAsErrors => (sum_of_squares, cluster.len() as u64)
+            };
+            if magnitude > 0 {
+                weighted_sum += sum_of_squares as f64 / magnitude as f64;
+            }
+        }
+        let common_count = solution.member_count() - solution_only_count;
+        let gold_only_count = gold_standard.member_count() - common_count;
+        let n = match policy {
+            MissingMemberPolicy::Ignore => common_count,
+            _ => solution.member_count() + gold_only_count
+        };
+        if policy == MissingMemberPolicy::Singleton {
+            weighted_sum += gold_only_count as f64;
+        }
+        let precision = if n == 0 { 0.0 } else { weighted_sum / n as f64 };
+        (precision, solution_only_count, common_count)
+    }
+
+    /// Compute the BCubed Precision for weighted items.
+    fn compute_weighted_precision<C1 : Chopped, C2 : Chopped, G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(solution : &Clustering<C1,usize,G1>, gold_standard : &Clustering<C2,usize,G2>, weights : &PointWeights) -> f64 {
+        let mut total_weight = 0_f64;
+        let mut weighted_sum = 0_f64;
+        for cluster in solution.get_clusters().values() {
+            let pi_sub_i_weight = weights.cluster_weight(cluster) as f64;
+            let sum_of_squares = Self::tally_weighted_squares(
+                cluster.get_members()
+                       .iter()
+                       .map(|m| (
+                           gold_standard.get_category(*m)
+                               .unwrap_or_else(|| panic!("Item {:?} from one Clustering not present in the other", *m)),
+                           weights.get(*m)
+                       ))
+            );
+            total_weight += pi_sub_i_weight;
+            weighted_sum += sum_of_squares / pi_sub_i_weight;
+        }
+        weighted_sum / total_weight
+    }
+
     /// Compute the BCubed Precision.
-    fn compute_precision<C : Chopped, M : Chopped, G : Iterator<Item = C>>(solution : &Clustering<C,M,G>, gold_standard : &Clustering<C,M,G>) -> f64 {
+    fn compute_precision<C1 : Chopped, C2 : Chopped, M : Chopped, G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(solution : &Clustering<C1,M,G1>, gold_standard : &Clustering<C2,M,G2>) -> f64 {
         let n = solution.member_count() as f64;
         let mut weighted_sum = 0_f64;
+        let mut tallies = HashMap::new();
         for cluster in solution.get_clusters().values() {
             let pi_sub_i_magnitude = cluster.len() as f64;
             let sum_of_squares = Self::tally_squares(
                 cluster.get_members()
                        .iter()
                        .map(|m| gold_standard.get_category(*m)
-                           .expect(&format!("Item {:?} from one Clustering not present in the other", *m)))
+                           .unwrap_or_else(|| panic!("Item {:?} from one Clustering not present in the other", *m))),
+                &mut tallies
             ) as f64;
             weighted_sum += sum_of_squares / pi_sub_i_magnitude;
         }
         weighted_sum / n
     }
     /// Compute the BCubed Recall.
-    fn compute_recall<C : Chopped, M : Chopped, G : Iterator<Item = C>>(solution : &Clustering<C,M,G>, gold_standard : &Clustering<C,M,G>) -> f64 {
+    fn compute_recall<C1 : Chopped, C2 : Chopped, M : Chopped, G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(solution : &Clustering<C1,M,G1>, gold_standard : &Clustering<C2,M,G2>) -> f64 {
         // The computation for Recall is the symmetric with that of Precision; we just swap the 
         // order of the Clusterings.
         Self::compute_precision(gold_standard, solution)
@@ -165,9 +504,11 @@ impl BCubed {
     ///   - Σ Σ g₀(xⱼ,xₗ)
     /// 
     /// It only does so for a single cluster. The caller must loop over all clusters.
-    fn tally_squares<C : Chopped, I : Iterator<Item = C>>(categories : I) -> u64 {
+    /// 
+    /// The `tallies` map is cleared first, so that one map may serve every cluster in turn instead of allocating a new one for each.
+    pub(crate) fn tally_squares<C : Chopped, I : Iterator<Item = C>>(categories : I, tallies : &mut HashMap<C, u64>) -> u64 {
+        tallies.clear();
         let mut sum_of_squares = 0_u64;
-        let mut tallies : HashMap<C, u64> = HashMap::new();
         for category in categories {
             match tallies.entry(category) {
                 Entry::Occupied(mut entry) => {
@@ -183,6 +524,89 @@ impl BCubed {
         }
         sum_of_squares
     }
+
+    /// Sum the weights of the items in each category, and sum the squares of those sums. 
+    /// 
+    /// This is `tally_squares` where each item counts as many times as its weight.
+    fn tally_weighted_squares<C : Chopped, I : Iterator<Item = (C, u64)>>(weighted_categories : I) -> f64 {
+        let mut tallies : HashMap<C, f64> = HashMap::new();
+        for (category, weight) in weighted_categories {
+            *tallies.entry(category).or_insert(0.0) += weight as f64;
+        }
+        tallies.values().map(|tally| tally * tally).sum()
+    }
+}
+
+
+// ........................... DenseContingency ..........................................
+
+/// Items grouped by solution category, holding the gold category of each, for `BCubed::compare_dense`.
+pub(crate) struct DenseContingency {
+    /// Where the gold categories of the items of each solution category start in `gold_by_cluster`.
+    /// There is one more offset than there are solution categories.
+    cluster_offsets : Vec<usize>,
+
+    /// Gold category of each item, grouped by solution category.
+    gold_by_cluster : Vec<usize>,
+
+    /// Number of items in each gold category.
+    gold_sizes : Vec<u64>
+}
+
+/// Per-thread working storage for `DenseContingency::cluster_sums`: a count for each gold category,
+/// and which counts are not zero, so that only those need resetting.
+pub(crate) struct DenseScratch {
+    counts : Vec<u64>,
+    touched : Vec<usize>
+}
+
+impl DenseScratch {
+    pub(crate) fn new(category_count : usize) -> Self { DenseScratch { counts : vec![0; category_count], touched : Vec::new() } }
+}
+
+impl DenseContingency {
+    fn new(solution_labels : &[usize], gold_labels : &[usize]) -> Self {
+        let cluster_count = solution_labels.iter().max().map_or(0, |largest| largest + 1);
+        let category_count = gold_labels.iter().max().map_or(0, |largest| largest + 1);
+        let mut gold_sizes = vec![0_u64; category_count];
+        for gold in gold_labels { gold_sizes[*gold] += 1; }
+        // Counting sort of the items by solution category.
+        let mut cluster_offsets = vec![0_usize; cluster_count + 1];
+        for cluster in solution_labels { cluster_offsets[cluster + 1] += 1; }
+        for i in 1..cluster_offsets.len() { cluster_offsets[i] += cluster_offsets[i - 1]; }
+        let mut next = cluster_offsets.clone();
+        let mut gold_by_cluster = vec![0_usize; gold_labels.len()];
+        for (cluster, gold) in solution_labels.iter().zip(gold_labels.iter()) {
+            gold_by_cluster[next[*cluster]] = *gold;
+            next[*cluster] += 1;
+        }
+        DenseContingency { cluster_offsets, gold_by_cluster, gold_sizes }
+    }
+
+    pub(crate) fn cluster_count(&self) -> usize { self.cluster_offsets.len() - 1 }
+
+    pub(crate) fn category_count(&self) -> usize { self.gold_sizes.len() }
+
+    /// The terms of one solution category in the precision and recall sums.
+    /// An item count of n shared with a gold category contributes n² divided by the size of the solution category
+    /// to the precision, and n² divided by the size of the gold category to the recall.
+    pub(crate) fn cluster_sums(&self, cluster : usize, scratch : &mut DenseScratch) -> (f64, f64) {
+        let golds = &self.gold_by_cluster[self.cluster_offsets[cluster]..self.cluster_offsets[cluster + 1]];
+        if golds.is_empty() { return (0.0, 0.0); }
+        for gold in golds {
+            if scratch.counts[*gold] == 0 { scratch.touched.push(*gold); }
+            scratch.counts[*gold] += 1;
+        }
+        let mut sum_of_squares = 0_u64;
+        let mut recall_sum = 0_f64;
+        for gold in scratch.touched.drain(..) {
+            let square = scratch.counts[gold] * scratch.counts[gold];
+            sum_of_squares += square;
+            recall_sum += square as f64 / self.gold_sizes[gold] as f64;
+            scratch.counts[gold] = 0;
+        }
+        (sum_of_squares as f64 / golds.len() as f64, recall_sum)
+    }
 }
 
 
@@ -329,6 +753,129 @@ mod tests {
 
     }
 
-    
+    /// A weighted item must count the same as the same number of identical unweighted items.
+    #[test]
+    fn weighted_matches_expanded() {
+        use crate::clustering::weights::PointWeights;
+        // Item 100 stands for items 100, 101 and 102; item 200 stands for items 200 and 201.
+        let solution = clustering::from_delimited_string("1,2,100;3,200");
+        let gold = clustering::from_delimited_string("1,2,3;100,200");
+        let weights = PointWeights::from_pairs(vec![(100, 3), (200, 2)]);
+        let expanded_solution = clustering::from_delimited_string("1,2,100,101,102;3,200,201");
+        let expanded_gold = clustering::from_delimited_string("1,2,3;100,101,102,200,201");
+
+        let weighted = BCubed::compare_weighted(&solution, &gold, 0.5, &weights);
+        let expanded = BCubed::compare(&expanded_solution, &expanded_gold, 0.5);
+        asserting("Same precision").that(&((weighted.get_precision() - expanded.get_precision()).abs() < 1e-12)).is_equal_to(true);
+        asserting("Same recall").that(&((weighted.get_recall() - expanded.get_recall()).abs() < 1e-12)).is_equal_to(true);
+
+        let unweighted = BCubed::compare_weighted(&solution, &gold, 0.5, &PointWeights::new());
+        asserting("Uniform weights match compare").that(&unweighted).is_equal_to(BCubed::compare(&solution, &gold, 0.5));
+    }
+
+    /// Categories of different types, and labels held outside a `Clustering`, must give the same result as `compare`.
+    #[test]
+    fn compare_to_labels_of_other_types() {
+        use std::collections::HashMap;
+        let solution = clustering::from_delimited_string("0,1,2,3;4,5,6;7,8,9,10,11,12,13");
+        let gold = clustering::from_delimited_string("0,1,2,3,4;5,6,8,11,12,13;7;9;10");
+        let expected = BCubed::compare(&solution, &gold, 0.5);
+
+        let names = ["red", "blue", "green", "white", "black"];
+        let mut labels = vec![""; 14];
+        for cluster in gold.get_clusters().values() {
+            for member in cluster.get_members() { labels[*member] = names[cluster.get_category()]; }
+        }
+        let mut named_gold = clustering::Clustering::empty(names.iter().copied());
+        for (member, label) in labels.iter().enumerate() {
+            if named_gold.contains_category(label) { named_gold.add_to_cluster(member, label).unwrap(); }
+            else { named_gold.add_to_new_cluster(member).unwrap(); }
+        }
+        let close = |a : BCubed| (a.get_precision() - expected.get_precision()).abs() < 1e-12 && (a.get_recall() - expected.get_recall()).abs() < 1e-12;
+        asserting("Clustering with &str categories").that(&close(BCubed::compare(&solution, &named_gold, 0.5))).is_equal_to(true);
+        asserting("Vec of labels").that(&close(BCubed::compare_to_labels(&solution, &labels, 0.5))).is_equal_to(true);
+        asserting("Slice of labels").that(&close(BCubed::compare_to_labels(&solution, &labels[..], 0.5))).is_equal_to(true);
+        let map : HashMap<usize, &str> = labels.iter().copied().enumerate().collect();
+        asserting("HashMap of labels").that(&close(BCubed::compare_to_labels(&solution, &map, 0.5))).is_equal_to(true);
+        asserting("Clustering as labels").that(&close(BCubed::compare_to_labels(&solution, &gold, 0.5))).is_equal_to(true);
+        let owned : HashMap<usize, String> = labels.iter().map(|label| label.to_string()).enumerate().collect();
+        asserting("HashMap of String labels").that(&close(BCubed::compare_to_labels(&solution, &owned, 0.5))).is_equal_to(true);
+        let owned : Vec<String> = labels.iter().map(|label| label.to_string()).collect();
+        asserting("Vec of String labels").that(&close(BCubed::compare_to_labels(&solution, &owned, 0.5))).is_equal_to(true);
+    }
+
+    /// Members need not use every index of a Vec of labels; the labels in unused slots are ignored.
+    #[test]
+    fn compare_to_labels_with_sparse_members() {
+        let solution = clustering::from_delimited_string("0,2,4;6,8;10,12,14,16");
+        let gold = clustering::from_delimited_string("0,2;4,6,8;10,12;14,16");
+        let expected = BCubed::compare(&solution, &gold, 0.5);
+        let mut labels = vec![99_usize; 17];
+        for cluster in gold.get_clusters().values() {
+            for member in cluster.get_members() { labels[*member] = cluster.get_category(); }
+        }
+        let actual = BCubed::compare_to_labels(&solution, &labels, 0.5);
+        asserting("Precision").that(&(actual.get_precision() - expected.get_precision()).abs()).is_less_than(1e-12);
+        asserting("Recall").that(&(actual.get_recall() - expected.get_recall()).abs()).is_less_than(1e-12);
+    }
+
+    /// The array-based and parallel comparisons must agree with `compare`.
+    #[test]
+    fn fast_paths_match_compare() {
+        let solution = clustering::from_delimited_string("0,1,2,3;4,5,6;7,8,9,10,11,12,13");
+        let gold = clustering::from_delimited_string("0,1,2,3,4;5,6,8,11,12,13;7;9;10");
+        let expected = BCubed::compare(&solution, &gold, 0.5);
+        let close = |a : BCubed| (a.get_precision() - expected.get_precision()).abs() < 1e-12 && (a.get_recall() - expected.get_recall()).abs() < 1e-12;
+        asserting("Integers").that(&close(BCubed::compare_integers(&solution, &gold, 0.5))).is_equal_to(true);
+        let mut solution_labels = vec![0; 14];
+        let mut gold_labels = vec![0; 14];
+        for cluster in solution.get_clusters().values() { for member in cluster.get_members() { solution_labels[*member] = cluster.get_category(); } }
+        for cluster in gold.get_clusters().values() { for member in cluster.get_members() { gold_labels[*member] = cluster.get_category(); } }
+        asserting("Dense").that(&close(BCubed::compare_dense(&solution_labels, &gold_labels, 0.5))).is_equal_to(true);
+        #[cfg(feature = "parallel")]
+        asserting("Parallel").that(&close(BCubed::compare_parallel(&solution, &gold, 0.5))).is_equal_to(true);
+
+        // Members that do not run from zero fall back to compare.
+        let sparse_solution = clustering::from_delimited_string("1,2,3;4,5,6,100");
+        let sparse_gold = clustering::from_delimited_string("1,2,3,4;5,6,100");
+        asserting("Sparse").that(&BCubed::compare_integers(&sparse_solution, &sparse_gold, 0.5)).is_equal_to(BCubed::compare(&sparse_solution, &sparse_gold, 0.5));
+    }
 
+    /// Each policy must agree with `compare` applied to Clusterings altered to have the same members.
+    #[test]
+    fn partial_policies_match_equivalent_full_comparisons() {
+        use crate::clustering::bcubed::MissingMemberPolicy;
+        // The solution dropped noise points 7 and 8; the gold standard did not label 9.
+        let solution = clustering::from_delimited_string("1,2,3;4,5,6,9");
+        let gold = clustering::from_delimited_string("1,2,7;3,4,5,6;8");
+        let close = |a : BCubed, b : BCubed| (a.get_precision() - b.get_precision()).abs() < 1e-12 && (a.get_recall() - b.get_recall()).abs() < 1e-12;
+
+        let ignored = BCubed::compare_partial(&solution, &gold, 0.5, MissingMemberPolicy::Ignore);
+        let common_only = BCubed::compare(
+            &clustering::from_delimited_string("1,2,3;4,5,6"),
+            &clustering::from_delimited_string("1,2;3,4,5,6"), 0.5);
+        asserting(&format!("Ignore {:?}", ignored)).that(&close(ignored.bcubed, common_only)).is_equal_to(true);
+
+        let singletons = BCubed::compare_partial(&solution, &gold, 0.5, MissingMemberPolicy::Singleton);
+        let padded = BCubed::compare(
+            &clustering::from_delimited_string("1,2,3;4,5,6,9;7;8"),
+            &clustering::from_delimited_string("1,2,7;3,4,5,6;8;9"), 0.5);
+        asserting(&format!("Singleton {:?}", singletons)).that(&close(singletons.bcubed, padded)).is_equal_to(true);
+
+        let errors = BCubed::compare_partial(&solution, &gold, 0.5, MissingMemberPolicy::CountAsErrors);
+        asserting("Errors cost precision").that(&(errors.bcubed.get_precision() < singletons.bcubed.get_precision())).is_equal_to(true);
+        asserting("Errors cost recall").that(&(errors.bcubed.get_recall() < singletons.bcubed.get_recall())).is_equal_to(true);
+
+        asserting("Common").that(&errors.common_count).is_equal_to(6);
+        asserting("Solution only").that(&errors.solution_only_count).is_equal_to(1);
+        asserting("Gold only").that(&errors.gold_only_count).is_equal_to(2);
+        asserting("Coverage").that(&errors.coverage()).is_equal_to(6.0 / 9.0);
+        asserting("Gold coverage").that(&errors.gold_coverage()).is_equal_to(6.0 / 8.0);
+
+        for policy in [MissingMemberPolicy::Ignore, MissingMemberPolicy::Singleton, MissingMemberPolicy::CountAsErrors].iter() {
+            let same = BCubed::compare_partial(&solution, &solution, 0.5, *policy);
+            asserting(&format!("{:?} with identical members", policy)).that(&close(same.bcubed, BCubed::new(1.0, 1.0, 0.5))).is_equal_to(true);
+            asserting("Full coverage").that(&same.coverage()).is_equal_to(1.0);
+        }
+    }
 }
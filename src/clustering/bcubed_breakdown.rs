@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use super::cluster::Chopped;
+use super::Clustering;
+use super::bcubed::BCubed;
+
+// ........................... ItemScore ..........................................
+
+/// The B-Cubed precision and recall of a single item.
+///
+/// If the item is in solution cluster S and gold standard category G, its precision is the fraction of S that shares G,
+/// and its recall is the fraction of G that shares S. The global precision and recall are the averages of these over all items.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ItemScore<C1 : Chopped, C2 : Chopped, M : Chopped> {
+    pub member : M,
+
+    /// Category of the solution cluster holding the item.
+    pub solution_category : C1,
+
+    /// Category of the gold standard holding the item.
+    pub gold_category : C2,
+
+    pub precision : f64,
+    pub recall : f64
+}
+
+// ........................... ClusterScore ..........................................
+
+/// The average B-Cubed precision and recall of the members of one cluster, and how its members are divided
+/// among the categories (of type `O`) of the other `Clustering`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusterScore<C : Chopped, O : Chopped> {
+    /// Category of the cluster being scored.
+    pub category : C,
+
+    /// Number of members.
+    pub size : usize,
+
+    /// Average precision of the members.
+    pub precision : f64,
+
+    /// Average recall of the members.
+    pub recall : f64,
+
+    /// Each category of the other `Clustering` that holds some of the members, with the number it holds, most first.
+    /// For a solution cluster, these are the gold categories it mixes. For a gold category, these are the
+    /// solution clusters it is fragmented into.
+    pub overlaps : Vec<(O, usize)>
+}
+
+impl<C : Chopped, O : Chopped> ClusterScore<C, O> {
+    /// True if all the members fall in a single category of the other `Clustering`.
+    pub fn is_pure(&self) -> bool { self.overlaps.len() == 1 }
+}
+
+// ........................... BCubedBreakdown ..........................................
+
+/// A B-Cubed comparison broken down by item, by solution cluster and by gold standard category, for finding
+/// which clusters are impure (low precision) and which gold categories are fragmented (low recall).
+///
+/// Every list is sorted worst first:
+///   - items - By ascending F-measure of the item's precision and recall (using the same `alpha`).
+///   - solution_clusters - By ascending precision, then ascending recall.
+///   - gold_categories - By ascending recall, then ascending precision.
+///
+/// Ties are broken by member or category, so the order is repeatable.
+#[derive(Clone, Debug)]
+pub struct BCubedBreakdown<C1 : Chopped, C2 : Chopped, M : Chopped> {
+    /// The global measure, identical to that returned by `BCubed::compare`.
+    pub overall : BCubed,
+    pub items : Vec<ItemScore<C1,C2,M>>,
+    pub solution_clusters : Vec<ClusterScore<C1,C2>>,
+    pub gold_categories : Vec<ClusterScore<C2,C1>>
+}
+
+impl BCubed {
+    /// Compare two Clusterings as `compare` does, but also score each item, each solution cluster and each gold category.
+    ///
+    /// Runs in time linear in the number of items, plus the time to sort the results.
+    ///
+    ///   - solution - The `Clustering` whose quality is to be assessed.
+    ///   - gold_standard - The perfect `Clustering` whose categories are all properly assigned.
+    ///   - alpha - A value between zero and one. used to weight `precision` and `recall`.
+    pub fn compare_detailed<C1 : Chopped, C2 : Chopped, M : Chopped, G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(solution : &Clustering<C1,M,G1>, gold_standard : &Clustering<C2,M,G2>, alpha : f64) -> BCubedBreakdown<C1,C2,M> {
+        // Number of members shared by each pair of solution cluster and gold category.
+        let mut overlap_counts : HashMap<(C1, C2), usize> = HashMap::new();
+        let mut items = Vec::with_capacity(solution.member_count());
+        for cluster in solution.get_clusters().values() {
+            for member in cluster.get_members() {
+                let gold_category = gold_standard.get_category(*member)
+                    .unwrap_or_else(|| panic!("Item {:?} from one Clustering not present in the other", *member));
+                *overlap_counts.entry((cluster.get_category(), gold_category)).or_insert(0) += 1;
+                items.push(ItemScore { member : *member, solution_category : cluster.get_category(), gold_category, precision : 0.0, recall : 0.0 });
+            }
+        }
+        let solution_size = |category : C1| solution.get_cluster(category).map_or(0, |cluster| cluster.len()) as f64;
+        let gold_size = |category : C2| gold_standard.get_cluster(category).map_or(0, |cluster| cluster.len()) as f64;
+        for item in items.iter_mut() {
+            let overlap = overlap_counts[&(item.solution_category, item.gold_category)] as f64;
+            item.precision = overlap / solution_size(item.solution_category);
+            item.recall = overlap / gold_size(item.gold_category);
+        }
+        let f_measure = |item : &ItemScore<C1,C2,M>| BCubed::new(item.precision, item.recall, alpha).similarity();
+        items.sort_by(|a, b| f_measure(a).partial_cmp(&f_measure(b)).unwrap_or(Ordering::Equal).then(a.member.cmp(&b.member)));
+
+        let mut solution_clusters = Self::score_clusters(&items, |item| item.solution_category, |item| item.gold_category);
+        solution_clusters.sort_by(|a, b| Self::worst_first(a.precision, b.precision)
+            .then(Self::worst_first(a.recall, b.recall))
+            .then(a.category.cmp(&b.category)));
+        let mut gold_categories = Self::score_clusters(&items, |item| item.gold_category, |item| item.solution_category);
+        gold_categories.sort_by(|a, b| Self::worst_first(a.recall, b.recall)
+            .then(Self::worst_first(a.precision, b.precision))
+            .then(a.category.cmp(&b.category)));
+
+        BCubedBreakdown {
+            overall : BCubed::compare(solution, gold_standard, alpha),
+            items,
+            solution_clusters,
+            gold_categories
+        }
+    }
+
+    /// Average the item scores by the category returned by `own`, and count how those items are divided among
+    /// the categories returned by `other`.
+    fn score_clusters<C1 : Chopped, C2 : Chopped, M : Chopped, C : Chopped, O : Chopped, F1, F2>(items : &[ItemScore<C1,C2,M>], own : F1, other : F2) -> Vec<ClusterScore<C,O>>
+    where F1 : Fn(&ItemScore<C1,C2,M>) -> C, F2 : Fn(&ItemScore<C1,C2,M>) -> O
+    {
+        let mut scores : HashMap<C, ClusterScore<C,O>> = HashMap::new();
+        let mut overlap_counts : HashMap<C, HashMap<O, usize>> = HashMap::new();
+        for item in items {
+            let score = scores.entry(own(item))
+                .or_insert_with(|| ClusterScore { category : own(item), size : 0, precision : 0.0, recall : 0.0, overlaps : Vec::new() });
+            score.size += 1;
+            score.precision += item.precision;
+            score.recall += item.recall;
+            *overlap_counts.entry(own(item)).or_default().entry(other(item)).or_insert(0) += 1;
+        }
+        scores.into_iter()
+            .map(|(category, mut score)| {
+                score.precision /= score.size as f64;
+                score.recall /= score.size as f64;
+                score.overlaps = overlap_counts.remove(&category).unwrap_or_default().into_iter().collect();
+                score.overlaps.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+                score
+            })
+            .collect()
+    }
+
+    fn worst_first(a : f64, b : f64) -> Ordering { a.partial_cmp(&b).unwrap_or(Ordering::Equal) }
+}
+
+impl<C1 : Chopped, C2 : Chopped, M : Chopped> Display for BCubedBreakdown<C1, C2, M> {
+    /// The overall measure, then one line per solution cluster listing the gold categories it mixes,
+    /// then one line per gold category listing the solution clusters it is split among, each worst first.
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        writeln!(f, "Similarity {:.4}, precision {:.4}, recall {:.4}",
+            self.overall.similarity(), self.overall.get_precision(), self.overall.get_recall())?;
+        writeln!(f, "Solution clusters, least pure first:")?;
+        for score in self.solution_clusters.iter() {
+            write_cluster_score(f, score, "mixes gold")?;
+        }
+        writeln!(f, "Gold categories, most fragmented first:")?;
+        for score in self.gold_categories.iter() {
+            write_cluster_score(f, score, "split among clusters")?;
+        }
+        Ok(())
+    }
+}
+
+fn write_cluster_score<C : Chopped, O : Chopped>(f: &mut Formatter, score : &ClusterScore<C,O>, relation : &str) -> std::fmt::Result {
+    let overlaps : Vec<String> = score.overlaps.iter().map(|(category, count)| format!("{:?} ({})", category, count)).collect();
+    writeln!(f, "  {:?}: {} members, precision {:.4}, recall {:.4}, {} {}",
+        score.category, score.size, score.precision, score.recall, relation, overlaps.join(", "))
+}
+
+#[cfg(test)]
+/// Tests of the B-Cubed breakdown.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use crate::clustering;
+    use crate::clustering::bcubed::BCubed;
+
+    /// The averages of the item scores must equal the global precision and recall.
+    #[test]
+    fn items_average_to_overall() {
+        let solution = clustering::from_delimited_string("1,2,3,4;5,6,7;8,9,10,11,12,13,14");
+        let gold = clustering::from_delimited_string("1,2,3,4,5;6,7,9,12,13,14;8;10;11");
+        let breakdown = BCubed::compare_detailed(&solution, &gold, 0.5);
+        let n = breakdown.items.len() as f64;
+        let precision : f64 = breakdown.items.iter().map(|item| item.precision).sum::<f64>() / n;
+        let recall : f64 = breakdown.items.iter().map(|item| item.recall).sum::<f64>() / n;
+        asserting("Item count").that(&breakdown.items.len()).is_equal_to(14);
+        asserting("Precision").that(&((precision - breakdown.overall.get_precision()).abs() < 1e-12)).is_equal_to(true);
+        asserting("Recall").that(&((recall - breakdown.overall.get_recall()).abs() < 1e-12)).is_equal_to(true);
+        asserting("Overall matches compare").that(&breakdown.overall).is_equal_to(BCubed::compare(&solution, &gold, 0.5));
+    }
+
+    /// Solution cluster 2 mixes gold categories, and gold category 1 is fragmented, so each is listed first.
+    #[test]
+    fn worst_first() {
+        // Solution clusters: 0 = {1,2,3}, 1 = {4,5}, 2 = {6,7,8,9}
+        // Gold categories:   0 = {1,2,3,4,5}, 1 = {6,7}, 2 = {8,9}
+        let solution = clustering::from_delimited_string("1,2,3;4,5;6,7,8,9");
+        let gold = clustering::from_delimited_string("1,2,3,4,5;6,7;8,9");
+        let breakdown = BCubed::compare_detailed(&solution, &gold, 0.5);
+
+        let impure = &breakdown.solution_clusters[0];
+        asserting("Least pure cluster").that(&impure.category).is_equal_to(2);
+        asserting("Mixes two gold categories").that(&impure.overlaps).is_equal_to(vec![(1, 2), (2, 2)]);
+        asserting("Precision").that(&impure.precision).is_equal_to(0.5);
+        asserting("Other clusters are pure").that(&breakdown.solution_clusters[1..].iter().all(|score| score.is_pure())).is_equal_to(true);
+
+        let fragmented = &breakdown.gold_categories[0];
+        asserting("Most fragmented category").that(&fragmented.category).is_equal_to(0);
+        asserting("Split three and two").that(&fragmented.overlaps).is_equal_to(vec![(0, 3), (1, 2)]);
+        asserting("Recall").that(&((fragmented.recall - 13.0 / 25.0).abs() < 1e-12)).is_equal_to(true);
+
+        let worst_item = breakdown.items[0];
+        // Items 4 and 5 have precision 1 and recall 2/5, so their F-measure of 4/7 is below the 2/3 of the impure cluster's items.
+        asserting("Worst item is split from its category").that(&worst_item.member).is_equal_to(4);
+        let report = format!("{}", breakdown);
+        asserting("Report lists the mix").that(&report.contains("2: 4 members, precision 0.5000, recall 1.0000, mixes gold 1 (2), 2 (2)")).is_equal_to(true);
+    }
+}
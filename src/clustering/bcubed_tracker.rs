@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::fmt::Debug;
+use super::cluster::Chopped;
+use super::Clustering;
+use super::bcubed::{BCubed, MemberLabels};
+
+/// Number of members of one cluster (or gold category) and the sum of the squares of the number
+/// they share with each category of the other clustering, as computed by `BCubed::tally_squares`.
+struct ClusterTally<L : Clone + Hash + Eq + Debug> {
+    /// Number of members of the cluster with each gold label.
+    label_counts : HashMap<L, u64>,
+    sum_of_squares : u64,
+    size : u64
+}
+
+impl<L : Clone + Hash + Eq + Debug> ClusterTally<L> {
+    fn new() -> Self { ClusterTally { label_counts : HashMap::new(), sum_of_squares : 0, size : 0 } }
+}
+
+/// The cluster's term in the B-Cubed sum, before dividing by the number of items.
+fn term(sum_of_squares : u64, size : u64) -> f64 {
+    if size == 0 { 0.0 } else { sum_of_squares as f64 / size as f64 }
+}
+
+// ........................... BCubedTracker ..........................................
+
+/// Owns a `Clustering` as it evolves and keeps its `BCubed` measure against a gold standard up to date,
+/// so that quality can be followed merge by merge during agglomeration.
+///
+/// Recomputing `BCubed::compare` after every change costs time linear in the number of items. The tracker keeps the
+/// quantities that `tally_squares` would compute (the sum of squares of each solution cluster and of each gold category),
+/// and changes them by the amounts each operation adds or subtracts:
+///   - add_to_new_cluster, add_to_cluster, remove_item, move_item - constant time.
+///   - merge - time proportional to the number of gold labels in the smaller of the two clusters.
+///
+/// Only the members currently in the `Clustering` are scored, as with `MissingMemberPolicy::Ignore`,
+/// so members may be added one at a time or removed as noise.
+/// Precision and recall are kept as running sums of fractions, so they may drift from those of `compare`
+/// in the last few decimal places after millions of changes.
+pub struct BCubedTracker<'a, C : Chopped, M : Chopped, G : Iterator<Item = C>, L : MemberLabels<M> + ?Sized> {
+    clustering : Clustering<C,M,G>,
+
+    /// Gold standard label of every member.
+    gold_standard : &'a L,
+
+    /// Tally for each solution cluster of the gold labels of its members.
+    cluster_tallies : HashMap<C, ClusterTally<L::Label>>,
+
+    /// For each gold label, the sum of squares of the number of its members in each solution cluster, and its number of members.
+    label_tallies : HashMap<L::Label, (u64, u64)>,
+
+    /// Sum of the precision terms of all clusters.
+    precision_sum : f64,
+
+    /// Sum of the recall terms of all gold categories.
+    recall_sum : f64
+}
+
+impl<'a, C : Chopped, M : Chopped, G : Iterator<Item = C>, L : MemberLabels<M> + ?Sized> BCubedTracker<'a, C, M, G, L> {
+    /// Begin tracking a `Clustering`, which may already hold some members.
+    ///
+    ///   - clustering - The `Clustering` to change and measure.
+    ///   - gold_standard - The perfect label of every member that will ever be added, such as a gold standard `Clustering`.
+    pub fn new(clustering : Clustering<C,M,G>, gold_standard : &'a L) -> Self {
+        let mut tracker = BCubedTracker {
+            clustering,
+            gold_standard,
+            cluster_tallies : HashMap::new(),
+            label_tallies : HashMap::new(),
+            precision_sum : 0.0,
+            recall_sum : 0.0
+        };
+        let members : Vec<(M, C)> = tracker.clustering.get_clusters().values()
+            .flat_map(|cluster| cluster.get_members().iter().map(move |member| (*member, cluster.get_category())))
+            .collect();
+        for (member, category) in members {
+            tracker.tally_add(member, category);
+        }
+        tracker
+    }
+
+    /// The `BCubed` measure of the members currently clustered.
+    pub fn bcubed(&self, alpha : f64) -> BCubed {
+        let n = self.clustering.member_count() as f64;
+        if n == 0.0 { BCubed::new(0.0, 0.0, alpha) }
+        else { BCubed::new(self.precision_sum / n, self.recall_sum / n, alpha) }
+    }
+
+    /// The `Clustering` being tracked.
+    pub fn get_clustering(&self) -> &Clustering<C,M,G> { &self.clustering }
+
+    /// Stop tracking and return the `Clustering`.
+    pub fn into_clustering(self) -> Clustering<C,M,G> { self.clustering }
+
+    /// Same as `Clustering::add_to_new_cluster`.
+    pub fn add_to_new_cluster(&mut self, item : M) -> Result<C,C> {
+        let result = self.clustering.add_to_new_cluster(item);
+        if let Ok(category) = result { self.tally_add(item, category); }
+        result
+    }
+
+    /// Same as `Clustering::add_to_cluster`.
+    pub fn add_to_cluster(&mut self, item : M, category : C) -> Result<C,C> {
+        let result = self.clustering.add_to_cluster(item, category);
+        if result.is_ok() { self.tally_add(item, category); }
+        result
+    }
+
+    /// Same as `Clustering::merge`.
+    pub fn merge(&mut self, item1 : M, item2 : M) -> bool {
+        let categories = (self.clustering.get_category(item1), self.clustering.get_category(item2));
+        if !self.clustering.merge(item1, item2) { return false; }
+        match categories {
+            (Some(category1), Some(category2)) => self.tally_merge(category1, category2),
+            (Some(category1), None) => self.tally_add(item2, category1),
+            (None, Some(category2)) => self.tally_add(item1, category2),
+            (None, None) => {
+                let category = self.clustering.get_category(item1).unwrap();
+                self.tally_add(item1, category);
+                if item2 != item1 { self.tally_add(item2, category); }
+            }
+        }
+        true
+    }
+
+    /// Same as `Clustering::remove_item`.
+    pub fn remove_item(&mut self, item : M) -> bool {
+        match self.clustering.get_category(item) {
+            Some(category) => {
+                self.clustering.remove_item(item);
+                self.tally_remove(item, category);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Same as `Clustering::move_item`.
+    pub fn move_item(&mut self, item : M, new_category : C) -> bool {
+        let old_category = self.clustering.get_category(item);
+        if !self.clustering.move_item(item, new_category) { return false; }
+        if let Some(category) = old_category { self.tally_remove(item, category); }
+        self.tally_add(item, new_category);
+        true
+    }
+
+    fn gold_label(&self, item : M) -> L::Label {
+        self.gold_standard.label_of(item)
+            .unwrap_or_else(|| panic!("Item {:?} from the Clustering has no gold standard label", item))
+    }
+
+    /// Count an item newly added to a cluster.
+    fn tally_add(&mut self, item : M, category : C) {
+        let label = self.gold_label(item);
+        let tally = self.cluster_tallies.entry(category).or_insert_with(ClusterTally::new);
+        self.precision_sum -= term(tally.sum_of_squares, tally.size);
+        let count = tally.label_counts.entry(label.clone()).or_insert(0);
+        // Going from a count of c to c + 1 adds (c + 1)² - c² = 2c + 1 to the sum of squares.
+        let increase = 2 * *count + 1;
+        *count += 1;
+        tally.sum_of_squares += increase;
+        tally.size += 1;
+        self.precision_sum += term(tally.sum_of_squares, tally.size);
+
+        let (sum_of_squares, size) = self.label_tallies.entry(label).or_insert((0, 0));
+        self.recall_sum -= term(*sum_of_squares, *size);
+        *sum_of_squares += increase;
+        *size += 1;
+        self.recall_sum += term(*sum_of_squares, *size);
+    }
+
+    /// Stop counting an item removed from a cluster.
+    fn tally_remove(&mut self, item : M, category : C) {
+        let label = self.gold_label(item);
+        let tally = self.cluster_tallies.get_mut(&category).unwrap_or_else(|| panic!("No tally for category {:?}", category));
+        self.precision_sum -= term(tally.sum_of_squares, tally.size);
+        let count = tally.label_counts.get_mut(&label).unwrap_or_else(|| panic!("No tally for label {:?}", label));
+        let decrease = 2 * *count - 1;
+        *count -= 1;
+        if *count == 0 { tally.label_counts.remove(&label); }
+        tally.sum_of_squares -= decrease;
+        tally.size -= 1;
+        self.precision_sum += term(tally.sum_of_squares, tally.size);
+        if tally.size == 0 { self.cluster_tallies.remove(&category); }
+
+        let (sum_of_squares, size) = self.label_tallies.get_mut(&label).unwrap();
+        self.recall_sum -= term(*sum_of_squares, *size);
+        *sum_of_squares -= decrease;
+        *size -= 1;
+        self.recall_sum += term(*sum_of_squares, *size);
+        if *size == 0 { self.label_tallies.remove(&label); }
+    }
+
+    /// Combine the tallies of two clusters after `Clustering::merge` moved the members of the second into the first,
+    /// walking the labels of whichever cluster has fewer.
+    fn tally_merge(&mut self, kept_category : C, absorbed_category : C) {
+        let kept = self.cluster_tallies.remove(&kept_category).unwrap();
+        let absorbed = self.cluster_tallies.remove(&absorbed_category).unwrap();
+        self.precision_sum -= term(kept.sum_of_squares, kept.size) + term(absorbed.sum_of_squares, absorbed.size);
+        let (mut large, small) = if kept.label_counts.len() >= absorbed.label_counts.len() { (kept, absorbed) } else { (absorbed, kept) };
+        let mut cross_terms = 0_u64;
+        for (label, small_count) in small.label_counts {
+            let large_count = large.label_counts.entry(label.clone()).or_insert(0);
+            // Joining counts x and y adds (x + y)² - x² - y² = 2xy to the sums of squares of the cluster and of the label.
+            let cross_term = 2 * *large_count * small_count;
+            *large_count += small_count;
+            if cross_term > 0 {
+                cross_terms += cross_term;
+                let (sum_of_squares, size) = self.label_tallies.get_mut(&label).unwrap();
+                self.recall_sum -= term(*sum_of_squares, *size);
+                *sum_of_squares += cross_term;
+                self.recall_sum += term(*sum_of_squares, *size);
+            }
+        }
+        large.sum_of_squares += small.sum_of_squares + cross_terms;
+        large.size += small.size;
+        self.precision_sum += term(large.sum_of_squares, large.size);
+        self.cluster_tallies.insert(kept_category, large);
+    }
+}
+
+#[cfg(test)]
+/// Tests of incremental B-Cubed tracking.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+    use crate::clustering;
+    use crate::clustering::bcubed::{BCubed, MissingMemberPolicy};
+    use super::BCubedTracker;
+
+    fn agrees(tracker_score : BCubed, expected : BCubed) -> bool {
+        (tracker_score.get_precision() - expected.get_precision()).abs() < 1e-9
+        && (tracker_score.get_recall() - expected.get_recall()).abs() < 1e-9
+    }
+
+    /// Agglomerate items one merge at a time, checking the tracked measure against a full comparison after each.
+    #[test]
+    fn tracks_agglomeration() {
+        let gold = clustering::from_delimited_string("0,1,2,3,4;5,6,7,8,9;10,11,12,13,14;15,16,17,18,19");
+        let singletons = clustering::Clustering::uncategorized(&mut (0..20_usize), 0..usize::MAX);
+        let mut tracker = BCubedTracker::new(singletons, &gold);
+        asserting("Singletons have perfect precision").that(&tracker.bcubed(0.5).get_precision()).is_equal_to(1.0);
+        for (item1, item2) in [(0, 1), (2, 3), (0, 3), (5, 6), (4, 5), (10, 19), (0, 19), (3, 1)].iter() {
+            tracker.merge(*item1, *item2);
+            let expected = BCubed::compare(tracker.get_clustering(), &gold, 0.5);
+            asserting(&format!("After merging {} and {}", item1, item2)).that(&agrees(tracker.bcubed(0.5), expected)).is_equal_to(true);
+        }
+    }
+
+    /// Apply random additions, merges, moves and removals, checking against a full comparison of the members present.
+    #[test]
+    fn tracks_random_changes() {
+        let gold = clustering::from_delimited_string("0,1,2,3,4,5,6;7,8,9;10,11,12,13,14,15;16;17,18,19,20,21,22,23,24,25,26,27,28,29");
+        let mut labels = vec![0_usize; 30];
+        for cluster in gold.get_clusters().values() {
+            for member in cluster.get_members() { labels[*member] = cluster.get_category(); }
+        }
+        let mut tracker = BCubedTracker::new(clustering::integer_clustering(), &labels);
+        let mut rng = StdRng::seed_from_u64(5);
+        for step in 0..400 {
+            let item = rng.gen_range(0..30_usize);
+            let other = rng.gen_range(0..30_usize);
+            let action = match rng.gen_range(0..5) {
+                0 => { let _ = tracker.add_to_new_cluster(item); "add" },
+                1 => { tracker.merge(item, other); "merge" },
+                2 => {
+                    if let Some(category) = tracker.get_clustering().get_category(other) { tracker.move_item(item, category); }
+                    "move"
+                },
+                3 => { tracker.remove_item(item); "remove" },
+                _ => {
+                    if let Some(category) = tracker.get_clustering().get_category(other) { let _ = tracker.add_to_cluster(item, category); }
+                    "add to cluster"
+                }
+            };
+            if tracker.get_clustering().member_count() == 0 { continue; }
+            let expected = BCubed::compare_partial(tracker.get_clustering(), &gold, 0.5, MissingMemberPolicy::Ignore).bcubed;
+            asserting(&format!("Step {} ({} {} {})", step, action, item, other)).that(&agrees(tracker.bcubed(0.5), expected)).is_equal_to(true);
+        }
+    }
+}
@@ -73,7 +73,7 @@ impl<C : Chopped, M : Chopped> Cluster<C, M> {
 
 impl<C : Chopped, M : Chopped> Debug for Cluster<C, M> {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        let mut member_list : Vec<M> = self.members.iter().map(|m| *m).collect();
+        let mut member_list : Vec<M> = self.members.iter().copied().collect();
         member_list.sort();
         let member_string_list : Vec<String> = member_list.iter().map(|m| format!("{:?}", m)).collect();
         write!(f, "Cluster for category '{:?}' has {} members: {}", self.category, self.members.len(), member_string_list.join(","))
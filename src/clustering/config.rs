@@ -0,0 +1,219 @@
+use std::fmt::{Display, Formatter};
+use std::error::Error;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use super::single_linkage::{SingleLinkage, PermutationCombination};
+
+/// Names of the presets recognized by `SingleLinkage::preset` and the `preset` setting of a `LinkageConfig`.
+pub const PRESET_NAMES : [&str; 3] = ["default", "noisy-2d", "high-dim-text"];
+
+// ........................... ConfigError ..........................................
+
+/// Problems reading or applying a `LinkageConfig`.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read.
+    Io(std::io::Error),
+
+    /// The text is not a valid JSON configuration.
+    Json(serde_json::Error),
+
+    /// The text is not a valid TOML configuration.
+    Toml(toml::de::Error),
+
+    /// The file name does not end in `.json` or `.toml`.
+    UnknownFormat(String),
+
+    /// No preset has the given name.
+    UnknownPreset(String)
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "Unable to read the configuration: {}", error),
+            ConfigError::Json(error) => write!(f, "Invalid JSON configuration: {}", error),
+            ConfigError::Toml(error) => write!(f, "Invalid TOML configuration: {}", error),
+            ConfigError::UnknownFormat(file_name) => write!(f, "Configuration file {} must end in .json or .toml", file_name),
+            ConfigError::UnknownPreset(name) => write!(f, "No preset is named \"{}\". Choose one of: {}", name, PRESET_NAMES.join(", "))
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+// ........................... LinkageConfig ..........................................
+
+/// Settings for a `SingleLinkage` read from a JSON or TOML file, any of which may be omitted.
+///
+/// The settings are applied in order: the defaults for the number of points, then the named `preset` (if any),
+/// then every other setting present. For example, this TOML starts from the "noisy-2d" preset but skips fewer distances:
+///
+/// ```text
+/// preset = "noisy-2d"
+/// noise_skip_by = 7
+/// ```
+///
+/// The names match the fields of `SingleLinkage`, so a `SingleLinkage` saved with **serde** may be read back as a `LinkageConfig`.
+/// Unrecognized names are rejected, to catch misspellings.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkageConfig {
+    pub preset : Option<String>,
+    pub bits_per_dimension : Option<u8>,
+    pub need_to_sort_by_hilbert_curve : Option<bool>,
+    pub minimum_cluster_count : Option<u16>,
+    pub noise_skip_by : Option<u16>,
+    pub outlier_cluster_size : Option<u16>,
+    pub sort_distances_completely : Option<bool>,
+    pub lowest_index_for_checking_growth_ratio : Option<u32>,
+    pub permutation_count : Option<u16>,
+    pub permutation_combination : Option<PermutationCombination>,
+    pub permutation_seed : Option<u64>,
+    pub sample_size : Option<u32>,
+    pub sample_count : Option<u16>,
+    pub sample_seed : Option<u64>
+}
+
+impl LinkageConfig {
+    /// Parse a configuration from JSON text.
+    pub fn from_json(text : &str) -> Result<Self, ConfigError> {
+        serde_json::from_str(text).map_err(ConfigError::Json)
+    }
+
+    /// Parse a configuration from TOML text.
+    pub fn from_toml(text : &str) -> Result<Self, ConfigError> {
+        toml::from_str(text).map_err(ConfigError::Toml)
+    }
+
+    /// Read a configuration from a file whose name ends in `.json` or `.toml`.
+    pub fn load<P : AsRef<Path>>(path : P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&text),
+            Some("toml") => Self::from_toml(&text),
+            _ => Err(ConfigError::UnknownFormat(path.display().to_string()))
+        }
+    }
+
+    /// Create a `SingleLinkage` for the given number of points, starting from its defaults,
+    /// then applying the preset (if any) and the other settings present.
+    ///
+    ///   - returns - An `Err` if the preset is not one of the `PRESET_NAMES`.
+    pub fn configure(&self, num_points : u32, bits_per_dimension : u8) -> Result<SingleLinkage, ConfigError> {
+        let finder = match &self.preset {
+            Some(name) => SingleLinkage::preset(name, num_points, bits_per_dimension)?,
+            None => SingleLinkage::new(num_points, bits_per_dimension)
+        };
+        Ok(self.apply(finder))
+    }
+
+    /// Override the settings of a `SingleLinkage` with those present in this configuration, ignoring the `preset`.
+    pub fn apply(&self, finder : SingleLinkage) -> SingleLinkage {
+        let mut finder = finder;
+        if let Some(bits_per_dimension) = self.bits_per_dimension { finder = finder.with_bits_per_dimension(bits_per_dimension); }
+        finder = match self.need_to_sort_by_hilbert_curve {
+            Some(true) => finder.with_need_to_sort_by_hilbert_curve(),
+            Some(false) => finder.without_need_to_sort_by_hilbert_curve(),
+            None => finder
+        };
+        if let Some(count) = self.minimum_cluster_count { finder = finder.with_minimum_cluster_count(count); }
+        if let Some(skip) = self.noise_skip_by { finder = finder.with_noise_skip_by(skip); }
+        if let Some(size) = self.outlier_cluster_size { finder = finder.with_outlier_cluster_size(size); }
+        finder = match self.sort_distances_completely {
+            Some(true) => finder.with_sort_distances_completely(),
+            Some(false) => finder.without_sort_distances_completely(),
+            None => finder
+        };
+        if let Some(index) = self.lowest_index_for_checking_growth_ratio { finder = finder.with_lowest_index_for_checking_growth_ratio(index); }
+        if self.permutation_count.is_some() || self.permutation_combination.is_some() {
+            finder = finder.with_permutations(
+                self.permutation_count.unwrap_or_else(|| finder.get_permutation_count()),
+                self.permutation_combination.unwrap_or_else(|| finder.get_permutation_combination())
+            );
+        }
+        if let Some(seed) = self.permutation_seed { finder = finder.with_permutation_seed(seed); }
+        if self.sample_size.is_some() || self.sample_count.is_some() {
+            finder = finder.with_sampling(
+                self.sample_size.unwrap_or_else(|| finder.get_sample_size()),
+                self.sample_count.unwrap_or_else(|| finder.get_sample_count())
+            );
+        }
+        if let Some(seed) = self.sample_seed { finder = finder.with_sample_seed(seed); }
+        finder
+    }
+}
+
+impl SingleLinkage {
+    /// Create a `SingleLinkage` configured for a common kind of data.
+    ///
+    ///   - "default" - The same as `new`.
+    ///   - "noisy-2d" - Low-dimensional data with background noise and touching clusters, like the S1 to S4 data sets.
+    ///     Skips nine distances when looking for the jump, which hand tuning found best for S1 and S2, sorts the distances
+    ///     completely, and sorts the points by Hilbert curve.
+    ///   - "high-dim-text" - Documents modeled as bags of words, with hundreds or thousands of dimensions and many
+    ///     near-duplicate documents. A single Hilbert curve separates many true neighbors in high dimensions, so the median
+    ///     of five curves is used. Binning replaces the full sort, since such data sets tend to be large,
+    ///     and the search for the jump starts at three quarters of the way through the distances, past the near duplicates.
+    ///   - returns - An `Err` if the name is not one of the `PRESET_NAMES`.
+    pub fn preset(name : &str, num_points : u32, bits_per_dimension : u8) -> Result<Self, ConfigError> {
+        let finder = SingleLinkage::new(num_points, bits_per_dimension);
+        match name {
+            "default" => Ok(finder),
+            "noisy-2d" => Ok(finder
+                .with_need_to_sort_by_hilbert_curve()
+                .with_sort_distances_completely()
+                .with_noise_skip_by(9)),
+            "high-dim-text" => Ok(finder
+                .with_need_to_sort_by_hilbert_curve()
+                .without_sort_distances_completely()
+                .with_noise_skip_by(3)
+                .with_lowest_index_for_checking_growth_ratio(num_points / 4 * 3)
+                .with_permutations(5, PermutationCombination::Median)),
+            _ => Err(ConfigError::UnknownPreset(name.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+/// Tests of parsing and applying configurations.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use crate::clustering::single_linkage::{SingleLinkage, PermutationCombination};
+    use super::{LinkageConfig, ConfigError, PRESET_NAMES};
+
+    #[test]
+    fn toml_preset_with_override() {
+        let config = LinkageConfig::from_toml("preset = \"noisy-2d\"\nnoise_skip_by = 7\noutlier_cluster_size = 20\n").unwrap();
+        let finder = config.configure(5000, 20).unwrap();
+        let expected = SingleLinkage::preset("noisy-2d", 5000, 20).unwrap()
+            .with_noise_skip_by(7)
+            .with_outlier_cluster_size(20);
+        asserting("Preset then overrides").that(&finder).is_equal_to(expected);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let finder = SingleLinkage::new(1000, 16)
+            .with_permutations(3, PermutationCombination::Minimum)
+            .with_sampling(200, 4);
+        let json = serde_json::to_string(&finder).unwrap();
+        let restored : SingleLinkage = serde_json::from_str(&json).unwrap();
+        asserting("Saved SingleLinkage restored").that(&restored).is_equal_to(finder);
+        let config = LinkageConfig::from_json(&json).unwrap();
+        asserting("Saved SingleLinkage read as a config").that(&config.configure(1000, 8).unwrap()).is_equal_to(finder);
+    }
+
+    #[test]
+    fn errors() {
+        let unknown_field = LinkageConfig::from_json("{ \"noise_skip\" : 3 }");
+        asserting("Misspelling rejected").that(&matches!(unknown_field, Err(ConfigError::Json(_)))).is_equal_to(true);
+        let config = LinkageConfig::from_toml("preset = \"noisy-3d\"").unwrap();
+        asserting("Unknown preset").that(&matches!(config.configure(100, 8), Err(ConfigError::UnknownPreset(_)))).is_equal_to(true);
+        for name in PRESET_NAMES.iter() {
+            asserting(&format!("Preset {} exists", name)).that(&SingleLinkage::preset(name, 100, 8).is_ok()).is_equal_to(true);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use hilbert::{Point, Permutation};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+use super::single_linkage::{SingleLinkage, AdjacentPairDistance};
+use super::tuning::LabelledDataset;
+use super::spanning_tree::DisjointSet;
+
+/// Default number of Hilbert curves whose runs are counted by `SingleLinkage::estimate_true_cluster_count`.
+pub const DEFAULT_CURVE_COUNT : u16 = 8;
+
+// ........................... CountCalibration ..........................................
+
+/// How many large Hilbert runs each true cluster tends to break into, used to turn a count of runs into a count of clusters.
+///
+/// A Hilbert curve leaves and reenters every cluster several times, so a cluster is split into several runs.
+/// The ratio depends on the shape and density of the clusters, the noise, the number of dimensions and the settings
+/// of the `SingleLinkage`, so it is best measured on labelled data resembling the data to be clustered, using `fit`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CountCalibration {
+    /// Lowest ratio of runs to true clusters observed.
+    pub lowest_runs_per_cluster : f64,
+
+    /// Typical ratio of runs to true clusters (the geometric mean of those observed).
+    pub runs_per_cluster : f64,
+
+    /// Highest ratio of runs to true clusters observed.
+    pub highest_runs_per_cluster : f64
+}
+
+impl Default for CountCalibration {
+    /// The typical and highest ratios were measured on the S1 and S2 data sets (15 clusters of 5,000 points in two dimensions,
+    /// S2 with more noise and overlap) using the settings from `SingleLinkage::new` and `DEFAULT_CURVE_COUNT` curves,
+    /// which gave 2.2 for both, with the highest widened by a tenth. The lowest is the low end of the long observed rule
+    /// that runs outnumber clusters by 1.5 to 3 times, because heavily overlapping clusters are split less often.
+    fn default() -> Self {
+        CountCalibration {
+            lowest_runs_per_cluster : 1.5,
+            runs_per_cluster : 2.2,
+            highest_runs_per_cluster : 2.4
+        }
+    }
+}
+
+impl CountCalibration {
+    /// Measure the ratio of runs to true clusters on labelled data sets.
+    ///
+    /// The true clusters are those in each gold standard with more members than `outlier_cluster_size`.
+    ///
+    ///   - datasets - At least one labelled data set.
+    ///   - configure - Create the `SingleLinkage` to use for a data set, just as it will be created for unlabelled data.
+    pub fn fit<F>(datasets : &[LabelledDataset], configure : F) -> Self
+    where F : Fn(&LabelledDataset) -> SingleLinkage
+    {
+        if datasets.is_empty() { panic!("At least one labelled data set is required for calibration."); }
+        let ratios : Vec<f64> = datasets.iter()
+            .map(|dataset| {
+                let finder = configure(dataset);
+                let mut points = dataset.points.clone();
+                let linkage_square_distance = finder.find(&mut points).linkage_square_distance;
+                let outlier_cluster_size = finder.get_outlier_cluster_size() as usize;
+                let true_count = dataset.gold_standard.get_clusters().values().filter(|cluster| cluster.len() > outlier_cluster_size).count();
+                let run_counts = finder.count_runs_on_curves(&dataset.points, linkage_square_distance, DEFAULT_CURVE_COUNT);
+                median(&run_counts) / true_count.max(1) as f64
+            })
+            .collect();
+        let log_mean = ratios.iter().map(|ratio| ratio.ln()).sum::<f64>() / ratios.len() as f64;
+        CountCalibration {
+            lowest_runs_per_cluster : ratios.iter().copied().fold(f64::MAX, f64::min),
+            runs_per_cluster : log_mean.exp(),
+            highest_runs_per_cluster : ratios.iter().copied().fold(0.0, f64::max)
+        }
+    }
+}
+
+// ........................... ClusterCountEstimate ..........................................
+
+/// An estimate of the true number of clusters, with an interval likely to contain it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusterCountEstimate {
+    /// Median count of runs over the curves divided by the typical `runs_per_cluster`.
+    pub estimate : f64,
+
+    /// Fewest clusters likely: the fewest runs on any curve divided by the `highest_runs_per_cluster`, rounded down.
+    pub lowest : u32,
+
+    /// Most clusters likely: the most runs on any curve divided by the `lowest_runs_per_cluster`, rounded up.
+    pub highest : u32,
+
+    /// Count of large runs on each curve, the first being the unpermuted curve analyzed by `find`.
+    pub run_counts : Vec<u32>,
+
+    /// Number of sets larger than `outlier_cluster_size` formed by joining the runs on all the curves wherever they share a point.
+    ///
+    /// A run never spans a gap wider than the linkage distance, so this falls toward the number of clusters that single-link
+    /// clustering forms at that distance as curves are added, not toward the true number of clusters. Noise that chains
+    /// true clusters together drags it well below the true count (to 6 for S1 and 2 for S2, which have 15), so it is
+    /// reported alongside the calibrated estimate rather than used in it.
+    pub linked_run_count : u32
+}
+
+impl ClusterCountEstimate {
+    /// The estimate rounded to the nearest whole number of clusters.
+    pub fn rounded(&self) -> u32 { self.estimate.round() as u32 }
+
+    /// Does the interval contain the given count?
+    pub fn contains(&self, cluster_count : u32) -> bool { self.lowest <= cluster_count && cluster_count <= self.highest }
+}
+
+impl Display for ClusterCountEstimate {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "About {} clusters (likely {} to {}), from {} curves with {} to {} large runs",
+            self.rounded(), self.lowest, self.highest, self.run_counts.len(),
+            self.run_counts.iter().min().unwrap_or(&0), self.run_counts.iter().max().unwrap_or(&0))
+    }
+}
+
+impl SingleLinkage {
+    /// Estimate how many clusters a full clustering at the given linkage distance will find,
+    /// correcting the count of large Hilbert runs (the `large_cluster_count` of a `LinkageResult`) for how often
+    /// a cluster is split into several runs.
+    ///
+    /// The runs are counted on several Hilbert curves, each formed by permuting the coordinates and reflecting
+    /// a random subset of them, so that the estimate does not hinge on where one curve happens to cut the clusters.
+    /// The curves are chosen using `permutation_seed`, so the estimate is repeatable.
+    /// The runs of all the curves are also joined wherever they share a point, giving the `linked_run_count`.
+    ///
+    ///   - points - Points to analyze, in any order. They are not modified.
+    ///   - linkage_square_distance - Linkage distance, usually from `find`.
+    ///   - curve_count - Number of curves to count runs on. More curves steady the estimate. Zero is treated as one.
+    ///   - calibration - Ratio of runs to true clusters, from `CountCalibration::default` or `CountCalibration::fit`.
+    pub fn estimate_true_cluster_count(&self, points : &[Point], linkage_square_distance : u64, curve_count : u16, calibration : &CountCalibration) -> ClusterCountEstimate {
+        let (run_counts, linked_run_count) = self.link_runs_on_curves(points, linkage_square_distance, curve_count);
+        let fewest_runs = *run_counts.iter().min().unwrap_or(&0) as f64;
+        let most_runs = *run_counts.iter().max().unwrap_or(&0) as f64;
+        ClusterCountEstimate {
+            estimate : median(&run_counts) / calibration.runs_per_cluster,
+            lowest : (fewest_runs / calibration.highest_runs_per_cluster).floor() as u32,
+            highest : (most_runs / calibration.lowest_runs_per_cluster).ceil() as u32,
+            run_counts,
+            linked_run_count
+        }
+    }
+
+    /// Count the large runs at the linkage distance on the unpermuted curve and on `curve_count - 1` others,
+    /// each of which permutes the coordinates and reflects a random subset of them.
+    fn count_runs_on_curves(&self, points : &[Point], linkage_square_distance : u64, curve_count : u16) -> Vec<u32> {
+        self.link_runs_on_curves(points, linkage_square_distance, curve_count).0
+    }
+
+    /// Count the large runs on each curve as `count_runs_on_curves` does, and also join every run on every curve
+    /// into one set of linked points, counting the sets larger than `outlier_cluster_size`.
+    ///
+    /// Every point in a run is within the linkage distance of the next, so the points of a run always belong
+    /// to one single-link cluster. Joining runs that share a point never merges points a full clustering would keep apart,
+    /// so each new curve can only heal splits that the earlier curves made.
+    fn link_runs_on_curves(&self, points : &[Point], linkage_square_distance : u64, curve_count : u16) -> (Vec<u32>, u32) {
+        let dimensions = points.first().map_or(1, |p| p.dimensions());
+        let largest_coordinate = if self.get_bits_per_dimension() >= 32 { u32::MAX } else { (1_u32 << self.get_bits_per_dimension()) - 1 };
+        let slot_of_id : HashMap<usize, usize> = points.iter().enumerate().map(|(slot, point)| (point.get_id(), slot)).collect();
+        let mut linked_points = DisjointSet::new(points.len());
+        let mut rng = StdRng::seed_from_u64(self.get_permutation_seed());
+        let run_counts = (0..curve_count.max(1))
+            .map(|i_curve| {
+                let mut curve_points : Vec<Point> =
+                    if i_curve == 0 { points.to_vec() }
+                    else {
+                        let mut moves : Vec<usize> = (0..dimensions).collect();
+                        moves.shuffle(&mut rng);
+                        let permutation = Permutation::new(&moves);
+                        let reflect : Vec<bool> = (0..dimensions).map(|_| rng.gen()).collect();
+                        // Neither permuting nor reflecting coordinates changes the distances between points.
+                        // Coordinates too wide for bits_per_dimension (see `validate`) are reflected to zero rather than overflow.
+                        points.iter()
+                            .map(|point| {
+                                let permuted = point.permute(&permutation);
+                                let coordinates : Vec<u32> = permuted.get_coordinates().iter().zip(reflect.iter())
+                                    .map(|(c, flip)| if *flip { largest_coordinate.saturating_sub(*c) } else { *c })
+                                    .collect();
+                                Point::new(point.get_id(), &coordinates)
+                            })
+                            .collect()
+                    };
+                self.sort_by_hilbert_curve(&mut curve_points);
+                let distances = AdjacentPairDistance::all_pairs(&curve_points);
+                for run in self.find_runs(&distances, linkage_square_distance) {
+                    for pair in run.point_ids.windows(2) {
+                        linked_points.union(slot_of_id[&pair[0]], slot_of_id[&pair[1]]);
+                    }
+                }
+                self.estimate_cluster_counts(distances, linkage_square_distance).large_cluster_count
+            })
+            .collect();
+        let outlier_cluster_size = self.get_outlier_cluster_size() as usize;
+        let linked_count = (0..points.len())
+            .filter(|slot| linked_points.find(*slot) == *slot && linked_points.size(*slot) > outlier_cluster_size)
+            .count();
+        (run_counts, linked_count as u32)
+    }
+}
+
+/// Median of the counts, averaging the middle two if there is an even number of them.
+fn median(counts : &[u32]) -> f64 {
+    let mut sorted = counts.to_vec();
+    sorted.sort_unstable();
+    let middle = sorted.len() / 2;
+    if sorted.is_empty() { 0.0 }
+    else if sorted.len() % 2 == 1 { sorted[middle] as f64 }
+    else { (sorted[middle - 1] as f64 + sorted[middle] as f64) / 2.0 }
+}
+
+#[cfg(test)]
+/// Tests of the count estimate.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use hilbert::Point;
+    use crate::clustering::single_linkage::SingleLinkage;
+    use super::CountCalibration;
+
+    #[test]
+    fn median() {
+        asserting("Odd").that(&super::median(&[5, 1, 3])).is_equal_to(3.0);
+        asserting("Even").that(&super::median(&[4, 1, 3, 10])).is_equal_to(3.5);
+    }
+
+    /// Nine square clusters on a grid. A reflected or permuted curve sees the same clusters.
+    #[test]
+    fn estimate_true_cluster_count() {
+        let mut points = Vec::new();
+        for cluster in 0..9_u32 {
+            for x in 0..12_u32 {
+                for y in 0..12_u32 {
+                    points.push(Point::new(points.len(), &[(cluster % 3) * 300 + x * 3, (cluster / 3) * 300 + y * 3]));
+                }
+            }
+        }
+        let finder = SingleLinkage::new(points.len() as u32, 10);
+        let calibration = CountCalibration { lowest_runs_per_cluster : 1.0, runs_per_cluster : 1.0, highest_runs_per_cluster : 1.0 };
+        let estimate = finder.estimate_true_cluster_count(&points, 9, 6, &calibration);
+        asserting("Curve count").that(&estimate.run_counts.len()).is_equal_to(6);
+        asserting(&format!("Every curve finds runs, got {:?}", estimate.run_counts)).that(&estimate.run_counts.iter().all(|count| *count >= 9)).is_equal_to(true);
+        asserting("Interval holds the estimate").that(&estimate.contains(estimate.rounded())).is_equal_to(true);
+        let repeated = finder.estimate_true_cluster_count(&points, 9, 6, &calibration);
+        asserting("Repeatable").that(&repeated).is_equal_to(estimate);
+    }
+
+    /// Coordinates wider than `bits_per_dimension` must not overflow when reflected, just as `find` tolerates them.
+    #[test]
+    fn estimate_with_coordinates_too_wide() {
+        let points : Vec<Point> = (0..60_u32).map(|i| Point::new(i as usize, &[i * 10, (i % 7) * 3])).collect();
+        let finder = SingleLinkage::new(points.len() as u32, 4);
+        asserting("Points are too wide").that(&finder.validate(&points).is_err()).is_equal_to(true);
+        let estimate = finder.estimate_true_cluster_count(&points, 100, 4, &CountCalibration::default());
+        asserting("Every curve counted").that(&estimate.run_counts.len()).is_equal_to(4);
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use super::cluster::Chopped;
+use super::Clustering;
+
+/// Default largest number of members that can leave one cluster for another and be reported
+/// as individual moves rather than as a split or merge.
+pub const DEFAULT_MOVE_LIMIT : usize = 1;
+
+// ........................... Split, Merge, MovedMember ..........................................
+
+/// A cluster of the first `Clustering` whose members went to several clusters of the second.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Split<C1 : Chopped, C2 : Chopped> {
+    /// Category of the cluster that was split.
+    pub from : C1,
+
+    /// Each cluster receiving a part and the number of members it received, largest part first.
+    pub into : Vec<(C2, usize)>
+}
+
+/// A cluster of the second `Clustering` whose members came from several clusters of the first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Merge<C1 : Chopped, C2 : Chopped> {
+    /// Each cluster contributing a part and the number of members it contributed, largest part first.
+    pub from : Vec<(C1, usize)>,
+
+    /// Category of the merged cluster.
+    pub into : C2
+}
+
+/// A member that left its cluster along with too few others to count as a split.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MovedMember<C1 : Chopped, C2 : Chopped, M : Chopped> {
+    pub member : M,
+
+    /// Category of the member in the first `Clustering`.
+    pub from : C1,
+
+    /// Category of the member in the second `Clustering`.
+    pub to : C2
+}
+
+// ........................... ClusteringDiff ..........................................
+
+/// Explains how a second `Clustering` of the same members differs from a first, as clusters preserved,
+/// split, merged, formed and dissolved, and members moved.
+///
+/// The members shared by a cluster of the first and a cluster of the second form a _part_.
+/// A part counts toward splits and merges if it has more members than the move limit,
+/// or if it is the whole of either cluster. The members of any other part are reported as moves.
+/// A cluster keeping a single such part, which is the only one its counterpart received, is preserved,
+/// even if a few members moved in or out.
+/// A cluster of the second made up only of moved members is _formed_, and one of the first whose members
+/// all moved away is _dissolved_. A split cluster may also contribute to a merge.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusteringDiff<C1 : Chopped, C2 : Chopped, M : Chopped> {
+    /// Each preserved cluster of the first, its counterpart in the second and the number of members they share, in order of the first category.
+    pub preserved : Vec<(C1, C2, usize)>,
+
+    /// Clusters of the first split into several of the second, in order of category.
+    pub splits : Vec<Split<C1, C2>>,
+
+    /// Clusters of the second merged from several of the first, in order of category.
+    pub merges : Vec<Merge<C1, C2>>,
+
+    /// Members moved individually, in order of member.
+    pub moves : Vec<MovedMember<C1, C2, M>>,
+
+    /// Clusters of the second made up only of moved members, in ascending order.
+    pub formed : Vec<C2>,
+
+    /// Clusters of the first whose members all moved away, in ascending order.
+    pub dissolved : Vec<C1>
+}
+
+impl<C1 : Chopped, C2 : Chopped, M : Chopped> ClusteringDiff<C1, C2, M> {
+    /// Explain the differences between two `Clusterings`, reporting parts of up to `DEFAULT_MOVE_LIMIT` members as moves.
+    ///
+    ///   - first - The original `Clustering`.
+    ///   - second - The revised `Clustering`. It must have the same members as the first.
+    pub fn new<G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(first : &Clustering<C1,M,G1>, second : &Clustering<C2,M,G2>) -> Self {
+        Self::with_move_limit(first, second, DEFAULT_MOVE_LIMIT)
+    }
+
+    /// Explain the differences between two `Clusterings`.
+    ///
+    ///   - first - The original `Clustering`.
+    ///   - second - The revised `Clustering`. It must have the same members as the first.
+    ///   - move_limit - Largest part (other than a whole cluster) whose members are reported as moves
+    ///     instead of as a split or merge. Zero reports no moves.
+    pub fn with_move_limit<G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(first : &Clustering<C1,M,G1>, second : &Clustering<C2,M,G2>, move_limit : usize) -> Self {
+        if first.member_count() != second.member_count() {
+            panic!("The first Clustering has {} items but the second has {}", first.member_count(), second.member_count());
+        }
+        let mut parts : BTreeMap<(C1, C2), Vec<M>> = BTreeMap::new();
+        for (category, cluster) in first.get_clusters().iter() {
+            for member in cluster.get_members() {
+                let second_category = second.get_category(*member)
+                    .unwrap_or_else(|| panic!("Item {:?} from one Clustering not present in the other", *member));
+                parts.entry((*category, second_category)).or_default().push(*member);
+            }
+        }
+        let first_size = |category : C1| first.get_cluster(category).map_or(0, |cluster| cluster.len());
+        let second_size = |category : C2| second.get_cluster(category).map_or(0, |cluster| cluster.len());
+
+        let mut first_parts : BTreeMap<C1, Vec<(C2, usize)>> = BTreeMap::new();
+        let mut second_parts : BTreeMap<C2, Vec<(C1, usize)>> = BTreeMap::new();
+        let mut moves = Vec::new();
+        for ((category, second_category), mut members) in parts.into_iter() {
+            let overlap = members.len();
+            if overlap > move_limit || overlap == first_size(category) || overlap == second_size(second_category) {
+                first_parts.entry(category).or_default().push((second_category, overlap));
+                second_parts.entry(second_category).or_default().push((category, overlap));
+            }
+            else {
+                members.sort();
+                moves.extend(members.into_iter().map(|member| MovedMember { member, from : category, to : second_category }));
+            }
+        }
+        moves.sort_by_key(|moved| moved.member);
+
+        let kept : BTreeSet<C1> = first_parts.keys().copied().collect();
+        let mut preserved = Vec::new();
+        let mut splits = Vec::new();
+        for (category, mut into) in first_parts.into_iter() {
+            if into.len() > 1 {
+                into.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+                splits.push(Split { from : category, into });
+            }
+            else {
+                let (second_category, overlap) = into[0];
+                if second_parts[&second_category].len() == 1 { preserved.push((category, second_category, overlap)); }
+            }
+        }
+        let merges = second_parts.iter()
+            .filter(|(_, from)| from.len() > 1)
+            .map(|(category, from)| {
+                let mut from = from.clone();
+                from.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+                Merge { from, into : *category }
+            })
+            .collect();
+        let formed = second.get_clusters().keys()
+            .filter(|category| !second_parts.contains_key(category))
+            .copied()
+            .collect::<BTreeSet<C2>>()
+            .into_iter()
+            .collect();
+        let dissolved = first.get_clusters().keys()
+            .filter(|category| !kept.contains(category))
+            .copied()
+            .collect::<BTreeSet<C1>>()
+            .into_iter()
+            .collect();
+        ClusteringDiff { preserved, splits, merges, moves, formed, dissolved }
+    }
+
+    /// True if every cluster was preserved with no members moved, so the two `Clusterings` partition their members identically.
+    pub fn is_unchanged(&self) -> bool {
+        self.splits.is_empty() && self.merges.is_empty() && self.moves.is_empty() && self.formed.is_empty() && self.dissolved.is_empty()
+    }
+}
+
+impl<C1 : Chopped, C2 : Chopped, M : Chopped> Display for ClusteringDiff<C1, C2, M> {
+    /// A summary line of counts, then one line per change, for example:
+    ///
+    /// ```text
+    /// 2 preserved, 1 split, 1 merged, 0 formed, 0 dissolved, 1 moved
+    /// preserved 0 -> 0 (4)
+    /// split 1 -> 1 (2), 2 (2)
+    /// merged 2 (2), 3 (2) -> 3
+    /// moved 5 from 0 to 2
+    /// ```
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        writeln!(f, "{} preserved, {} split, {} merged, {} formed, {} dissolved, {} moved",
+            self.preserved.len(), self.splits.len(), self.merges.len(), self.formed.len(), self.dissolved.len(), self.moves.len())?;
+        for (category, second_category, overlap) in self.preserved.iter() {
+            writeln!(f, "preserved {:?} -> {:?} ({})", category, second_category, overlap)?;
+        }
+        for split in self.splits.iter() {
+            let parts : Vec<String> = split.into.iter().map(|(category, overlap)| format!("{:?} ({})", category, overlap)).collect();
+            writeln!(f, "split {:?} -> {}", split.from, parts.join(", "))?;
+        }
+        for merge in self.merges.iter() {
+            let parts : Vec<String> = merge.from.iter().map(|(category, overlap)| format!("{:?} ({})", category, overlap)).collect();
+            writeln!(f, "merged {} -> {:?}", parts.join(", "), merge.into)?;
+        }
+        for category in self.formed.iter() { writeln!(f, "formed {:?}", category)?; }
+        for category in self.dissolved.iter() { writeln!(f, "dissolved {:?}", category)?; }
+        for moved in self.moves.iter() {
+            writeln!(f, "moved {:?} from {:?} to {:?}", moved.member, moved.from, moved.to)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+/// Tests of explaining the differences between clusterings.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use crate::clustering;
+    use super::{ClusteringDiff, Merge, MovedMember, Split};
+
+    #[test]
+    fn splits_merges_and_moves() {
+        let first = clustering::from_delimited_string("1,2,3,4,5;6,7,8,9;10,11;12,13;14,15,16");
+        let second = clustering::from_delimited_string("1,2,3,4;6,7;8,9,5;10,11,12,13;14,15,16");
+        let diff = ClusteringDiff::new(&first, &second);
+        asserting("Preserved").that(&diff.preserved).is_equal_to(vec![(0, 0, 4), (4, 4, 3)]);
+        asserting("Splits").that(&diff.splits).is_equal_to(vec![Split { from : 1, into : vec![(1, 2), (2, 2)] }]);
+        asserting("Merges").that(&diff.merges).is_equal_to(vec![Merge { from : vec![(2, 2), (3, 2)], into : 3 }]);
+        asserting("Moves").that(&diff.moves).is_equal_to(vec![MovedMember { member : 5, from : 0, to : 2 }]);
+        asserting("Changed").that(&diff.is_unchanged()).is_equal_to(false);
+        let text = format!("{}", diff);
+        asserting("Rendered").that(&text).is_equal_to(
+            "2 preserved, 1 split, 1 merged, 0 formed, 0 dissolved, 1 moved\n\
+             preserved 0 -> 0 (4)\n\
+             preserved 4 -> 4 (3)\n\
+             split 1 -> 1 (2), 2 (2)\n\
+             merged 2 (2), 3 (2) -> 3\n\
+             moved 5 from 0 to 2\n".to_string());
+
+        let coarse = ClusteringDiff::with_move_limit(&first, &second, 0);
+        asserting("With no moves, cluster 0 splits").that(&coarse.splits[0]).is_equal_to(Split { from : 0, into : vec![(0, 4), (2, 1)] });
+    }
+
+    #[test]
+    fn formed_from_moves() {
+        let first = clustering::from_delimited_string("1,2,3;4,5,6;7,8,9");
+        let second = clustering::from_delimited_string("1,2;4,5;7,8;3,6,9");
+        let diff = ClusteringDiff::new(&first, &second);
+        asserting("Preserved").that(&diff.preserved.len()).is_equal_to(3);
+        asserting("Formed").that(&diff.formed).is_equal_to(vec![3]);
+        asserting("Moves").that(&diff.moves.len()).is_equal_to(3);
+    }
+
+    #[test]
+    fn relabelled_is_unchanged() {
+        let first = clustering::from_delimited_string("1,2;3,4,5;6");
+        let second = clustering::from_delimited_string("6;3,4,5;1,2");
+        let diff = ClusteringDiff::new(&first, &second);
+        asserting("Unchanged").that(&diff.is_unchanged()).is_equal_to(true);
+        asserting("Preserved").that(&diff.preserved).is_equal_to(vec![(0, 2, 2), (1, 1, 3), (2, 0, 1)]);
+    }
+}
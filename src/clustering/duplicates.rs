@@ -0,0 +1,222 @@
+use std::ops::Range;
+use std::collections::HashMap;
+use hilbert::Point;
+use super::{Clustering, integer_clustering};
+use super::cluster::Chopped;
+use super::single_linkage::saturating_square_distance;
+use super::weights::PointWeights;
+
+// ........................... CollapsedPoints ..........................................
+
+/// Points with duplicates (and optionally near-duplicates) collapsed into weighted representatives.
+///
+/// A few identical or nearly identical points followed by a gap can look like the jump in distance that
+/// `SingleLinkage` searches for, which is one reason why `lowest_index_for_checking_growth_ratio` defaults to N/2.
+/// Collapsing them first removes the run of zero or tiny distances they contribute.
+///
+/// Each representative is a copy of the first of the points it stands for, keeping that point's id.
+/// Its weight is the number of original points it stands for. After clustering the representatives,
+/// `expand` puts every original point into the cluster of its representative.
+#[derive(Clone, Debug)]
+pub struct CollapsedPoints {
+    /// One point for each group of duplicates, in the order in which each group was first encountered.
+    representatives : Vec<Point>,
+
+    /// Ids of the original points that each representative stands for, aligned with `representatives`.
+    /// The representative's own id comes first.
+    members : Vec<Vec<usize>>,
+
+    /// Maps the id of a representative to its position in `representatives`.
+    position_of_id : HashMap<usize, usize>
+}
+
+impl CollapsedPoints {
+    /// Collapse points with identical coordinates into one representative each.
+    ///
+    /// This takes O(N) time and preserves the order of the points (excluding the duplicates that are removed).
+    pub fn collapse_duplicates(points : &[Point]) -> Self {
+        let mut collapsed = CollapsedPoints::empty(points.len());
+        let mut position_of_coordinates : HashMap<&Vec<u32>, usize> = HashMap::with_capacity(points.len());
+        for point in points {
+            match position_of_coordinates.get(point.get_coordinates()) {
+                Some(position) => collapsed.members[*position].push(point.get_id()),
+                None => {
+                    position_of_coordinates.insert(point.get_coordinates(), collapsed.representatives.len());
+                    collapsed.add_representative(point);
+                }
+            }
+        }
+        collapsed
+    }
+
+    /// Collapse points with identical coordinates, then collapse points that lie near each other.
+    ///
+    /// After the exact duplicates are removed, the representatives are arranged in Hilbert curve order.
+    /// Each representative absorbs those following it whose square distance from it does not exceed `square_radius`.
+    /// Only neighbors along the curve are compared, so some pairs of nearby points on opposite sides of a fold in the
+    /// curve will not be collapsed, but no point is ever farther than the radius from its representative.
+    ///
+    ///   - `points` - Points to collapse.
+    ///   - `bits_per_dimension` - Number of bits needed to encode the largest coordinate value, used by the Hilbert sort.
+    ///   - `square_radius` - Square of the largest distance between a representative and the points it absorbs.
+    ///   - returns - The representatives, in Hilbert curve order.
+    pub fn collapse_within(points : &[Point], bits_per_dimension : u8, square_radius : u64) -> Self {
+        let exact = Self::collapse_duplicates(points);
+        let mut sorted_representatives = exact.representatives.clone();
+        Point::hilbert_sort(&mut sorted_representatives, bits_per_dimension as usize);
+
+        let mut collapsed = CollapsedPoints::empty(sorted_representatives.len());
+        let mut current : Option<(usize, &Point)> = None;
+        for point in sorted_representatives.iter() {
+            let exact_members = exact.get_members(point.get_id()).unwrap();
+            match current {
+                Some((position, representative)) if saturating_square_distance(representative, point) <= square_radius => {
+                    collapsed.members[position].extend(exact_members.iter());
+                },
+                _ => {
+                    current = Some((collapsed.representatives.len(), point));
+                    collapsed.add_representative(point);
+                    collapsed.members.last_mut().unwrap().extend(exact_members[1..].iter());
+                }
+            }
+        }
+        collapsed
+    }
+
+    fn empty(capacity : usize) -> Self {
+        CollapsedPoints {
+            representatives : Vec::with_capacity(capacity),
+            members : Vec::with_capacity(capacity),
+            position_of_id : HashMap::with_capacity(capacity)
+        }
+    }
+
+    fn add_representative(&mut self, point : &Point) {
+        self.position_of_id.insert(point.get_id(), self.representatives.len());
+        self.representatives.push(point.clone());
+        self.members.push(vec![point.get_id()]);
+    }
+
+    /// The representative points, which may be passed to `SingleLinkage` or a clusterer in place of the original points.
+    pub fn get_representatives(&self) -> &[Point] { &self.representatives }
+
+    /// Number of representatives.
+    pub fn len(&self) -> usize { self.representatives.len() }
+
+    /// Are there no representatives?
+    pub fn is_empty(&self) -> bool { self.representatives.is_empty() }
+
+    /// Number of original points.
+    pub fn original_count(&self) -> usize { self.members.iter().map(|ids| ids.len()).sum() }
+
+    /// Ids of all the original points that the representative with the given id stands for, starting with its own id.
+    pub fn get_members(&self, representative_id : usize) -> Option<&[usize]> {
+        self.position_of_id.get(&representative_id).map(|position| &self.members[*position][..])
+    }
+
+    /// Number of original points that the representative with the given id stands for, or zero if there is no such representative.
+    pub fn get_weight(&self, representative_id : usize) -> usize {
+        self.get_members(representative_id).map_or(0, |ids| ids.len())
+    }
+
+    /// Weights of the representatives, aligned with `get_representatives`.
+    pub fn get_weights(&self) -> Vec<usize> {
+        self.members.iter().map(|ids| ids.len()).collect()
+    }
+
+    /// Weights of the representatives by id, for use with `SingleLinkage::find_weighted` and `BCubed::compare_weighted`.
+    pub fn get_point_weights(&self) -> PointWeights {
+        PointWeights::from_pairs(self.representatives.iter().zip(self.members.iter()).map(|(p, ids)| (p.get_id(), ids.len() as u64)))
+    }
+
+    /// Convert a clustering of representatives into a clustering of all the original points.
+    ///
+    /// Each cluster of the result holds the original points of the representatives in one cluster of the given clustering.
+    /// Any member that is not a representative is carried over as is.
+    pub fn expand<C, G>(&self, representative_clustering : &Clustering<C, usize, G>) -> Clustering<usize, usize, Range<usize>>
+    where C : Chopped, G : Iterator<Item = C>
+    {
+        let mut clustering = integer_clustering();
+        let mut clusters : Vec<_> = representative_clustering.get_clusters().values().collect();
+        clusters.sort();
+        for cluster in clusters {
+            let mut representative_ids : Vec<usize> = cluster.get_members().iter().copied().collect();
+            representative_ids.sort_unstable();
+            let mut category = None;
+            for representative_id in representative_ids {
+                let ids = match self.get_members(representative_id) {
+                    Some(ids) => ids,
+                    None => std::slice::from_ref(&representative_id)
+                };
+                for id in ids {
+                    let added = match category {
+                        Some(category) => clustering.add_to_cluster(*id, category),
+                        None => clustering.add_to_new_cluster(*id)
+                    };
+                    category = Some(added.unwrap_or_else(|_| panic!("Point {} is in more than one cluster", id)));
+                }
+            }
+        }
+        clustering
+    }
+}
+
+#[cfg(test)]
+/// Tests of collapsing and expanding.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use hilbert::Point;
+    use crate::clustering::integer_clustering;
+    use super::CollapsedPoints;
+
+    fn points_with_duplicates() -> Vec<Point> {
+        vec![
+            Point::new(0, &[10, 10]),
+            Point::new(1, &[50, 50]),
+            Point::new(2, &[10, 10]),
+            Point::new(3, &[11, 10]),
+            Point::new(4, &[10, 10]),
+            Point::new(5, &[50, 50])
+        ]
+    }
+
+    #[test]
+    fn collapse_duplicates() {
+        let collapsed = CollapsedPoints::collapse_duplicates(&points_with_duplicates());
+        let representative_ids : Vec<usize> = collapsed.get_representatives().iter().map(|p| p.get_id()).collect();
+        asserting("Representatives in original order").that(&representative_ids).is_equal_to(vec![0, 1, 3]);
+        asserting("Members of 0").that(&collapsed.get_members(0).unwrap().to_vec()).is_equal_to(vec![0, 2, 4]);
+        asserting("Weights").that(&collapsed.get_weights()).is_equal_to(vec![3, 2, 1]);
+        asserting("Weight of non-representative").that(&collapsed.get_weight(2)).is_equal_to(0);
+        asserting("Original count").that(&collapsed.original_count()).is_equal_to(6);
+    }
+
+    #[test]
+    fn collapse_within() {
+        let collapsed = CollapsedPoints::collapse_within(&points_with_duplicates(), 6, 1);
+        asserting("Two representatives").that(&collapsed.len()).is_equal_to(2);
+        let mut weights = collapsed.get_weights();
+        weights.sort_unstable();
+        asserting("Weights").that(&weights).is_equal_to(vec![2, 4]);
+        let near_group = collapsed.get_representatives().iter().find(|p| p.get_coordinates()[0] < 20).unwrap().get_id();
+        let mut members = collapsed.get_members(near_group).unwrap().to_vec();
+        members.sort_unstable();
+        asserting("Near duplicate absorbed").that(&members).is_equal_to(vec![0, 2, 3, 4]);
+    }
+
+    #[test]
+    fn expand() {
+        let collapsed = CollapsedPoints::collapse_duplicates(&points_with_duplicates());
+        let mut representative_clustering = integer_clustering();
+        let category = representative_clustering.add_to_new_cluster(0).unwrap();
+        representative_clustering.add_to_cluster(3, category).unwrap();
+        representative_clustering.add_to_new_cluster(1).unwrap();
+        let clustering = collapsed.expand(&representative_clustering);
+        asserting("All points present").that(&clustering.member_count()).is_equal_to(6);
+        asserting("Two clusters").that(&clustering.cluster_count()).is_equal_to(2);
+        asserting("Duplicate with its representative").that(&clustering.are_together(4, 3)).is_equal_to(true);
+        asserting("Other cluster").that(&clustering.are_together(5, 1)).is_equal_to(true);
+        asserting("Apart").that(&clustering.are_together(5, 2)).is_equal_to(false);
+    }
+}
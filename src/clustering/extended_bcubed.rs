@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use super::cluster::Chopped;
+use super::Clustering;
+use super::bcubed::BCubed;
+
+// ........................... MultiLabels ..........................................
+
+/// Assigns each member to one or more categories, as in a gold standard where categories may overlap.
+///
+/// The labels of each member are kept sorted and free of duplicates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiLabels<M : Chopped, L : Chopped> {
+    labels : HashMap<M, Vec<L>>
+}
+
+impl<M : Chopped, L : Chopped> Default for MultiLabels<M, L> {
+    fn default() -> Self { MultiLabels { labels : HashMap::new() } }
+}
+
+impl<M : Chopped, L : Chopped> MultiLabels<M, L> {
+    /// Create an empty set of labels.
+    pub fn new() -> Self { Self::default() }
+
+    /// Create labels from (member, label) pairs. A member that appears in several pairs gets several labels.
+    pub fn from_pairs<I : IntoIterator<Item = (M, L)>>(pairs : I) -> Self {
+        let mut multi_labels = Self::new();
+        for (member, label) in pairs {
+            multi_labels.insert(member, label);
+        }
+        multi_labels
+    }
+
+    /// Label each member of a `Clustering` with the category of its cluster.
+    pub fn from_clustering<G : Iterator<Item = L>>(clustering : &Clustering<L,M,G>) -> Self {
+        Self::from_pairs(
+            clustering.get_clusters().values()
+                .flat_map(|cluster| cluster.get_members().iter().map(move |member| (*member, cluster.get_category())))
+        )
+    }
+
+    /// Add a label to a member.
+    ///
+    ///   - returns - False if the member already had the label.
+    pub fn insert(&mut self, member : M, label : L) -> bool {
+        let labels = self.labels.entry(member).or_default();
+        match labels.binary_search(&label) {
+            Ok(_) => false,
+            Err(position) => { labels.insert(position, label); true }
+        }
+    }
+
+    /// The labels of the member in ascending order, or None if it has none.
+    pub fn get(&self, member : M) -> Option<&[L]> { self.labels.get(&member).map(|labels| labels.as_slice()) }
+
+    /// Number of members labelled.
+    pub fn member_count(&self) -> usize { self.labels.len() }
+
+    /// Iterate over the members and their labels.
+    pub fn iter(&self) -> impl Iterator<Item = (&M, &Vec<L>)> { self.labels.iter() }
+}
+
+// ........................... Extended BCubed ..........................................
+
+impl BCubed {
+    /// Compare two overlapping clusterings using the extended B-Cubed measure, in which items may belong to
+    /// several clusters and categories.
+    ///
+    /// The extension was proposed in section 5 of:
+    ///
+    /// _A comparison of Extrinsic Clustering Evaluation Metrics based on Formal Constraints_
+    /// by **Enrique Amigo, Julio Gonzalo, Javier Artiles, Felisa Verdejo**
+    ///
+    /// For items e and e', let C be the number of solution clusters they share and L the number of gold categories they share.
+    /// ```text
+    ///   Multiplicity precision(e, e') = min(C, L) / C, defined when C > 0
+    ///   Multiplicity recall(e, e')    = min(C, L) / L, defined when L > 0
+    /// ```
+    /// The precision of an item is the average multiplicity precision over the items sharing a cluster with it (itself included),
+    /// and its recall is the average multiplicity recall over the items sharing a category with it.
+    /// The overall precision and recall are the averages over all items. When every item has exactly one cluster and
+    /// one category, this equals `compare`.
+    ///
+    /// As with `tally_squares`, items are not compared pairwise. Items with the same clusters and the same categories
+    /// score the same, so they are counted together, and only groups of items that share a cluster (or category) are compared.
+    /// This is linear in the number of items when each cluster holds few distinct combinations of categories.
+    ///
+    ///   - solution - The clusters of each item whose quality is to be assessed.
+    ///   - gold_standard - The true categories of each item. It must have the same members as the solution.
+    ///   - alpha - A value between zero and one. used to weight `precision` and `recall`.
+    pub fn compare_extended<M : Chopped, C1 : Chopped, C2 : Chopped>(solution : &MultiLabels<M,C1>, gold_standard : &MultiLabels<M,C2>, alpha : f64) -> Self {
+        if solution.member_count() != gold_standard.member_count() {
+            panic!("The solution has {} items but the gold standard has {}", solution.member_count(), gold_standard.member_count());
+        }
+        let mut group_counts : HashMap<(&[C1], &[C2]), u64> = HashMap::new();
+        for (member, clusters) in solution.iter() {
+            let categories = gold_standard.get(*member)
+                .unwrap_or_else(|| panic!("Item {:?} from one Clustering not present in the other", *member));
+            *group_counts.entry((clusters.as_slice(), categories)).or_insert(0) += 1;
+        }
+        let mut clusters = Vec::with_capacity(group_counts.len());
+        let mut categories = Vec::with_capacity(group_counts.len());
+        let mut counts = Vec::with_capacity(group_counts.len());
+        for ((group_clusters, group_categories), count) in group_counts {
+            clusters.push(group_clusters);
+            categories.push(group_categories);
+            counts.push(count);
+        }
+        let n = solution.member_count() as f64;
+        let precision = Self::multiplicity_average(&clusters, &categories, &counts) / n;
+        let recall = Self::multiplicity_average(&categories, &clusters, &counts) / n;
+        BCubed::new(precision, recall, alpha)
+    }
+
+    /// Compare a single-label `Clustering` to a gold standard whose categories may overlap, using the extended B-Cubed measure.
+    ///
+    ///   - solution - The `Clustering` whose quality is to be assessed.
+    ///   - gold_standard - The true categories of each item. It must have the same members as the solution.
+    ///   - alpha - A value between zero and one. used to weight `precision` and `recall`.
+    pub fn compare_to_multi_labels<C1 : Chopped, C2 : Chopped, M : Chopped, G : Iterator<Item = C1>>(solution : &Clustering<C1,M,G>, gold_standard : &MultiLabels<M,C2>, alpha : f64) -> Self {
+        Self::compare_extended(&MultiLabels::from_clustering(solution), gold_standard, alpha)
+    }
+
+    /// Sum over all items of the average multiplicity precision with the items that share one of its `own` labels,
+    /// measured against the `other` labels. Swapping `own` and `other` turns precision into recall.
+    ///
+    ///   - own - Labels of each group of items, whose sharing makes two items neighbors.
+    ///   - other - Labels of each group of items in the other clustering.
+    ///   - counts - Number of items in each group.
+    fn multiplicity_average<A : Chopped, B : Chopped>(own : &[&[A]], other : &[&[B]], counts : &[u64]) -> f64 {
+        let mut groups_by_label : HashMap<A, Vec<usize>> = HashMap::new();
+        for (i_group, labels) in own.iter().enumerate() {
+            for label in labels.iter() {
+                groups_by_label.entry(*label).or_default().push(i_group);
+            }
+        }
+        // Marks the groups already visited as neighbors of the current group, to visit each only once.
+        let mut last_visited_by = vec![usize::MAX; own.len()];
+        let mut sum = 0_f64;
+        for (i_group, labels) in own.iter().enumerate() {
+            let mut weighted_multiplicity = 0_f64;
+            let mut neighbor_count = 0_u64;
+            for label in labels.iter() {
+                for i_neighbor in groups_by_label[label].iter().copied() {
+                    if last_visited_by[i_neighbor] == i_group { continue; }
+                    last_visited_by[i_neighbor] = i_group;
+                    let shared_own = count_shared(labels, own[i_neighbor]);
+                    let shared_other = count_shared(other[i_group], other[i_neighbor]);
+                    weighted_multiplicity += counts[i_neighbor] as f64 * shared_own.min(shared_other) as f64 / shared_own as f64;
+                    neighbor_count += counts[i_neighbor];
+                }
+            }
+            sum += counts[i_group] as f64 * weighted_multiplicity / neighbor_count as f64;
+        }
+        sum
+    }
+}
+
+/// Count the labels common to two sorted lists.
+fn count_shared<L : Chopped>(a : &[L], b : &[L]) -> usize {
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] { i += 1; }
+        else if a[i] > b[j] { j += 1; }
+        else { shared += 1; i += 1; j += 1; }
+    }
+    shared
+}
+
+#[cfg(test)]
+/// Tests of the extended B-Cubed measure, using small hand-built clusterings whose expected values
+/// were computed by hand from the definitions.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use crate::clustering;
+    use crate::clustering::bcubed::BCubed;
+    use super::MultiLabels;
+
+    fn labels(text : &str) -> MultiLabels<usize, usize> {
+        // Same format as from_delimited_string, but an item may appear in several clusters.
+        MultiLabels::from_pairs(
+            text.split(';').enumerate()
+                .flat_map(|(category, members)| members.split(',').map(move |member| (member.parse::<usize>().unwrap(), category)))
+        )
+    }
+
+    fn close(actual : BCubed, precision : f64, recall : f64) -> bool {
+        (actual.get_precision() - precision).abs() < 1e-9 && (actual.get_recall() - recall).abs() < 1e-9
+    }
+
+    #[test]
+    fn single_labels_match_compare() {
+        let solution = clustering::from_delimited_string("1,2,3,4;5,6,7;8,9,10,11,12,13,14");
+        let gold = clustering::from_delimited_string("1,2,3,4,5;6,7,9,12,13,14;8;10;11");
+        let expected = BCubed::compare(&solution, &gold, 0.5);
+        let extended = BCubed::compare_to_multi_labels(&solution, &MultiLabels::from_clustering(&gold), 0.5);
+        asserting(&format!("{:?} vs {:?}", extended, expected)).that(&close(extended, expected.get_precision(), expected.get_recall())).is_equal_to(true);
+    }
+
+    /// An item in two categories must be placed in two clusters for a perfect score.
+    /// Placing it in just one loses recall, and the perfect overlapping clustering scores one.
+    #[test]
+    fn multiplicity_recall() {
+        // Item 3 belongs to both categories.
+        let gold = labels("1,2,3;3,4,5");
+        let perfect = BCubed::compare_extended(&labels("1,2,3;3,4,5"), &gold, 0.5);
+        asserting("Perfect").that(&close(perfect, 1.0, 1.0)).is_equal_to(true);
+
+        let single = BCubed::compare_extended(&labels("1,2,3;4,5"), &gold, 0.5);
+        // Items 1 and 2 are perfect. Item 3 shares a category with all five items, but a cluster only with 1 and 2 (and itself),
+        // so its recall is (1 + 1 + 1/2) / 5 = 1/2. Items 4 and 5 lack item 3, so their recall is 2/3.
+        // Precision is perfect except for item 3 with itself: min(1, 2) / 1 = 1, so perfect.
+        let expected_recall = (1.0 + 1.0 + 0.5 + 2.0 / 3.0 + 2.0 / 3.0) / 5.0;
+        asserting(&format!("Single {:?}", single)).that(&close(single, 1.0, expected_recall)).is_equal_to(true);
+        asserting("Overlap rewarded").that(&(single.similarity() < perfect.similarity())).is_equal_to(true);
+    }
+
+    /// Placing an item in more clusters than it has categories must cost precision.
+    #[test]
+    fn multiplicity_precision() {
+        let gold = labels("1,2;3,4");
+        // Item 2 is wrongly placed in both clusters.
+        let excessive = BCubed::compare_extended(&labels("1,2;2,3,4"), &gold, 0.5);
+        // Item 2 shares one cluster with 1 (right), one with 3 and 4 (wrong), and two with itself but only one category: 1/2.
+        // Precision of 2 is (1 + 1/2 + 0 + 0) / 4. Item 1: (1 + 1) / 2. Items 3 and 4: (0 + 1 + 1) / 3.
+        let expected_precision = (1.0 + (1.5 / 4.0) + 2.0 / 3.0 + 2.0 / 3.0) / 4.0;
+        // Item 2 shares its only category with 1 and itself, and shares at least one cluster with each: min(1,1)/1 = 1. All recall is perfect.
+        asserting(&format!("Excessive {:?}", excessive)).that(&close(excessive, expected_precision, 1.0)).is_equal_to(true);
+    }
+
+    /// Two items sharing two categories should share two clusters; sharing only one costs recall.
+    #[test]
+    fn multiplicity_shared() {
+        let gold = labels("1,2;1,2,3");
+        let perfect = BCubed::compare_extended(&labels("1,2;1,2,3"), &gold, 0.5);
+        let merged = BCubed::compare_extended(&labels("1,2,3"), &gold, 0.5);
+        asserting("Perfect").that(&close(perfect, 1.0, 1.0)).is_equal_to(true);
+        // Items 1 and 2 share two categories but one cluster: recall 1/2 with each other.
+        // Item 1: (1/2 + 1/2 + 1) / 3 against 1 (itself), 2, 3. Same for item 2. Item 3: (1 + 1 + 1) / 3.
+        let expected_recall = (2.0 / 3.0 + 2.0 / 3.0 + 1.0) / 3.0;
+        asserting(&format!("Merged {:?}", merged)).that(&close(merged, 1.0, expected_recall)).is_equal_to(true);
+    }
+}
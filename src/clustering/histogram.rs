@@ -0,0 +1,395 @@
+use std::ops::Range;
+use std::cmp::{min,max};
+use super::msb::MostSignificantBit;
+
+/// Default number of bits of precision kept by a `DistanceHistogram`, giving a relative error below 0.4%.
+pub const DEFAULT_PRECISION_BITS : u8 = 8;
+
+/// A histogram of square distances whose buckets grow logarithmically, in the manner of an **HDR Histogram**.
+///
+/// Every value is counted in a bucket whose width is no more than 2^-precision_bits of the value, so any value
+/// reported by the histogram is within that relative error of a value that was added. Values below 2^precision_bits
+/// are counted exactly. The histogram never stores the values themselves, so its size depends only on the
+/// precision and the magnitude of the largest value (at most 57 x 256 counts for the default precision),
+/// not on how many values are added.
+///
+/// Two histograms with the same precision can be merged, so that separate threads, shards or files
+/// may each fill their own histogram and combine them afterwards. The result does not depend on the
+/// order in which values were added or histograms merged.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DistanceHistogram {
+    /// Number of significant bits retained for each value.
+    precision_bits : u8,
+
+    /// Number of values that fell in each bucket. Grows as larger values are added.
+    counts : Vec<u64>,
+
+    /// Total number of values added.
+    total_count : u64,
+
+    /// Lowest value added, or `u64::MAX` if empty.
+    lowest_value : u64,
+
+    /// Highest value added, or zero if empty.
+    highest_value : u64
+}
+
+/// A place where the sorted values in a `DistanceHistogram` grow from one value to the next.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ValueJump {
+    /// Highest value before the jump.
+    pub value_before_jump : u64,
+
+    /// Lowest value after the jump.
+    pub value_after_jump : u64,
+
+    /// `value_after_jump - value_before_jump`.
+    pub difference : u64,
+
+    /// `value_after_jump / value_before_jump`, treating a `value_before_jump` of zero as one.
+    pub ratio : f64,
+
+    /// Number of values in the histogram that precede the jump.
+    pub values_before_jump : u64
+}
+
+/// Divide the values from zero to `top_of_highest_bin` into ranges of increasing width, for tallying a `DistanceHistogram` into coarse bins.
+///
+///   - `top_of_lowest_bin` - The first range goes from zero (inclusive) to this number (exclusive).
+///   - `top_of_highest_bin` - The last range includes this value.
+///   - `minimum_bin_width` - No range will be narrower than this.
+///   - `multiplier` - The top of each range will equal the top of the previous range times this multiplier,
+///     unless that makes a range whose width is less than `minimum_bin_width`. If this multiplier is
+///     less than 1.001, it will be set to 1.001. A good value is 1.05.
+pub fn logarithmic_bounds(top_of_lowest_bin : u64, top_of_highest_bin : u64, minimum_bin_width : u64, mut multiplier : f64) -> Vec<Range<u64>> {
+    let mut bounds = Vec::with_capacity(1000);
+    if multiplier < 1.001 { multiplier = 1.001; }
+    bounds.push(0..top_of_lowest_bin);
+    let mut next_bottom = top_of_lowest_bin as f64;
+    let mut next_top = (top_of_lowest_bin as f64 * multiplier).ceil();
+    let tippy_top = top_of_highest_bin as f64;
+    let min_width = minimum_bin_width as f64;
+    while next_top < tippy_top {
+        if next_top - next_bottom < min_width {
+            next_top = next_bottom + min_width;
+        }
+        bounds.push(next_bottom as u64..next_top as u64);
+        next_bottom = next_top;
+        next_top *= multiplier;
+    }
+    bounds.push(next_bottom as u64..max(next_top as u64, top_of_highest_bin.saturating_add(1)));
+    bounds
+}
+
+impl DistanceHistogram {
+    /// Create an empty histogram that keeps the given number of significant bits for each value.
+    ///
+    ///   - precision_bits - Must be between 1 and 16. Each extra bit halves the relative error and doubles the number of buckets.
+    pub fn new(precision_bits : u8) -> Self {
+        if !(1..=16).contains(&precision_bits) {
+            panic!("precision_bits must be between 1 and 16, not {}.", precision_bits);
+        }
+        DistanceHistogram {
+            precision_bits,
+            counts : Vec::new(),
+            total_count : 0,
+            lowest_value : u64::MAX,
+            highest_value : 0
+        }
+    }
+
+    /// Number of significant bits retained for each value.
+    pub fn get_precision_bits(&self) -> u8 { self.precision_bits }
+
+    /// Lowest value added, or `u64::MAX` if the histogram is empty.
+    pub fn get_lowest_value(&self) -> u64 { self.lowest_value }
+
+    /// Highest value added, or zero if the histogram is empty.
+    pub fn get_highest_value(&self) -> u64 { self.highest_value }
+
+    /// Number of values added.
+    pub fn len(&self) -> u64 { self.total_count }
+
+    /// Has no value been added?
+    pub fn is_empty(&self) -> bool { self.total_count == 0 }
+
+    /// Count one more occurrence of the value.
+    pub fn add(&mut self, value : u64) {
+        let index = self.bucket_index(value);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+        self.total_count += 1;
+        self.lowest_value = min(self.lowest_value, value);
+        self.highest_value = max(self.highest_value, value);
+    }
+
+    /// Add all the counts from another histogram into this one.
+    ///
+    ///   - other - Must have the same `precision_bits`.
+    pub fn merge(&mut self, other : &Self) {
+        if self.precision_bits != other.precision_bits {
+            panic!("Only histograms with the same precision may be merged.");
+        }
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+        self.total_count += other.total_count;
+        self.lowest_value = min(self.lowest_value, other.lowest_value);
+        self.highest_value = max(self.highest_value, other.highest_value);
+    }
+
+    /// Index of the bucket that counts the given value.
+    ///
+    /// Values below 2^precision_bits have a bucket each. Above that, the buckets for values sharing
+    /// the same most significant bit split the range between one power of two and the next into 2^precision_bits equal parts.
+    pub fn bucket_index(&self, value : u64) -> usize {
+        let precision = self.precision_bits as usize;
+        let msb = value.msb();
+        if msb < precision { value as usize }
+        else {
+            let shift = msb - precision;
+            let sub_bucket = (value >> shift) as usize - (1 << precision);
+            ((shift + 1) << precision) + sub_bucket
+        }
+    }
+
+    /// Range of values counted by the bucket with the given index (end exclusive, except that the last
+    /// bucket ends at `u64::MAX`).
+    pub fn bucket_bounds(&self, index : usize) -> Range<u64> {
+        let precision = self.precision_bits as usize;
+        let sub_bucket_count = 1_usize << precision;
+        if index < sub_bucket_count { index as u64..index as u64 + 1 }
+        else {
+            let shift = (index >> precision) - 1;
+            let sub_bucket = (index & (sub_bucket_count - 1)) as u64;
+            let start = (sub_bucket_count as u64 + sub_bucket) << shift;
+            start..start.saturating_add(1 << shift)
+        }
+    }
+
+    /// Visit each non-empty bucket in ascending order, yielding the lowest and highest values that
+    /// could be in it (clipped to the lowest and highest values added) and its count.
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, u64, u64)> + '_ {
+        self.counts.iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(move |(index, count)| {
+                let bounds = self.bucket_bounds(index);
+                (max(bounds.start, self.lowest_value), min(bounds.end - 1, self.highest_value), *count)
+            })
+    }
+
+    /// Estimate the value below which the given fraction of values fall.
+    ///
+    ///   - quantile - A fraction between zero and one. Zero yields the lowest value and one the highest.
+    ///   - returns - The highest value that could be in the bucket holding the value of that rank,
+    ///     or zero if the histogram is empty.
+    pub fn value_at_quantile(&self, quantile : f64) -> u64 {
+        if self.is_empty() { return 0; }
+        if quantile <= 0.0 { return self.lowest_value; }
+        let quantile = quantile.min(1.0);
+        let rank = max(1, (quantile * self.total_count as f64).ceil() as u64);
+        let mut values_so_far = 0;
+        for (_, highest, count) in self.buckets() {
+            values_so_far += count;
+            if values_so_far >= rank { return highest; }
+        }
+        self.highest_value
+    }
+
+    /// Tally the buckets that fall within each of the given ranges, assigning each bucket to the range holding its lowest value.
+    ///
+    ///   - bounds - Contiguous ranges in ascending order, such as those from `logarithmic_bounds`.
+    ///   - returns - For each range, the lowest and highest values that could be in it (the highest clipped to the range)
+    ///     and its count, or `None` if no values fall in it.
+    pub fn tally(&self, bounds : &[Range<u64>]) -> Vec<Option<(u64, u64, u64)>> {
+        let mut tallies = vec![None; bounds.len()];
+        let mut i_range = 0;
+        for (lowest, highest, count) in self.buckets() {
+            while i_range < bounds.len() && lowest >= bounds[i_range].end { i_range += 1; }
+            if i_range == bounds.len() { break; }
+            if lowest < bounds[i_range].start { continue; }
+            // A bucket may straddle two ranges, so keep its values within the bounds of this range.
+            let highest = min(highest, max(lowest, bounds[i_range].end.saturating_sub(1)));
+            tallies[i_range] = Some(match tallies[i_range] {
+                Some((range_lowest, range_highest, range_count)) => (min(range_lowest, lowest), max(range_highest, highest), range_count + count),
+                None => (lowest, highest, count)
+            });
+        }
+        tallies
+    }
+
+    /// Combine consecutive ranges that hold too few values, so that each holds at least `minimum_count` values
+    /// (except possibly the last).
+    ///
+    /// A range with too few values is widened to take in the ranges that follow it until it holds enough.
+    ///
+    ///   - bounds - Contiguous ranges in ascending order.
+    ///   - returns - The combined ranges, which cover the same values.
+    pub fn consolidate(&self, bounds : &[Range<u64>], minimum_count : u64) -> Vec<Range<u64>> {
+        let mut consolidated = Vec::with_capacity(bounds.len());
+        let mut held : Option<(Range<u64>, u64)> = None;
+        for (range, tally) in bounds.iter().zip(self.tally(bounds)) {
+            let count = tally.map_or(0, |(_, _, count)| count);
+            let (range, count) = match held.take() {
+                Some((held_range, held_count)) => (held_range.start..range.end, held_count + count),
+                None => (range.clone(), count)
+            };
+            if count >= minimum_count { consolidated.push(range); }
+            else { held = Some((range, count)); }
+        }
+        if let Some((range, _)) = held {
+            consolidated.push(range);
+        }
+        consolidated
+    }
+
+    /// Find where the sorted values grow by the largest amount from one value to the next,
+    /// considering only the values that fall within the given range.
+    ///
+    /// Values that share a bucket are indistinguishable, so the largest jump is sought between consecutive non-empty buckets.
+    /// If several jumps are equally large, the first is chosen.
+    ///
+    ///   - values - Only consider buckets whose lowest value falls in this range.
+    ///   - previous_value - The value preceding the range, to compare to the first bucket in range.
+    ///   - returns - The jump, or `None` if no values fall in the range.
+    pub fn largest_jump(&self, values : Range<u64>, previous_value : u64) -> Option<ValueJump> {
+        self.largest_jump_by(values, previous_value, |jump| jump.difference)
+    }
+
+    /// Find where the sorted values grow by the largest ratio from one value to the next,
+    /// considering only the values that fall within the given range.
+    ///
+    /// Values that share a bucket are indistinguishable, so the largest jump is sought between consecutive non-empty buckets.
+    ///
+    ///   - values - Only consider buckets whose lowest value falls in this range.
+    ///   - previous_value - The value preceding the range, to compare to the first bucket in range.
+    ///   - returns - The jump, or `None` if no values fall in the range.
+    pub fn largest_relative_jump(&self, values : Range<u64>, previous_value : u64) -> Option<ValueJump> {
+        self.largest_jump_by(values, previous_value, |jump| jump.ratio)
+    }
+
+    /// Find the first of the jumps between consecutive non-empty buckets in the range for which `size` is largest.
+    fn largest_jump_by<K, F>(&self, values : Range<u64>, previous_value : u64, size : F) -> Option<ValueJump>
+    where K : PartialOrd, F : Fn(&ValueJump) -> K
+    {
+        let mut largest_jump : Option<ValueJump> = None;
+        let mut previous_value = previous_value;
+        let mut values_so_far = 0;
+        for (lowest, highest, count) in self.buckets() {
+            if lowest >= values.end { break; }
+            if lowest >= values.start {
+                let jump = ValueJump {
+                    value_before_jump : previous_value,
+                    value_after_jump : lowest,
+                    difference : lowest.saturating_sub(previous_value),
+                    ratio : lowest as f64 / max(previous_value, 1) as f64,
+                    values_before_jump : values_so_far
+                };
+                let is_larger = match largest_jump {
+                    Some(ref largest) => size(&jump) > size(largest),
+                    None => true
+                };
+                if is_larger { largest_jump = Some(jump); }
+                previous_value = highest;
+            }
+            values_so_far += count;
+        }
+        largest_jump
+    }
+}
+
+impl Default for DistanceHistogram {
+    fn default() -> Self { Self::new(DEFAULT_PRECISION_BITS) }
+}
+
+#[cfg(test)]
+/// Tests of the bucket layout, merging and queries.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use super::{DistanceHistogram, logarithmic_bounds};
+
+    #[test]
+    fn buckets_are_contiguous() {
+        let histogram = DistanceHistogram::new(4);
+        let mut expected_start = 0;
+        for index in 0..(60 * 16) {
+            let bounds = histogram.bucket_bounds(index);
+            asserting(&format!("Bucket {} starts where the previous ended", index)).that(&bounds.start).is_equal_to(expected_start);
+            asserting("Start maps to this bucket").that(&histogram.bucket_index(bounds.start)).is_equal_to(index);
+            asserting("Last value maps to this bucket").that(&histogram.bucket_index(bounds.end - 1)).is_equal_to(index);
+            asserting("Relative error within precision").that(&((bounds.end - bounds.start) as f64 <= (bounds.start as f64 / 16.0).max(1.0))).is_equal_to(true);
+            expected_start = bounds.end;
+        }
+        asserting("Largest value fits").that(&histogram.bucket_bounds(histogram.bucket_index(u64::MAX)).contains(&(u64::MAX - 1))).is_equal_to(true);
+    }
+
+    #[test]
+    fn merge_matches_adding_all() {
+        let mut all = DistanceHistogram::default();
+        let mut low = DistanceHistogram::default();
+        let mut high = DistanceHistogram::default();
+        for value in (0..5000_u64).map(|i| i * i * 37) {
+            all.add(value);
+            if value % 2 == 0 { low.add(value); } else { high.add(value); }
+        }
+        high.merge(&low);
+        asserting("Same histogram").that(&high).is_equal_to(all);
+    }
+
+    #[test]
+    fn quantiles() {
+        let mut histogram = DistanceHistogram::default();
+        for value in 1..=1000_u64 { histogram.add(value * 1000); }
+        asserting("Lowest").that(&histogram.value_at_quantile(0.0)).is_equal_to(1000);
+        asserting("Highest").that(&histogram.value_at_quantile(1.0)).is_equal_to(1_000_000);
+        let median = histogram.value_at_quantile(0.5);
+        asserting(&format!("Median {} near 500,000", median)).that(&(500_000..502_000).contains(&median)).is_equal_to(true);
+    }
+
+    #[test]
+    fn largest_jumps() {
+        let mut histogram = DistanceHistogram::default();
+        for value in 100..200_u64 { histogram.add(value); }
+        for value in 1000..1010_u64 { histogram.add(value); }
+        let jump = histogram.largest_relative_jump(0..u64::MAX, 100).unwrap();
+        asserting("Before jump").that(&jump.value_before_jump).is_equal_to(199);
+        asserting("After jump").that(&jump.value_after_jump).is_equal_to(1000);
+        asserting("Values before jump").that(&jump.values_before_jump).is_equal_to(100);
+        asserting("No values in range").that(&histogram.largest_relative_jump(300..900, 200).is_none()).is_equal_to(true);
+
+        // From 10 to 20 doubles, but from 100 to 150 grows more.
+        let mut histogram = DistanceHistogram::default();
+        for value in [10, 20, 100, 150].iter() { histogram.add(*value); }
+        asserting("Largest ratio").that(&histogram.largest_relative_jump(0..200, 10).unwrap().value_before_jump).is_equal_to(20);
+        asserting("Largest difference").that(&histogram.largest_jump(0..200, 10).unwrap().value_before_jump).is_equal_to(20);
+        asserting("Largest difference after 100").that(&histogram.largest_jump(100..200, 20).unwrap().value_before_jump).is_equal_to(20);
+        asserting("Largest ratio after 100").that(&histogram.largest_relative_jump(100..200, 90).unwrap().value_before_jump).is_equal_to(100);
+    }
+
+    #[test]
+    fn tally_and_consolidate() {
+        let bounds = logarithmic_bounds(20, 200, 20, 1.05);
+        asserting("Starts at zero").that(&bounds[0]).is_equal_to(0..20);
+        asserting("Covers the top").that(&bounds.last().unwrap().contains(&200)).is_equal_to(true);
+        let mut histogram = DistanceHistogram::default();
+        for value in [5, 25, 30, 61, 62].iter() { histogram.add(*value); }
+        let tallies = histogram.tally(&bounds);
+        asserting("First range").that(&tallies[0]).is_equal_to(Some((5, 5, 1)));
+        asserting("Second range").that(&tallies[1]).is_equal_to(Some((25, 30, 2)));
+        asserting("Empty range").that(&tallies[2]).is_equal_to(None);
+
+        // The lone value in the first range is held and combined upward with the next range.
+        let consolidated = histogram.consolidate(&bounds, 2);
+        asserting("Held range combined upward").that(&consolidated[0]).is_equal_to(0..40);
+        asserting("Same coverage").that(&consolidated.last().unwrap().end).is_equal_to(bounds.last().unwrap().end);
+        let total : u64 = histogram.tally(&consolidated).iter().map(|tally| tally.map_or(0, |(_, _, count)| count)).sum();
+        asserting("No values lost").that(&total).is_equal_to(5);
+    }
+}
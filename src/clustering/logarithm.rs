@@ -31,8 +31,8 @@ use super::msb::MostSignificantBit;
 ///    - Padé Approximation error is ±0.000025
 ///    - Taylor series has error ±0.00014 (five times worse)
 pub fn log_ratio(numerator : u64, denominator : u64) -> f64 {
-    // Ln(2) comes from The On-line Encyclopedia of Integer Sequences https://oeis.org/A002162
-    const LOG2 : f64 = 0.6931471805599453; 
+    // Ln(2), also listed in The On-line Encyclopedia of Integer Sequences https://oeis.org/A002162
+    const LOG2 : f64 = f64::consts::LN_2; 
     if numerator == 0 || denominator == 0 { return f64::NAN; }
 
     // Range reduction 
@@ -52,10 +52,8 @@ pub fn log_ratio(numerator : u64, denominator : u64) -> f64 {
         else {
             -log_1_plus_x((reduced_denominator / reduced_numerator) - 1.0)
         };
-    let approximate_log = (n as f64 - d as f64) * LOG2 + log_fraction;
-    
-    // let approximate_log = (n as f64 - d as f64) * LOG2 + log_1_plus_x(reduced_numerator - 1.0) - log_1_plus_x(reduced_denominator - 1.0);
-    approximate_log
+    // Equivalent to: (n as f64 - d as f64) * LOG2 + log_1_plus_x(reduced_numerator - 1.0) - log_1_plus_x(reduced_denominator - 1.0)
+    (n as f64 - d as f64) * LOG2 + log_fraction
 }
 
 /// Approximate the natural logarithm of the ratio of two unsigned integers to an accuracy of ±0.01,
@@ -65,7 +63,7 @@ pub fn log_ratio_small(numerator : u64, denominator : u64) -> f64 {
     if numerator == 0 || denominator == 0 { return f64::NAN; }
     let ratio = numerator as f64 / denominator as f64;
 
-    if ratio <= 4.5 && ratio >= 0.18 {
+    if (0.18..=4.5).contains(&ratio) {
         return log_1_plus_x(ratio - 1.0);
     }
     ratio.ln()
@@ -96,7 +94,7 @@ fn log_1_plus_x(x : f64) -> f64 {
     // 2.0 * y * (15.0 - 4.0 * y_squared) / (15.0 - 9.0 * y_squared)
 
     // Reduce multiplications: (8/9)y·(3.75 - y²)/((5/3) - y²)
-    0.8888888888888889 * y * (3.75 - y_squared) / (1.6666666666666667 - y_squared)
+    0.888_888_888_888_889 * y * (3.75 - y_squared) / (1.6666666666666667 - y_squared)
 }
 
 
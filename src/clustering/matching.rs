@@ -0,0 +1,337 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::iter::Chain;
+use std::vec::IntoIter;
+use super::cluster::Chopped;
+use super::Clustering;
+
+// ........................... ConfusionMatrix ..........................................
+
+/// Counts of the members shared by every solution cluster (row) and gold category (column).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfusionMatrix<C1 : Chopped, C2 : Chopped> {
+    /// Categories of the solution clusters, in ascending order, one per row.
+    pub solution_categories : Vec<C1>,
+
+    /// Gold categories, in ascending order, one per column.
+    pub gold_categories : Vec<C2>,
+
+    /// Number of members shared by each solution cluster and gold category, indexed by row, then column.
+    pub counts : Vec<Vec<usize>>
+}
+
+impl<C1 : Chopped, C2 : Chopped> ConfusionMatrix<C1, C2> {
+    /// Tally the members shared by each solution cluster and gold category.
+    ///
+    ///   - solution - The `Clustering` whose clusters form the rows.
+    ///   - gold_standard - The `Clustering` whose categories form the columns. It must have the same members as the solution.
+    pub fn new<M : Chopped, G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(solution : &Clustering<C1,M,G1>, gold_standard : &Clustering<C2,M,G2>) -> Self {
+        if solution.member_count() != gold_standard.member_count() {
+            panic!("The solution has {} items but the gold standard has {}", solution.member_count(), gold_standard.member_count());
+        }
+        let mut solution_categories : Vec<C1> = solution.get_clusters().keys().copied().collect();
+        solution_categories.sort();
+        let mut gold_categories : Vec<C2> = gold_standard.get_clusters().keys().copied().collect();
+        gold_categories.sort();
+        let column_of : HashMap<C2, usize> = gold_categories.iter().enumerate().map(|(column, category)| (*category, column)).collect();
+        let counts = solution_categories.iter()
+            .map(|category| {
+                let mut row = vec![0_usize; gold_categories.len()];
+                for member in solution.get_cluster(*category).unwrap().get_members() {
+                    let gold_category = gold_standard.get_category(*member)
+                        .unwrap_or_else(|| panic!("Item {:?} from one Clustering not present in the other", *member));
+                    row[column_of[&gold_category]] += 1;
+                }
+                row
+            })
+            .collect();
+        ConfusionMatrix { solution_categories, gold_categories, counts }
+    }
+
+    /// Number of members shared by the solution cluster and gold category, or zero if either is absent.
+    pub fn get(&self, solution_category : C1, gold_category : C2) -> usize {
+        match (self.solution_categories.binary_search(&solution_category), self.gold_categories.binary_search(&gold_category)) {
+            (Ok(row), Ok(column)) => self.counts[row][column],
+            _ => 0
+        }
+    }
+
+    /// Total number of members.
+    pub fn member_count(&self) -> usize { self.counts.iter().map(|row| row.iter().sum::<usize>()).sum() }
+}
+
+impl<C1 : Chopped, C2 : Chopped> Display for ConfusionMatrix<C1, C2> {
+    /// A table with a row per solution cluster and a column per gold category.
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let headings : Vec<String> = self.gold_categories.iter().map(|category| format!("{:?}", category)).collect();
+        let labels : Vec<String> = self.solution_categories.iter().map(|category| format!("{:?}", category)).collect();
+        let label_width = labels.iter().map(|label| label.len()).max().unwrap_or(0);
+        let widths : Vec<usize> = headings.iter().enumerate()
+            .map(|(column, heading)| self.counts.iter().map(|row| row[column].to_string().len()).max().unwrap_or(0).max(heading.len()))
+            .collect();
+        write!(f, "{:width$}", "", width = label_width)?;
+        for (heading, width) in headings.iter().zip(widths.iter()) {
+            write!(f, " {:>width$}", heading, width = width)?;
+        }
+        writeln!(f)?;
+        for (label, row) in labels.iter().zip(self.counts.iter()) {
+            write!(f, "{:>width$}", label, width = label_width)?;
+            for (count, width) in row.iter().zip(widths.iter()) {
+                write!(f, " {:>width$}", count, width = width)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// ........................... ClusterMatching ..........................................
+
+/// The one-to-one pairing of solution clusters with gold categories that shares the most members,
+/// found by the Hungarian algorithm.
+///
+/// Clusters that overlap no category left for them (because there are more clusters than categories,
+/// or the categories they overlap are better matched elsewhere) are left unmatched, as are such categories.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusterMatching<C1 : Chopped, C2 : Chopped> {
+    /// Each matched solution cluster, its gold category and the number of members they share, in order of solution category.
+    pub matches : Vec<(C1, C2, usize)>,
+
+    /// Solution clusters matched to no gold category, in ascending order.
+    pub unmatched_solution : Vec<C1>,
+
+    /// Gold categories matched to no solution cluster, in ascending order.
+    pub unmatched_gold : Vec<C2>,
+
+    /// Members shared by every solution cluster and gold category.
+    pub confusion : ConfusionMatrix<C1, C2>
+}
+
+impl<C1 : Chopped, C2 : Chopped> ClusterMatching<C1, C2> {
+    /// Match the clusters of the solution to the categories of the gold standard so that the matched pairs share as many members as possible.
+    ///
+    /// Runs in time proportional to K²L, where K is the lesser and L the greater of the number of clusters and of categories.
+    ///
+    ///   - solution - The `Clustering` whose clusters are to be named.
+    ///   - gold_standard - The `Clustering` whose categories supply the names. It must have the same members as the solution.
+    pub fn new<M : Chopped, G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(solution : &Clustering<C1,M,G1>, gold_standard : &Clustering<C2,M,G2>) -> Self {
+        let confusion = ConfusionMatrix::new(solution, gold_standard);
+        let rows = confusion.solution_categories.len();
+        let columns = confusion.gold_categories.len();
+        // The algorithm minimizes cost over a matrix with no more rows than columns, so negate the overlaps
+        // and transpose if there are more clusters than categories.
+        let row_to_column : Vec<Option<usize>> = if rows <= columns {
+            let cost : Vec<Vec<i64>> = confusion.counts.iter().map(|row| row.iter().map(|count| -(*count as i64)).collect()).collect();
+            hungarian(&cost).into_iter().map(Some).collect()
+        }
+        else {
+            let cost : Vec<Vec<i64>> = (0..columns).map(|column| confusion.counts.iter().map(|row| -(row[column] as i64)).collect()).collect();
+            let mut row_to_column = vec![None; rows];
+            for (column, row) in hungarian(&cost).into_iter().enumerate() {
+                row_to_column[row] = Some(column);
+            }
+            row_to_column
+        };
+        let mut matches = Vec::new();
+        let mut unmatched_solution = Vec::new();
+        let mut matched_columns = vec![false; columns];
+        for (row, column) in row_to_column.into_iter().enumerate() {
+            match column {
+                // A pair sharing no members is no match at all.
+                Some(column) if confusion.counts[row][column] > 0 => {
+                    matched_columns[column] = true;
+                    matches.push((confusion.solution_categories[row], confusion.gold_categories[column], confusion.counts[row][column]));
+                },
+                _ => unmatched_solution.push(confusion.solution_categories[row])
+            }
+        }
+        let unmatched_gold = confusion.gold_categories.iter().zip(matched_columns.iter())
+            .filter(|(_, matched)| !**matched)
+            .map(|(category, _)| *category)
+            .collect();
+        ClusterMatching { matches, unmatched_solution, unmatched_gold, confusion }
+    }
+
+    /// The gold category matched to the solution cluster, if any.
+    pub fn gold_category_of(&self, solution_category : C1) -> Option<C2> {
+        self.matches.iter().find(|(category, _, _)| *category == solution_category).map(|(_, gold_category, _)| *gold_category)
+    }
+
+    /// Fraction of all members that fall in a matched pair, the accuracy of the solution read as a classifier
+    /// whose classes are named by the matching.
+    pub fn accuracy(&self) -> f64 {
+        let member_count = self.confusion.member_count();
+        if member_count == 0 { 0.0 }
+        else { self.matches.iter().map(|(_, _, overlap)| overlap).sum::<usize>() as f64 / member_count as f64 }
+    }
+
+    /// Copy the solution, naming each matched cluster by its gold category.
+    ///
+    ///   - solution - The `Clustering` that was matched.
+    ///   - new_categories - Supplies categories for the unmatched clusters. Any gold category it yields is skipped.
+    ///     Once those are assigned, it becomes the category generator of the copy.
+    pub fn relabel<M : Chopped, G1 : Iterator<Item = C1>, G : Iterator<Item = C2>>(&self, solution : &Clustering<C1,M,G1>, new_categories : G) -> Clustering<C2, M, Chain<IntoIter<C2>, G>> {
+        let gold_categories : HashSet<C2> = self.confusion.gold_categories.iter().copied().collect();
+        let mut new_categories = new_categories;
+        let mut sources : Vec<C1> = self.matches.iter().map(|(category, _, _)| *category).collect();
+        let mut targets : Vec<C2> = self.matches.iter().map(|(_, gold_category, _)| *gold_category).collect();
+        for category in self.unmatched_solution.iter() {
+            let target = new_categories.by_ref().find(|candidate| !gold_categories.contains(candidate))
+                .unwrap_or_else(|| panic!("new_categories ran out of categories for unmatched cluster {:?}", category));
+            sources.push(*category);
+            targets.push(target);
+        }
+        // The copy creates each cluster with the next category from its generator, so the generator begins with the targets in order.
+        let mut relabelled = Clustering::empty(targets.into_iter().chain(new_categories));
+        for category in sources {
+            let cluster = solution.get_cluster(category).unwrap_or_else(|| panic!("No Cluster for category {:?}", category));
+            let mut members = cluster.get_members().iter();
+            if let Some(first) = members.next() {
+                let target = relabelled.add_to_new_cluster(*first).unwrap_or_else(|_| panic!("Unable to add {:?} to a new cluster", first));
+                for member in members {
+                    relabelled.add_to_cluster(*member, target).unwrap_or_else(|_| panic!("Unable to add {:?} to cluster {:?}", member, target));
+                }
+            }
+        }
+        relabelled
+    }
+}
+
+impl<C1 : Chopped, C2 : Chopped> Display for ClusterMatching<C1, C2> {
+    /// One line per matched cluster, then the unmatched clusters and categories, and the accuracy.
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        for (category, gold_category, overlap) in self.matches.iter() {
+            let size = self.confusion.solution_categories.binary_search(category)
+                .map_or(0, |row| self.confusion.counts[row].iter().sum::<usize>());
+            writeln!(f, "Solution cluster {:?} is gold category {:?} ({} of {} members)", category, gold_category, overlap, size)?;
+        }
+        if !self.unmatched_solution.is_empty() { writeln!(f, "Unmatched solution clusters: {:?}", self.unmatched_solution)?; }
+        if !self.unmatched_gold.is_empty() { writeln!(f, "Unmatched gold categories: {:?}", self.unmatched_gold)?; }
+        writeln!(f, "Accuracy {:.4}", self.accuracy())
+    }
+}
+
+/// Assign each row of the cost matrix to a distinct column so as to minimize the total cost (the Hungarian algorithm,
+/// in the O(n²m) form that maintains potentials on rows and columns).
+///
+///   - cost - A matrix with no more rows than columns.
+///   - returns - The column assigned to each row.
+fn hungarian(cost : &[Vec<i64>]) -> Vec<usize> {
+    let rows = cost.len();
+    if rows == 0 { return Vec::new(); }
+    let columns = cost[0].len();
+    // Index zero is a sentinel, so rows and columns are numbered from one.
+    let mut row_potential = vec![0_i64; rows + 1];
+    let mut column_potential = vec![0_i64; columns + 1];
+    let mut row_of_column = vec![0_usize; columns + 1];
+    let mut way = vec![0_usize; columns + 1];
+    for row in 1..=rows {
+        row_of_column[0] = row;
+        let mut column = 0;
+        let mut min_slack = vec![i64::MAX; columns + 1];
+        let mut used = vec![false; columns + 1];
+        loop {
+            used[column] = true;
+            let current_row = row_of_column[column];
+            let mut delta = i64::MAX;
+            let mut next_column = 0;
+            for j in 1..=columns {
+                if used[j] { continue; }
+                let slack = cost[current_row - 1][j - 1] - row_potential[current_row] - column_potential[j];
+                if slack < min_slack[j] {
+                    min_slack[j] = slack;
+                    way[j] = column;
+                }
+                if min_slack[j] < delta {
+                    delta = min_slack[j];
+                    next_column = j;
+                }
+            }
+            for j in 0..=columns {
+                if used[j] {
+                    row_potential[row_of_column[j]] += delta;
+                    column_potential[j] -= delta;
+                }
+                else {
+                    min_slack[j] -= delta;
+                }
+            }
+            column = next_column;
+            if row_of_column[column] == 0 { break; }
+        }
+        // Flip the alternating path back to the start to enlarge the matching.
+        loop {
+            let previous = way[column];
+            row_of_column[column] = row_of_column[previous];
+            column = previous;
+            if column == 0 { break; }
+        }
+    }
+    let mut column_of_row = vec![0_usize; rows];
+    for j in 1..=columns {
+        if row_of_column[j] != 0 { column_of_row[row_of_column[j] - 1] = j - 1; }
+    }
+    column_of_row
+}
+
+#[cfg(test)]
+/// Tests of matching clusters to categories.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use crate::clustering;
+    use super::{ClusterMatching, hungarian};
+
+    #[test]
+    fn hungarian_beats_greedy() {
+        // Greedy would take the 9, then be left with 1 + 2 = 3; the best total is 8 + 7 + 6 = 21.
+        let cost = vec![
+            vec![-9, -8, -1],
+            vec![-7, -1, -1],
+            vec![-1, -2, -6]
+        ];
+        asserting("Optimal assignment").that(&hungarian(&cost)).is_equal_to(vec![1, 0, 2]);
+        let wide = vec![vec![-1, -5, -3, -2], vec![-4, -6, -1, -1]];
+        asserting("More columns than rows").that(&hungarian(&wide)).is_equal_to(vec![1, 0]);
+    }
+
+    #[test]
+    fn match_and_relabel() {
+        // Solution clusters: 0 = {1,2,3,4}, 1 = {5,6,7}, 2 = {8,9}, 3 = {10}
+        // Gold categories named by strings.
+        let solution = clustering::from_delimited_string("1,2,3,4;5,6,7;8,9;10");
+        let names = ["fraud-ring-A", "fraud-ring-B", "legitimate"];
+        let mut gold = clustering::Clustering::empty(names.iter().copied());
+        for (member, category) in [(1, "fraud-ring-A"), (2, "fraud-ring-A"), (3, "fraud-ring-A"), (4, "fraud-ring-B"), (5, "fraud-ring-B"), (6, "fraud-ring-B"),
+                                   (7, "legitimate"), (8, "legitimate"), (9, "legitimate"), (10, "legitimate")].iter() {
+            if gold.contains_category(*category) { gold.add_to_cluster(*member, *category).unwrap(); }
+            else { gold.add_to_new_cluster(*member).unwrap(); }
+        }
+        let matching = ClusterMatching::new(&solution, &gold);
+        asserting("Matches").that(&matching.matches).is_equal_to(vec![(0, "fraud-ring-A", 3), (1, "fraud-ring-B", 2), (2, "legitimate", 2)]);
+        asserting("Unmatched solution").that(&matching.unmatched_solution).is_equal_to(vec![3]);
+        asserting("Nothing left of gold").that(&matching.unmatched_gold.is_empty()).is_equal_to(true);
+        asserting("Accuracy").that(&matching.accuracy()).is_equal_to(0.7);
+        asserting("Confusion").that(&matching.confusion.get(1, "legitimate")).is_equal_to(1);
+        asserting("Named").that(&matching.gold_category_of(1)).is_equal_to(Some("fraud-ring-B"));
+        let report = format!("{}", matching);
+        asserting("Report").that(&report.contains("Solution cluster 1 is gold category \"fraud-ring-B\" (2 of 3 members)")).is_equal_to(true);
+
+        let relabelled = matching.relabel(&solution, ["legitimate", "other"].iter().copied());
+        asserting("Relabelled").that(&relabelled.get_category(5)).is_equal_to(Some("fraud-ring-B"));
+        asserting("Unmatched takes a new name").that(&relabelled.get_category(10)).is_equal_to(Some("other"));
+        asserting("Same partition").that(&relabelled.are_together(8, 9)).is_equal_to(true);
+        asserting("Every member").that(&relabelled.member_count()).is_equal_to(10);
+    }
+
+    /// More solution clusters than gold categories: each category is matched once.
+    #[test]
+    fn more_clusters_than_categories() {
+        let solution = clustering::from_delimited_string("1,2;3,4;5,6,7,8");
+        let gold = clustering::from_delimited_string("1,2,3,4;5,6,7,8");
+        let matching = ClusterMatching::new(&solution, &gold);
+        asserting("Two matches").that(&matching.matches.len()).is_equal_to(2);
+        asserting("One unmatched").that(&matching.unmatched_solution.len()).is_equal_to(1);
+        asserting("Accuracy").that(&matching.accuracy()).is_equal_to(0.75);
+    }
+}
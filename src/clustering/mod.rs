@@ -1,12 +1,29 @@
 use std::collections::{HashMap};
 use std::fmt::{Formatter,Debug};
 use std::ops::Range;
-use std::usize;
 pub mod cluster;
 pub mod bcubed;
+pub mod bcubed_breakdown;
+pub mod extended_bcubed;
+pub mod bcubed_tracker;
 pub mod single_linkage;
 pub mod logarithm;
 pub mod msb;
+pub mod histogram;
+pub mod duplicates;
+pub mod weights;
+pub mod spanning_tree;
+pub mod streaming;
+pub mod tuning;
+pub mod auto;
+pub mod config;
+pub mod target_count;
+pub mod count_estimate;
+pub mod resampling;
+pub mod matching;
+pub mod diff;
+#[cfg(feature = "parallel")]
+pub mod parallel;
 
 use self::cluster::{Cluster, Chopped};
 
@@ -41,7 +58,7 @@ impl<C : Chopped, M : Chopped, G : Iterator<Item = C>> Clustering<C, M, G> {
     pub fn uncategorized<I : Iterator<Item = M>>(items : &mut I, category_generator : G) -> Self {
         let mut clustering = Clustering::empty(category_generator);
         for member in items {
-            clustering.add_to_new_cluster(member).expect(&format!("Unable to add item {:?} to new cluster", member));
+            clustering.add_to_new_cluster(member).unwrap_or_else(|_| panic!("Unable to add item {:?} to new cluster", member));
         }
         clustering
     }
@@ -111,7 +128,7 @@ impl<C : Chopped, M : Chopped, G : Iterator<Item = C>> Clustering<C, M, G> {
                 let mut cluster2_members : Vec<M> = Vec::new();
                 {
                   let cluster2 = self.get_cluster(category2).unwrap();
-                  cluster2_members.extend(cluster2.get_members().iter().map(|m| m.clone()));
+                  cluster2_members.extend(cluster2.get_members().iter().copied());
                 }
                 for member in cluster2_members.iter() {
                     self.member_to_cluster.insert(*member, category1);
@@ -124,8 +141,8 @@ impl<C : Chopped, M : Chopped, G : Iterator<Item = C>> Clustering<C, M, G> {
                 
                 self.clusters.remove(&category2);
             },
-            (Some(category1), None) => { self.add_to_cluster(item2, category1).expect(&format!("Unable to add item to category {:?}", category1)); },
-            (None, Some(category2)) => { self.add_to_cluster(item1, category2).expect(&format!("Unable to add item to category {:?}", category2)); },
+            (Some(category1), None) => { self.add_to_cluster(item2, category1).unwrap_or_else(|_| panic!("Unable to add item to category {:?}", category1)); },
+            (None, Some(category2)) => { self.add_to_cluster(item1, category2).unwrap_or_else(|_| panic!("Unable to add item to category {:?}", category2)); },
             (None, None) => {
                 let new_category = self.add_to_new_cluster(item1).unwrap();
                 let _ = self.add_to_cluster(item2, new_category);
@@ -145,11 +162,11 @@ impl<C : Chopped, M : Chopped, G : Iterator<Item = C>> Clustering<C, M, G> {
         match self.get_category(item) {
             Some(category) => {
                 {
-                    let cluster = self.get_cluster_mut(category).expect(&format!("No Cluster for category {:?}", category));
+                    let cluster = self.get_cluster_mut(category).unwrap_or_else(|| panic!("No Cluster for category {:?}", category));
                     cluster.remove_member(&item);
                     if cluster.is_empty() { self.clusters.remove(&category); }
                 }
-                self.member_to_cluster.remove(&item).expect(&format!("Member {:?} not in Clustering index", item));
+                self.member_to_cluster.remove(&item).unwrap_or_else(|| panic!("Member {:?} not in Clustering index", item));
                 true
             },
             None => false
@@ -175,11 +192,11 @@ impl<C : Chopped, M : Chopped, G : Iterator<Item = C>> Clustering<C, M, G> {
             },
             Some(current_category) if current_category != new_category  => {
                 self.remove_item(item);
-                self.add_to_cluster(item, new_category).expect(&format!("Unable to add item to new category {:?}", new_category));
+                self.add_to_cluster(item, new_category).unwrap_or_else(|_| panic!("Unable to add item to new category {:?}", new_category));
                 true
             },
             None => {
-                self.add_to_cluster(item, new_category).expect(&format!("Unable to add item to new category {:?}", new_category));
+                self.add_to_cluster(item, new_category).unwrap_or_else(|_| panic!("Unable to add item to new category {:?}", new_category));
                 true
             },
             _ => panic!("Impossible case in move_item")
@@ -205,7 +222,7 @@ impl<C : Chopped, M : Chopped, G : Iterator<Item = C>> Clustering<C, M, G> {
     pub fn get_cluster(&self, category : C) -> Option<&Cluster<C,M>> { self.clusters.get(&category) }
 
     /// Get all the `Clusters` in the `Clustering`.
-    pub fn get_clusters<'a>(&self) -> &HashMap<C,Cluster<C,M>> {
+    pub fn get_clusters(&self) -> &HashMap<C,Cluster<C,M>> {
         &self.clusters
     }
 
@@ -268,8 +285,8 @@ pub fn from_delimited_string(clustering_string : &str) -> Clustering<usize,usize
         for member_string in cluster_string.split(',') {
             let member = member_string.parse::<usize>().unwrap();
             match cluster_id_opt {
-                Some(cluster_id) => { clustering.add_to_cluster(member, cluster_id).expect(&format!("Unable to add {} to cluster {}", member, cluster_id)); },
-                None => { cluster_id_opt = Some(clustering.add_to_new_cluster(member).expect(&format!("Unable to add {} to a new cluster", member))); }
+                Some(cluster_id) => { clustering.add_to_cluster(member, cluster_id).unwrap_or_else(|_| panic!("Unable to add {} to cluster {}", member, cluster_id)); },
+                None => { cluster_id_opt = Some(clustering.add_to_new_cluster(member).unwrap_or_else(|_| panic!("Unable to add {} to a new cluster", member))); }
             }
         }
     }
@@ -0,0 +1,170 @@
+//! Parallel versions of the expensive stages of `SingleLinkage::find` and of `BCubed` comparison, available with the `parallel` feature.
+//!
+//! Every function for `SingleLinkage` yields exactly the same result as its serial counterpart,
+//! so turning the feature on or off never changes a `LinkageResult`. The `BCubed` sums of fractions are added
+//! in a different order, so they may differ in the last decimal place.
+use std::collections::HashMap;
+use rayon::prelude::*;
+use hilbert::Point;
+use super::Clustering;
+use super::cluster::Chopped;
+use super::bcubed::{BCubed, DenseContingency, DenseScratch};
+use super::single_linkage::AdjacentPairDistance;
+use super::histogram::DistanceHistogram;
+
+/// Sort a collection of `Points` in ascending **Hilbert Index** order, computing the Hilbert indices in parallel.
+///
+/// Points with identical Hilbert indices keep their original relative order,
+/// the same as `Point::hilbert_sort`, which uses a stable sort.
+pub fn hilbert_sort(points : &mut Vec<Point>, bits_per_dimension : usize) {
+    // Pairing each key with its position makes every key unique, so an unstable sort yields a stable ordering.
+    let mut keyed : Vec<_> = points.par_iter()
+        .enumerate()
+        .map(|(position, point)| (point.hilbert_transform(bits_per_dimension), position))
+        .collect();
+    keyed.par_sort_unstable();
+    let mut unsorted : Vec<Option<Point>> = points.drain(..).map(Some).collect();
+    points.extend(keyed.iter().map(|(_, position)| unsorted[*position].take().unwrap()));
+}
+
+/// Generate a collection of the distances between consecutive points in parallel.
+pub fn all_pairs(points : &[Point]) -> Vec<AdjacentPairDistance> {
+    points.par_windows(2)
+        .enumerate()
+        .map(|(index, pair)| AdjacentPairDistance::new(&pair[0], &pair[1], index, index + 1))
+        .collect()
+}
+
+/// Sort the square distances in parallel.
+///
+/// Equal integers are indistinguishable, so an unstable sort yields the same order as a stable one.
+pub fn sort_distances(square_distances : &mut [u64]) {
+    square_distances.par_sort_unstable();
+}
+
+/// Count the square distances in a histogram, with each thread filling its own histogram.
+///
+/// Merging histograms adds their counts, so the result is the same as when filled serially.
+pub fn fill_histogram(square_distances : &[u64]) -> DistanceHistogram {
+    square_distances.par_iter()
+        .fold(DistanceHistogram::default, |mut local_histogram, square_distance| {
+            local_histogram.add(*square_distance);
+            local_histogram
+        })
+        .reduce(DistanceHistogram::default, |mut low_histogram, high_histogram| {
+            low_histogram.merge(&high_histogram);
+            low_histogram
+        })
+}
+
+/// Compute the BCubed Precision (or, with the Clusterings swapped, the Recall), tallying the clusters concurrently.
+///
+/// Each thread reuses one map for the tallies of all the clusters it handles.
+pub fn bcubed_precision<C1, C2, M, G1, G2>(solution : &Clustering<C1,M,G1>, gold_standard : &Clustering<C2,M,G2>) -> f64
+where C1 : Chopped + Send + Sync, C2 : Chopped + Send + Sync, M : Chopped + Send + Sync,
+      G1 : Iterator<Item = C1> + Sync, G2 : Iterator<Item = C2> + Sync
+{
+    let clusters : Vec<_> = solution.get_clusters().values().collect();
+    let weighted_sum : f64 = clusters.par_iter()
+        .map_init(HashMap::new, |tallies, cluster| {
+            let sum_of_squares = BCubed::tally_squares(
+                cluster.get_members()
+                       .iter()
+                       .map(|m| gold_standard.get_category(*m)
+                           .unwrap_or_else(|| panic!("Item {:?} from one Clustering not present in the other", *m))),
+                tallies
+            );
+            sum_of_squares as f64 / cluster.len() as f64
+        })
+        .sum();
+    weighted_sum / solution.member_count() as f64
+}
+
+/// Sum the precision and recall terms of every solution category of a `DenseContingency`, with each thread
+/// tallying its categories in its own array.
+pub(crate) fn dense_bcubed_sums(table : &DenseContingency) -> (f64, f64) {
+    (0..table.cluster_count()).into_par_iter()
+        .map_init(|| DenseScratch::new(table.category_count()), |scratch, cluster| table.cluster_sums(cluster, scratch))
+        .reduce(|| (0.0, 0.0), |(p1, r1), (p2, r2)| (p1 + p2, r1 + r2))
+}
+
+#[cfg(test)]
+/// Tests that the parallel stages match the serial ones.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use hilbert::Point;
+    use crate::clustering::single_linkage::AdjacentPairDistance;
+    use crate::clustering::histogram::DistanceHistogram;
+
+    /// A grid of points with some duplicates, in scrambled order.
+    fn scrambled_points() -> Vec<Point> {
+        (0..2000_usize)
+            .map(|id| {
+                let scrambled = (id * 7919) % 2000;
+                Point::new(id, &[(scrambled % 50) as u32 * 3, (scrambled / 50) as u32 * 5])
+            })
+            .chain((2000..2100_usize).map(|id| Point::new(id, &[30, 40])))
+            .collect()
+    }
+
+    #[test]
+    fn hilbert_sort_matches_serial() {
+        let mut serial = scrambled_points();
+        let mut parallel = scrambled_points();
+        Point::hilbert_sort(&mut serial, 8);
+        super::hilbert_sort(&mut parallel, 8);
+        let serial_ids : Vec<usize> = serial.iter().map(|p| p.get_id()).collect();
+        let parallel_ids : Vec<usize> = parallel.iter().map(|p| p.get_id()).collect();
+        asserting("Same order, including duplicates").that(&parallel_ids).is_equal_to(serial_ids);
+    }
+
+    #[test]
+    fn all_pairs_and_sort_match_serial() {
+        let points = scrambled_points();
+        let serial : Vec<AdjacentPairDistance> = points.windows(2).enumerate()
+            .map(|(i, pair)| AdjacentPairDistance::new(&pair[0], &pair[1], i, i + 1))
+            .collect();
+        let parallel = super::all_pairs(&points);
+        asserting("Same pairs").that(&parallel).is_equal_to(serial.clone());
+        let mut serial_square_distances : Vec<u64> = serial.iter().map(|pair| pair.square_distance).collect();
+        let mut parallel_square_distances = serial_square_distances.clone();
+        serial_square_distances.sort();
+        super::sort_distances(&mut parallel_square_distances);
+        asserting("Same sorted distances").that(&parallel_square_distances).is_equal_to(serial_square_distances);
+    }
+
+    #[test]
+    fn fill_histogram_matches_serial() {
+        let points = scrambled_points();
+        let distances : Vec<u64> = super::all_pairs(&points).iter().map(|pair| pair.square_distance).collect();
+        let mut serial = DistanceHistogram::default();
+        for square_distance in distances.iter() {
+            serial.add(*square_distance);
+        }
+        let parallel = super::fill_histogram(&distances);
+        asserting("Same histogram").that(&parallel).is_equal_to(serial);
+    }
+
+    #[test]
+    fn bcubed_matches_serial() {
+        use crate::clustering::{integer_clustering, bcubed::BCubed};
+        let mut solution = integer_clustering();
+        let mut gold = integer_clustering();
+        let solution_labels : Vec<usize> = (0..5000_usize).map(|id| (id * 7919) % 37).collect();
+        let gold_labels : Vec<usize> = (0..5000_usize).map(|id| id % 23).collect();
+        for (labels, clustering) in [(&solution_labels, &mut solution), (&gold_labels, &mut gold)].iter_mut() {
+            let mut first_of_label = std::collections::HashMap::new();
+            for (id, label) in labels.iter().enumerate() {
+                match first_of_label.get(label) {
+                    Some(first) => { clustering.merge(*first, id); },
+                    None => { first_of_label.insert(*label, id); clustering.add_to_new_cluster(id).unwrap(); }
+                }
+            }
+        }
+        let serial = BCubed::compare(&solution, &gold, 0.5);
+        let close = |other : BCubed| (other.get_precision() - serial.get_precision()).abs() < 1e-12 && (other.get_recall() - serial.get_recall()).abs() < 1e-12;
+        asserting("Same as serial").that(&close(BCubed::compare_parallel(&solution, &gold, 0.5))).is_equal_to(true);
+        asserting("Dense same as serial").that(&close(BCubed::compare_dense(&solution_labels, &gold_labels, 0.5))).is_equal_to(true);
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::fmt::{Display, Formatter};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use super::cluster::Chopped;
+use super::{Clustering, integer_clustering};
+use super::bcubed::BCubed;
+use super::tuning::Metric;
+
+/// Default number of bootstrap samples or random permutations drawn by a `Resampler`.
+pub const DEFAULT_REPLICATE_COUNT : usize = 1000;
+
+/// Default confidence level of the intervals computed by a `Resampler`.
+pub const DEFAULT_CONFIDENCE : f64 = 0.95;
+
+// ........................... ConfidenceInterval ..........................................
+
+/// A score and the range within which it would likely fall if the members had been drawn afresh from the same population.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ConfidenceInterval {
+    /// The score of all the members, as given.
+    pub estimate : f64,
+
+    /// Lower bound of the interval.
+    pub lower : f64,
+
+    /// Upper bound of the interval.
+    pub upper : f64,
+
+    /// Fraction of bootstrap scores between the bounds, such as 0.95.
+    pub confidence : f64
+}
+
+impl ConfidenceInterval {
+    /// Does the interval contain the value?
+    pub fn contains(&self, value : f64) -> bool { self.lower <= value && value <= self.upper }
+
+    /// Distance between the bounds.
+    pub fn width(&self) -> f64 { self.upper - self.lower }
+
+    /// Form the interval from the central `confidence` fraction of the bootstrap scores (the percentile method).
+    fn from_replicates(estimate : f64, replicates : &mut [f64], confidence : f64) -> Self {
+        replicates.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let tail = (1.0 - confidence) / 2.0;
+        let at = |fraction : f64| replicates[(fraction * (replicates.len() - 1) as f64).round() as usize];
+        ConfidenceInterval { estimate, lower : at(tail), upper : at(1.0 - tail), confidence }
+    }
+}
+
+impl Display for ConfidenceInterval {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{:.4} ({:.0}% CI {:.4} to {:.4})", self.estimate, self.confidence * 100.0, self.lower, self.upper)
+    }
+}
+
+/// Confidence intervals for each part of a `BCubed` measure.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BCubedInterval {
+    pub precision : ConfidenceInterval,
+    pub recall : ConfidenceInterval,
+    pub similarity : ConfidenceInterval
+}
+
+impl Display for BCubedInterval {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "Similarity {}, precision {}, recall {}", self.similarity, self.precision, self.recall)
+    }
+}
+
+// ........................... PermutationTest ..........................................
+
+/// The B-Cubed precision and recall of one member.
+type PrecisionRecall = (f64, f64);
+
+/// The outcome of a paired permutation test of whether two solutions differ in quality.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PermutationTest {
+    /// B-Cubed similarity of the first solution to the gold standard.
+    pub first_score : f64,
+
+    /// B-Cubed similarity of the second solution to the gold standard.
+    pub second_score : f64,
+
+    /// Chance of a difference at least as large as `first_score - second_score` (in either direction)
+    /// if the two solutions were equally good.
+    pub p_value : f64,
+
+    /// Number of random permutations drawn.
+    pub permutation_count : usize
+}
+
+impl PermutationTest {
+    /// First score minus second score.
+    pub fn difference(&self) -> f64 { self.first_score - self.second_score }
+
+    /// Is the difference significant at the given level, such as 0.05?
+    pub fn is_significant(&self, level : f64) -> bool { self.p_value < level }
+}
+
+impl Display for PermutationTest {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "Similarity {:.4} vs {:.4} (difference {:+.4}), p = {:.4} from {} permutations",
+            self.first_score, self.second_score, self.difference(), self.p_value, self.permutation_count)
+    }
+}
+
+// ........................... Resampler ..........................................
+
+/// Judges whether differences in clustering scores are real or noise, by resampling the members.
+///
+/// Every result is reproducible: the random draws come from a generator seeded anew for each call.
+///
+/// ```
+///    use clusterphobia::clustering::from_delimited_string;
+///    use clusterphobia::clustering::resampling::Resampler;
+///    let solution = from_delimited_string("1,2,3;4,5,6,7;8,9");
+///    let gold = from_delimited_string("1,2,3,4;5,6,7;8,9");
+///    let interval = Resampler::new(42).with_replicate_count(200).bootstrap_bcubed(&solution, &gold, 0.5);
+///    assert!(interval.similarity.contains(interval.similarity.estimate));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Resampler {
+    /// Number of bootstrap samples, or of random permutations.
+    replicate_count : usize,
+
+    /// Confidence level of the intervals.
+    confidence : f64,
+
+    /// Seed for the random number generator.
+    seed : u64
+}
+
+impl Resampler {
+    /// Create a `Resampler` that draws `DEFAULT_REPLICATE_COUNT` replicates for intervals at the `DEFAULT_CONFIDENCE` level.
+    pub fn new(seed : u64) -> Self {
+        Resampler { replicate_count : DEFAULT_REPLICATE_COUNT, confidence : DEFAULT_CONFIDENCE, seed }
+    }
+
+    /// Set the number of bootstrap samples or random permutations. More give steadier results, but take longer.
+    pub fn with_replicate_count(mut self, replicate_count : usize) -> Self {
+        self.replicate_count = replicate_count.max(1);
+        self
+    }
+
+    /// Set the confidence level of the intervals, between zero and one.
+    pub fn with_confidence(mut self, confidence : f64) -> Self {
+        if confidence <= 0.0 || confidence >= 1.0 { panic!("Confidence must be between zero and one, not {}", confidence); }
+        self.confidence = confidence;
+        self
+    }
+
+    pub fn get_replicate_count(&self) -> usize { self.replicate_count }
+    pub fn get_confidence(&self) -> f64 { self.confidence }
+    pub fn get_seed(&self) -> u64 { self.seed }
+
+    /// Compute confidence intervals for the `BCubed` precision, recall and similarity by bootstrapping the members.
+    ///
+    /// Each bootstrap sample draws as many members as there are, with replacement. A member drawn W times counts as
+    /// W identical items, as in `BCubed::compare_weighted`. Only the counts of members shared by each solution cluster and
+    /// gold category change between samples, so each sample takes time linear in the number of members.
+    ///
+    ///   - solution - The `Clustering` whose quality is to be assessed.
+    ///   - gold_standard - The perfect `Clustering`, with the same members as the solution.
+    ///   - alpha - A value between zero and one. used to weight `precision` and `recall`.
+    pub fn bootstrap_bcubed<C1 : Chopped, C2 : Chopped, M : Chopped, G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(&self, solution : &Clustering<C1,M,G1>, gold_standard : &Clustering<C2,M,G2>, alpha : f64) -> BCubedInterval {
+        let table = ContingencyTable::new(solution, gold_standard);
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let n = table.member_cells.len();
+        let mut precisions = Vec::with_capacity(self.replicate_count);
+        let mut recalls = Vec::with_capacity(self.replicate_count);
+        let mut similarities = Vec::with_capacity(self.replicate_count);
+        let mut cell_weights = vec![0_u64; table.cell_count()];
+        for _ in 0..self.replicate_count {
+            cell_weights.iter_mut().for_each(|weight| *weight = 0);
+            for _ in 0..n {
+                cell_weights[table.member_cells[rng.gen_range(0..n)]] += 1;
+            }
+            let bcubed = table.bcubed(&cell_weights, alpha);
+            precisions.push(bcubed.get_precision());
+            recalls.push(bcubed.get_recall());
+            similarities.push(bcubed.similarity());
+        }
+        let estimate = BCubed::compare(solution, gold_standard, alpha);
+        BCubedInterval {
+            precision : ConfidenceInterval::from_replicates(estimate.get_precision(), &mut precisions, self.confidence),
+            recall : ConfidenceInterval::from_replicates(estimate.get_recall(), &mut recalls, self.confidence),
+            similarity : ConfidenceInterval::from_replicates(estimate.similarity(), &mut similarities, self.confidence)
+        }
+    }
+
+    /// Compute a confidence interval for any `Metric` by bootstrapping the members.
+    ///
+    /// Each bootstrap sample builds new Clusterings of the members drawn, numbering the draws from zero so that a
+    /// member drawn twice appears as two items in the same cluster. This is slower than `bootstrap_bcubed`,
+    /// which should be preferred for the B-Cubed measure.
+    ///
+    ///   - solution - The `Clustering` whose quality is to be assessed.
+    ///   - gold_standard - The perfect `Clustering`, with the same members as the solution.
+    ///   - metric - The score to compute, such as `bcubed_similarity`.
+    pub fn bootstrap_metric(&self, solution : &Clustering<usize,usize,Range<usize>>, gold_standard : &Clustering<usize,usize,Range<usize>>, metric : Metric) -> ConfidenceInterval {
+        let mut members : Vec<usize> = solution.get_clusters().values().flat_map(|cluster| cluster.get_members().iter().copied()).collect();
+        members.sort_unstable();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut scores : Vec<f64> = (0..self.replicate_count)
+            .map(|_| {
+                let draws : Vec<usize> = (0..members.len()).map(|_| members[rng.gen_range(0..members.len())]).collect();
+                metric(&resampled(solution, &draws), &resampled(gold_standard, &draws))
+            })
+            .collect();
+        ConfidenceInterval::from_replicates(metric(solution, gold_standard), &mut scores, self.confidence)
+    }
+
+    /// Test whether two solutions differ in quality more than chance would explain, scoring each against the same gold standard
+    /// by B-Cubed similarity.
+    ///
+    /// Each member has a precision and recall in each solution (see `BCubed::compare_detailed`). If the solutions were
+    /// equally good, swapping a member's scores between them would make no difference. Each permutation swaps the scores of
+    /// a random half of the members and recomputes the difference in similarity. The p-value is the fraction of
+    /// permutations (counting the actual assignment as one) whose difference is at least as large as the one observed.
+    ///
+    ///   - first - One solution.
+    ///   - second - The other solution, with the same members.
+    ///   - gold_standard - The perfect `Clustering`, with the same members as the solutions.
+    ///   - alpha - A value between zero and one. used to weight `precision` and `recall`.
+    pub fn permutation_test<C1 : Chopped, C2 : Chopped, C3 : Chopped, M : Chopped, G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>, G3 : Iterator<Item = C3>>(
+        &self, first : &Clustering<C1,M,G1>, second : &Clustering<C2,M,G2>, gold_standard : &Clustering<C3,M,G3>, alpha : f64) -> PermutationTest {
+        let first_items : HashMap<M, (f64, f64)> = BCubed::compare_detailed(first, gold_standard, alpha).items.iter()
+            .map(|item| (item.member, (item.precision, item.recall)))
+            .collect();
+        // Pair each member's scores, in a repeatable order.
+        let mut paired : Vec<(M, PrecisionRecall, PrecisionRecall)> = BCubed::compare_detailed(second, gold_standard, alpha).items.iter()
+            .map(|item| {
+                let first_scores = *first_items.get(&item.member)
+                    .unwrap_or_else(|| panic!("Item {:?} from one Clustering not present in the other", item.member));
+                (item.member, first_scores, (item.precision, item.recall))
+            })
+            .collect();
+        if paired.len() != first_items.len() { panic!("The two solutions do not have the same members"); }
+        paired.sort_by_key(|pair| pair.0);
+
+        let n = paired.len() as f64;
+        let difference = |swaps : &[bool]| {
+            let (mut sums_1, mut sums_2) = ((0.0, 0.0), (0.0, 0.0));
+            for ((_, scores_1, scores_2), swap) in paired.iter().zip(swaps.iter()) {
+                let (a, b) = if *swap { (scores_2, scores_1) } else { (scores_1, scores_2) };
+                sums_1 = (sums_1.0 + a.0, sums_1.1 + a.1);
+                sums_2 = (sums_2.0 + b.0, sums_2.1 + b.1);
+            }
+            let similarity_1 = BCubed::new(sums_1.0 / n, sums_1.1 / n, alpha).similarity();
+            let similarity_2 = BCubed::new(sums_2.0 / n, sums_2.1 / n, alpha).similarity();
+            (similarity_1, similarity_2)
+        };
+        let (first_score, second_score) = difference(&vec![false; paired.len()]);
+        let observed = (first_score - second_score).abs();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut swaps = vec![false; paired.len()];
+        let mut as_extreme = 0_usize;
+        for _ in 0..self.replicate_count {
+            swaps.iter_mut().for_each(|swap| *swap = rng.gen());
+            let (similarity_1, similarity_2) = difference(&swaps);
+            // Allow for rounding, so that a permutation equivalent to the observed one counts as being as extreme.
+            if (similarity_1 - similarity_2).abs() >= observed - 1e-12 { as_extreme += 1; }
+        }
+        PermutationTest {
+            first_score,
+            second_score,
+            p_value : (as_extreme + 1) as f64 / (self.replicate_count + 1) as f64,
+            permutation_count : self.replicate_count
+        }
+    }
+}
+
+/// Build a Clustering of the draws, where draw i is item i and falls in the same cluster as every other draw
+/// whose member shared its cluster in the original Clustering.
+fn resampled(clustering : &Clustering<usize,usize,Range<usize>>, draws : &[usize]) -> Clustering<usize,usize,Range<usize>> {
+    let mut sample = integer_clustering();
+    let mut new_category : HashMap<usize, usize> = HashMap::new();
+    for (item, member) in draws.iter().enumerate() {
+        let category = clustering.get_category(*member)
+            .unwrap_or_else(|| panic!("Item {:?} from one Clustering not present in the other", *member));
+        match new_category.get(&category) {
+            Some(sample_category) => { sample.add_to_cluster(item, *sample_category).unwrap_or_else(|_| panic!("Unable to add {} to cluster {}", item, sample_category)); },
+            None => { new_category.insert(category, sample.add_to_new_cluster(item).unwrap_or_else(|_| panic!("Unable to add {} to a new cluster", item))); }
+        }
+    }
+    sample
+}
+
+// ........................... ContingencyTable ..........................................
+
+/// The cells of the table of solution clusters versus gold categories that hold members, and the cell of each member.
+struct ContingencyTable {
+    /// Index of the cell holding each member.
+    member_cells : Vec<usize>,
+
+    /// Index of the solution cluster of each cell.
+    cell_clusters : Vec<usize>,
+
+    /// Index of the gold category of each cell.
+    cell_categories : Vec<usize>,
+
+    cluster_count : usize,
+    category_count : usize
+}
+
+impl ContingencyTable {
+    fn new<C1 : Chopped, C2 : Chopped, M : Chopped, G1 : Iterator<Item = C1>, G2 : Iterator<Item = C2>>(solution : &Clustering<C1,M,G1>, gold_standard : &Clustering<C2,M,G2>) -> Self {
+        if solution.member_count() != gold_standard.member_count() {
+            panic!("The solution has {} items but the gold standard has {}", solution.member_count(), gold_standard.member_count());
+        }
+        // Number the members, clusters and categories in order of member, so that the same seed draws the same samples in every run.
+        let mut members : Vec<M> = solution.get_clusters().values().flat_map(|cluster| cluster.get_members().iter().copied()).collect();
+        members.sort();
+        let mut cluster_indices : HashMap<C1, usize> = HashMap::new();
+        let mut category_indices : HashMap<C2, usize> = HashMap::new();
+        let mut cell_indices : HashMap<(usize, usize), usize> = HashMap::new();
+        let mut table = ContingencyTable { member_cells : Vec::new(), cell_clusters : Vec::new(), cell_categories : Vec::new(), cluster_count : 0, category_count : 0 };
+        for member in members {
+            let cluster = solution.get_category(member).unwrap();
+            let category = gold_standard.get_category(member)
+                .unwrap_or_else(|| panic!("Item {:?} from one Clustering not present in the other", member));
+            let next_cluster_index = cluster_indices.len();
+            let i_cluster = *cluster_indices.entry(cluster).or_insert(next_cluster_index);
+            let next_category_index = category_indices.len();
+            let i_category = *category_indices.entry(category).or_insert(next_category_index);
+            let next_cell_index = cell_indices.len();
+            let i_cell = *cell_indices.entry((i_cluster, i_category)).or_insert_with(|| {
+                table.cell_clusters.push(i_cluster);
+                table.cell_categories.push(i_category);
+                next_cell_index
+            });
+            table.member_cells.push(i_cell);
+        }
+        table.cluster_count = cluster_indices.len();
+        table.category_count = category_indices.len();
+        table
+    }
+
+    fn cell_count(&self) -> usize { self.cell_clusters.len() }
+
+    /// The `BCubed` measure when the cells hold the given numbers of items.
+    fn bcubed(&self, cell_weights : &[u64], alpha : f64) -> BCubed {
+        let mut cluster_weights = vec![0_u64; self.cluster_count];
+        let mut category_weights = vec![0_u64; self.category_count];
+        for (i_cell, weight) in cell_weights.iter().enumerate() {
+            cluster_weights[self.cell_clusters[i_cell]] += weight;
+            category_weights[self.cell_categories[i_cell]] += weight;
+        }
+        let (mut precision_sum, mut recall_sum) = (0_f64, 0_f64);
+        for (i_cell, weight) in cell_weights.iter().enumerate() {
+            if *weight == 0 { continue; }
+            let square = (weight * weight) as f64;
+            precision_sum += square / cluster_weights[self.cell_clusters[i_cell]] as f64;
+            recall_sum += square / category_weights[self.cell_categories[i_cell]] as f64;
+        }
+        let total = cell_weights.iter().sum::<u64>() as f64;
+        BCubed::new(precision_sum / total, recall_sum / total, alpha)
+    }
+}
+
+#[cfg(test)]
+/// Tests of resampling.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use crate::clustering;
+    use crate::clustering::bcubed::BCubed;
+    use crate::clustering::tuning::bcubed_similarity;
+    use super::{Resampler, ContingencyTable};
+
+    #[test]
+    fn contingency_table_matches_compare() {
+        let solution = clustering::from_delimited_string("1,2,3,4;5,6,7;8,9,10,11,12,13,14");
+        let gold = clustering::from_delimited_string("1,2,3,4,5;6,7,9,12,13,14;8;10;11");
+        let table = ContingencyTable::new(&solution, &gold);
+        let mut weights = vec![0_u64; table.cell_count()];
+        for cell in table.member_cells.iter() { weights[*cell] += 1; }
+        let expected = BCubed::compare(&solution, &gold, 0.5);
+        let actual = table.bcubed(&weights, 0.5);
+        asserting("Same similarity").that(&((actual.similarity() - expected.similarity()).abs() < 1e-12)).is_equal_to(true);
+    }
+
+    #[test]
+    fn bootstrap_intervals() {
+        let solution = clustering::from_delimited_string("1,2,3,4;5,6,7;8,9,10,11,12,13,14");
+        let gold = clustering::from_delimited_string("1,2,3,4,5;6,7,9,12,13,14;8;10;11");
+        let resampler = Resampler::new(7).with_replicate_count(300);
+        let interval = resampler.bootstrap_bcubed(&solution, &gold, 0.5);
+        asserting(&format!("Similarity {}", interval.similarity)).that(&interval.similarity.contains(interval.similarity.estimate)).is_equal_to(true);
+        asserting("Some spread").that(&(interval.similarity.width() > 0.0)).is_equal_to(true);
+        asserting("Repeatable").that(&resampler.bootstrap_bcubed(&solution, &gold, 0.5)).is_equal_to(interval);
+
+        let metric_interval = resampler.bootstrap_metric(&solution, &gold, bcubed_similarity);
+        asserting("Metric estimate").that(&((metric_interval.estimate - interval.similarity.estimate).abs() < 1e-12)).is_equal_to(true);
+        asserting(&format!("Metric interval {} near {}", metric_interval, interval.similarity))
+            .that(&((metric_interval.lower - interval.similarity.lower).abs() < 0.1 && (metric_interval.upper - interval.similarity.upper).abs() < 0.1)).is_equal_to(true);
+
+        let perfect = resampler.bootstrap_bcubed(&gold, &gold, 0.5);
+        asserting("Perfect has no spread").that(&(perfect.similarity.lower, perfect.similarity.upper)).is_equal_to((1.0, 1.0));
+    }
+
+    #[test]
+    fn permutation_test() {
+        // Forty members in four gold categories of ten.
+        let gold = clustering::from_delimited_string(&groups(10, &[10, 10, 10, 10]));
+        let good = clustering::from_delimited_string(&groups(10, &[10, 10, 10, 10]));
+        let poor = clustering::from_delimited_string(&groups(10, &[20, 20]));
+        let resampler = Resampler::new(11).with_replicate_count(500);
+        let distinct = resampler.permutation_test(&good, &poor, &gold, 0.5);
+        asserting(&format!("Clearly different: {}", distinct)).that(&distinct.is_significant(0.01)).is_equal_to(true);
+        let same = resampler.permutation_test(&good, &good, &gold, 0.5);
+        asserting("Identical solutions").that(&same.p_value).is_equal_to(1.0);
+        asserting("Repeatable").that(&resampler.permutation_test(&good, &poor, &gold, 0.5)).is_equal_to(distinct);
+    }
+
+    /// Delimited string of consecutive members (starting from `first`) grouped into clusters of the given sizes.
+    fn groups(first : usize, sizes : &[usize]) -> String {
+        let mut member = first;
+        sizes.iter()
+            .map(|size| {
+                let cluster : Vec<String> = (member..member + size).map(|m| m.to_string()).collect();
+                member += size;
+                cluster.join(",")
+            })
+            .collect::<Vec<String>>()
+            .join(";")
+    }
+}
@@ -1,17 +1,60 @@
 
 use std::ops::Range;
+use std::collections::HashMap;
 use std::cmp::Ordering;
 use std::cmp::{min,max};
-use hilbert::Point;
+use std::fmt::{Display,Formatter};
+use std::error::Error;
+use std::borrow::Borrow;
+use hilbert::{Point, Permutation};
+use serde::{Serialize, Deserialize};
+use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom, seq::index};
+use super::{Clustering, integer_clustering};
+use super::histogram::{DistanceHistogram, logarithmic_bounds};
+use super::weights::PointWeights;
+#[cfg(feature = "parallel")]
+use super::parallel;
+
+// ........................... Square distances ..........................................
+
+/// Square of the Cartesian distance between two points, accumulated so that it cannot overflow.
+/// 
+/// `Point::square_distance` expands (A - B)² into A² + B² - 2·A·B, whose intermediate sums can overflow a `u64` 
+/// for points with many dimensions or many bits per dimension, even when the distance itself would fit. 
+/// This sums the squares of the coordinate differences instead, and if the true square distance 
+/// exceeds `u64::MAX`, the result saturates at `u64::MAX`. 
+/// Because every term is non-negative, saturation preserves the ordering of all smaller distances.
+/// 
+/// The saturation is lossy: all distances at or beyond `u64::MAX` become equal, so they land in the same 
+/// histogram bin and cannot be told apart when searching for the biggest jump. Which pairs are linked is unaffected 
+/// so long as the linkage distance is below `u64::MAX`.
+pub fn saturating_square_distance(p1 : &Point, p2 : &Point) -> u64 {
+    p1.get_coordinates().iter()
+        .zip(p2.get_coordinates().iter())
+        .fold(0_u64, |sum, (c1, c2)| {
+            let diff = (*c1 as i64 - *c2 as i64).unsigned_abs();
+            sum.saturating_add(diff * diff)
+        })
+}
+
+/// Largest square distance possible between two points with the given number of dimensions 
+/// whose coordinates are encoded in `bits_per_dimension` bits, saturating at `u64::MAX`. 
+/// 
+/// Each coordinate difference can be at most 2ᵇ - 1, so the answer is D·(2ᵇ - 1)².
+pub fn largest_possible_square_distance(dimensions : usize, bits_per_dimension : u8) -> u64 {
+    let largest_coordinate = if bits_per_dimension >= 32 { u32::MAX as u64 } else { (1_u64 << bits_per_dimension) - 1 };
+    (largest_coordinate * largest_coordinate).saturating_mul(dimensions as u64)
+}
 
 // ........................... LinkageResult ..........................................
 
 /// Result from computing the Linkage distance, including 
 /// statistics about how many clusters are likely to be formed as a result
 /// of clustering using that distance. 
-#[derive(Copy,Clone,Debug,PartialEq)]
+#[derive(Copy,Clone,Debug,PartialEq,Serialize,Deserialize)]
 pub struct LinkageResult {
     /// The square of the linkage distance. (Avoid unnecessary square roots.)
+    /// Square distances too large for a `u64` saturate at `u64::MAX`.
     /// 
     /// _This is the primary result value, not set until after `find` is called._
     pub linkage_square_distance : u64, 
@@ -25,26 +68,57 @@ pub struct LinkageResult {
 
     /// Counts how many runs of points sorted in Hilbert Curve order are composed of points 
     /// separated by no more than the `linkage_square_distance` 
-    /// AND have a size that exceeds the `outlier_cluster_size`.
+    /// AND have a size (or total weight, for weighted points) that exceeds the `outlier_cluster_size`.
     /// This is an upper limit on the number of clusters that will result from 
     /// the full clustering algorithm. 
     /// 
     /// **Experiment shows that this tends to be from 1.5x to 3x the true number of clusters.**
+    /// For a calibrated estimate of the true number, see `SingleLinkage::estimate_true_cluster_count`.
     /// 
     /// _This a secondary result value, not set until after `find` is called._
     pub large_cluster_count : u32,
 
     /// Counts how many runs of points sorted in Hilbert Curve order are composed of points 
     /// separated by no more than the `linkage_square_distance` 
-    /// AND have a size that is less than or equal to the `outlier_cluster_size`.
+    /// AND have a size (or total weight, for weighted points) that is less than or equal to the `outlier_cluster_size`.
     /// 
     /// _This a secondary result value, not set until after `find` is called._
     pub outlier_cluster_count : u32,
 
     /// Counts how many individual points fall into clusters inferred to be outliers.
+    /// For weighted points, this is the total weight of those points.
     /// 
     /// _This a secondary result value, not set until after `find` is called._
-    pub outlier_count : u32
+    pub outlier_count : u32,
+
+    /// Number of Hilbert curves (each a random permutation of the coordinates) whose distances were combined
+    /// to find the `linkage_square_distance`. This is one unless `with_permutations` was used. 
+    pub permutation_count : u32,
+
+    /// Lowest `linkage_square_distance` found when analyzing the nearest neighbor distances along any single permutation 
+    /// of the Hilbert curve by itself. 
+    /// 
+    /// If only one curve was used, this equals the `linkage_square_distance`.
+    pub lowest_permuted_square_distance : u64,
+
+    /// Highest `linkage_square_distance` found when analyzing the nearest neighbor distances along any single permutation 
+    /// of the Hilbert curve by itself. 
+    /// 
+    /// If only one curve was used, this equals the `linkage_square_distance`.
+    pub highest_permuted_square_distance : u64,
+
+    /// Number of points in each random subsample analyzed, or zero if all points were analyzed. 
+    /// 
+    /// When sampling, the cluster and outlier counts describe the median subsample, not the full set of points.
+    pub sample_size : u32,
+
+    /// Number of random subsamples analyzed, or zero if all points were analyzed.
+    pub sample_count : u32,
+
+    /// Standard deviation of the corrected `linkage_square_distance` among the subsamples, an estimate of its error. 
+    /// 
+    /// This is zero if all points were analyzed.
+    pub sampling_error : f64
 }
 
 impl LinkageResult {
@@ -54,7 +128,107 @@ impl LinkageResult {
             count_of_too_large_distances : 0,
             large_cluster_count : 0,
             outlier_cluster_count : 0,
-            outlier_count : 0
+            outlier_count : 0,
+            permutation_count : 0,
+            lowest_permuted_square_distance : 0,
+            highest_permuted_square_distance : 0,
+            sample_size : 0,
+            sample_count : 0,
+            sampling_error : 0.0
+        }
+    }
+
+    /// Spread of the linkage distances found by the individual permutations of the Hilbert curve, 
+    /// relative to the combined `linkage_square_distance`. 
+    /// 
+    /// This is a confidence signal: near zero means the curves agree, while values approaching one or more 
+    /// mean that the choice of curve strongly affects the result. 
+    pub fn relative_spread(&self) -> f64 {
+        if self.linkage_square_distance == 0 { return 0.0; }
+        (self.highest_permuted_square_distance - self.lowest_permuted_square_distance) as f64 / self.linkage_square_distance as f64
+    }
+}
+
+impl Default for LinkageResult {
+    fn default() -> Self { Self::new() }
+}
+
+impl Display for LinkageResult {
+    /// A report of the linkage distance and what clustering at that distance is likely to yield.
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        writeln!(f, "Linkage distance: {:.1} (square distance {})", (self.linkage_square_distance as f64).sqrt(), self.linkage_square_distance)?;
+        writeln!(f, "Large clusters: at most {}, counting runs of points in Hilbert order that exceed the outlier cluster size.", self.large_cluster_count)?;
+        if self.large_cluster_count > 0 {
+            // Experiment shows that the run count tends to be from 1.5x to 3x the true number of clusters.
+            let fewest = max(1, (self.large_cluster_count as f64 / 3.0).round() as u32);
+            let most = max(fewest, (self.large_cluster_count as f64 / 1.5).round() as u32);
+            writeln!(f, "  Runs split some clusters, so expect roughly {} to {} clusters once they are merged.", fewest, most)?;
+        }
+        writeln!(f, "Outliers: {} points in {} runs no larger than the outlier cluster size.", self.outlier_count, self.outlier_cluster_count)?;
+        writeln!(f, "Gaps: {} distances between consecutive points exceed the linkage distance.", self.count_of_too_large_distances)?;
+        if self.permutation_count > 1 {
+            writeln!(f, "Permutations: {} Hilbert curves combined; alone, they found square distances from {} to {} (relative spread {:.1}%).", 
+                self.permutation_count, self.lowest_permuted_square_distance, self.highest_permuted_square_distance, 100.0 * self.relative_spread())?;
+        }
+        if self.sample_count > 0 {
+            writeln!(f, "Sampling: {} subsamples of {} points, whose corrected square distances have a standard deviation of {:.0}. The counts describe the median subsample.", 
+                self.sample_count, self.sample_size, self.sampling_error)?;
+        }
+        Ok(())
+    }
+}
+
+// ........................... PointError ..........................................
+
+/// Problems with a collection of `Points` that would make a linkage analysis silently wrong. 
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum PointError {
+    /// There are no points to analyze.
+    NoPoints,
+
+    /// A point has a different number of dimensions than the first point.
+    MismatchedDimensions { point_id : usize, expected_dimensions : usize, actual_dimensions : usize },
+
+    /// A point has a coordinate value too large to be encoded in the configured `bits_per_dimension`.
+    CoordinateTooLarge { point_id : usize, coordinate : u32, bits_per_dimension : u8 }
+}
+
+impl Display for PointError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            PointError::NoPoints => write!(f, "No points were supplied"),
+            PointError::MismatchedDimensions { point_id, expected_dimensions, actual_dimensions } => 
+                write!(f, "Point {} has {} dimensions, but {} were expected", point_id, actual_dimensions, expected_dimensions),
+            PointError::CoordinateTooLarge { point_id, coordinate, bits_per_dimension } => 
+                write!(f, "Point {} has coordinate {} which does not fit in {} bits", point_id, coordinate, bits_per_dimension)
+        }
+    }
+}
+
+impl Error for PointError {}
+
+// ........................... PermutationCombination ..........................................
+
+/// How to combine a point's nearest neighbor distances from each of several Hilbert curves into a single distance.
+#[derive(Copy,Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub enum PermutationCombination {
+    /// Take the median distance, which resists the occasional curve that separates true neighbors.
+    Median,
+
+    /// Take the minimum distance, which most aggressively removes the inflation caused by curves separating true neighbors.
+    Minimum
+}
+
+impl PermutationCombination {
+    /// Combine the values according to this rule. For an even number of values, the median is the lower of the middle two.
+    fn combine(&self, values : &mut [u64]) -> u64 {
+        match self {
+            PermutationCombination::Minimum => values.iter().copied().min().unwrap_or(u64::MAX),
+            PermutationCombination::Median => {
+                if values.is_empty() { return u64::MAX; }
+                values.sort_unstable();
+                values[(values.len() - 1) / 2]
+            }
         }
     }
 }
@@ -100,13 +274,17 @@ impl LinkageResult {
 /// Other things to be avoided: 
 ///   - There may be a few duplicate points or really close points, then a gap to the typical range of distances. 
 ///     We must not falsely identify this first jump in distances as the linkage distance.
+///     Collapsing such points first with `CollapsedPoints` (in the `duplicates` module) removes this hazard.
 ///   - If there is a great variety in the densities of clusters, then there could be additional gaps. 
 /// 
 /// While this algorithm does not actually cluster the points, it does estimate how many proper clusters and outlier clusters
 /// would be generated from the derived linkage distance. 
 /// 
 /// The configuration of this class permits a fluent style. 
-#[derive(Copy, Clone, Debug)]
+/// 
+/// The configuration may be saved and restored using **serde**. To load a partial configuration 
+/// or a named preset from a JSON or TOML file, see `LinkageConfig` (in the `config` module).
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct SingleLinkage {
     /// Should the Hilbert transform be performed, use this number of bits per dimension to encode the coordinates. 
     bits_per_dimension : u8,
@@ -135,6 +313,7 @@ pub struct SingleLinkage {
 
     /// When counting potential clusters, do not count as a cluster any runs of consecutive points in Hilbert order whose 
     /// separation is less than the linkage_square_distance unless they exceed this size. 
+    /// For weighted points, the total weight of the run is compared to this size.
     outlier_cluster_size : u16,
 
     /// If true, fully sort the distances as part of the analysis, which is more accurate but more expensive 
@@ -151,9 +330,36 @@ pub struct SingleLinkage {
     /// 
     /// This defaults to ½N. 
     /// 
+    /// For weighted points, N and this index count each point as many times as its weight. 
+    /// A point of weight W contributes W - 1 distances of zero that are never computed, 
+    /// so `find_weighted` lowers this index by that many for the distances it does compute.
+    /// 
     ///   - Increase it if nearly half or more of your points are coincident or very close together. 
     ///   - Decrease it if you have a huge number of outliers (beyond 40% outliers). 
-    lowest_index_for_checking_growth_ratio : u32 
+    lowest_index_for_checking_growth_ratio : u32,
+
+    /// Number of Hilbert curves to analyze. The first is the unpermuted curve, the rest use random permutations of the coordinates.
+    /// 
+    /// A single curve places some true neighbors far apart, inflating their distances. With several curves, 
+    /// each point's nearest neighbor distances on the curves are combined according to `permutation_combination`.
+    /// The default is one. 
+    permutation_count : u16,
+
+    /// How to combine each point's distances from the several Hilbert curves.
+    permutation_combination : PermutationCombination,
+
+    /// Seed for the random number generator that chooses the permutations, so that results are repeatable.
+    permutation_seed : u64,
+
+    /// If non-zero and smaller than the number of points, analyze random subsamples of this many points 
+    /// instead of all the points, then correct the result for the lower density of the sample. 
+    sample_size : u32,
+
+    /// Number of random subsamples to analyze when `sample_size` is set. Their spread provides an error estimate.
+    sample_count : u16,
+
+    /// Seed for the random number generator that chooses the subsamples, so that results are repeatable.
+    sample_seed : u64
 }
 
 impl SingleLinkage {
@@ -170,8 +376,105 @@ impl SingleLinkage {
             noise_skip_by : 5,
             outlier_cluster_size : 10,
             sort_distances_completely : true,
-            lowest_index_for_checking_growth_ratio : num_points / 2
+            lowest_index_for_checking_growth_ratio : num_points / 2,
+            permutation_count : 1,
+            permutation_combination : PermutationCombination::Median,
+            permutation_seed : 0,
+            sample_size : 0,
+            sample_count : 0,
+            sample_seed : 0
+        }
+    }
+
+    /// Number of bits used to represent each coordinate value.
+    pub fn get_bits_per_dimension(&self) -> u8 { self.bits_per_dimension }
+
+    /// Number of positions skipped when comparing sorted distances, to smooth out noise.
+    pub fn get_noise_skip_by(&self) -> u16 { self.noise_skip_by }
+
+    /// Fewest clusters that the linkage distance must yield.
+    pub fn get_minimum_cluster_count(&self) -> u16 { self.minimum_cluster_count }
+
+    /// Largest run of points (or total weight) counted as outliers rather than a cluster.
+    pub fn get_outlier_cluster_size(&self) -> u16 { self.outlier_cluster_size }
+
+    /// Number of sorted distances passed over before searching for the largest jump in distance.
+    pub fn get_lowest_index_for_checking_growth_ratio(&self) -> u32 { self.lowest_index_for_checking_growth_ratio }
+
+    /// Will `find` sort the points in Hilbert Curve order first?
+    pub fn get_need_to_sort_by_hilbert_curve(&self) -> bool { self.need_to_sort_by_hilbert_curve }
+
+    /// Number of Hilbert curves analyzed.
+    pub fn get_permutation_count(&self) -> u16 { self.permutation_count }
+
+    /// How the distances from several Hilbert curves are combined.
+    pub fn get_permutation_combination(&self) -> PermutationCombination { self.permutation_combination }
+
+    /// Seed that controls which random permutations are chosen.
+    pub fn get_permutation_seed(&self) -> u64 { self.permutation_seed }
+
+    /// Number of points in each random subsample, or zero if all points are analyzed.
+    pub fn get_sample_size(&self) -> u32 { self.sample_size }
+
+    /// Number of random subsamples analyzed when `sample_size` is set.
+    pub fn get_sample_count(&self) -> u16 { self.sample_count }
+
+    /// Create a SingleLinkage with all values set to defaults, except that `bits_per_dimension` 
+    /// is derived from the largest coordinate value found among the points. 
+    /// 
+    ///   - returns - An `Err` if there are no points or the points do not all have the same number of dimensions.
+    pub fn from_points(points : &[Point]) -> Result<Self, PointError> {
+        let bits_per_dimension = Self::bits_required(points)?;
+        Ok(Self::new(points.len() as u32, bits_per_dimension))
+    }
+
+    /// Find the smallest number of bits per dimension able to encode every coordinate value of every point. 
+    /// 
+    /// At least one bit is always required.
+    /// 
+    ///   - returns - An `Err` if there are no points or the points do not all have the same number of dimensions.
+    pub fn bits_required(points : &[Point]) -> Result<u8, PointError> {
+        let expected_dimensions = match points.first() {
+            Some(point) => point.dimensions(),
+            None => return Err(PointError::NoPoints)
+        };
+        let mut largest_coordinate = 0_u32;
+        for point in points {
+            if point.dimensions() != expected_dimensions {
+                return Err(PointError::MismatchedDimensions { 
+                    point_id : point.get_id(), 
+                    expected_dimensions, 
+                    actual_dimensions : point.dimensions() 
+                });
+            }
+            largest_coordinate = point.get_coordinates().iter().fold(largest_coordinate, |high, c| max(high, *c));
         }
+        Ok(max(1, 32 - largest_coordinate.leading_zeros()) as u8)
+    }
+
+    /// Verify that the points all have the same number of dimensions and that their coordinates 
+    /// fit within `bits_per_dimension` bits. 
+    /// 
+    /// If these conditions are violated, the Hilbert sort and the binning in `find` yield incorrect results without complaint.
+    pub fn validate(&self, points : &[Point]) -> Result<(), PointError> {
+        Self::bits_required(points)?;
+        let limit = if self.bits_per_dimension >= 32 { u64::MAX } else { 1_u64 << self.bits_per_dimension };
+        for point in points {
+            if let Some(coordinate) = point.get_coordinates().iter().find(|c| **c as u64 >= limit) {
+                return Err(PointError::CoordinateTooLarge { 
+                    point_id : point.get_id(), 
+                    coordinate : *coordinate, 
+                    bits_per_dimension : self.bits_per_dimension 
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Configure the algorithm by setting `bits_per_dimension`, the number of bits used to represent each coordinate value.
+    pub fn with_bits_per_dimension(mut self, bits_per_dimension : u8) -> Self {
+        self.bits_per_dimension = bits_per_dimension;
+        self
     }
 
     /// Configure the algorithm to expect that the input points HAVE NOT yet been sorted
@@ -202,6 +505,12 @@ impl SingleLinkage {
         self
     }
 
+    /// Configure the algorithm by setting a value for `outlier_cluster_size`. 
+    pub fn with_outlier_cluster_size(mut self, outlier_cluster_size : u16) -> Self {
+        self.outlier_cluster_size = outlier_cluster_size;
+        self
+    }
+
     /// Configure the algorithm by setting `sort_distances_completely` to true.
     /// This will provide the most accurate results at the expense of O(N Log N) running time.  
     pub fn with_sort_distances_completely(mut self) -> Self {
@@ -222,41 +531,251 @@ impl SingleLinkage {
         self
     }
 
+    /// Configure the algorithm to analyze several Hilbert curves, the first unpermuted and the others formed 
+    /// by randomly permuting the coordinates, then combine each point's nearest neighbor distances from all the curves.
+    /// This sets `permutation_count` and `permutation_combination`.
+    /// 
+    /// A point's nearest neighbor distance is no larger than either gap beside it, so with two or more curves the linkage distance 
+    /// is typically several times smaller than with one curve, which analyzes the gaps themselves.
+    pub fn with_permutations(mut self, permutation_count : u16, combination : PermutationCombination) -> Self {
+        self.permutation_count = max(permutation_count, 1);
+        self.permutation_combination = combination;
+        self
+    }
+
+    /// Configure the algorithm by setting `permutation_seed`, which controls which random permutations are chosen.
+    pub fn with_permutation_seed(mut self, seed : u64) -> Self {
+        self.permutation_seed = seed;
+        self
+    }
+
+    /// Configure the algorithm to analyze `sample_count` random subsamples of `sample_size` points each instead of all the points.
+    /// This sets `sample_size` and `sample_count`.
+    /// 
+    /// This is intended for a quick look at very large data sets, where even a single Hilbert sort is slow. 
+    /// The subsamples are always sorted in Hilbert order, regardless of `need_to_sort_by_hilbert_curve`.
+    /// A `sample_size` below two leaves no distances between points to measure, so all the points are analyzed instead.
+    pub fn with_sampling(mut self, sample_size : u32, sample_count : u16) -> Self {
+        self.sample_size = sample_size;
+        self.sample_count = max(sample_count, 1);
+        self
+    }
+
+    /// Configure the algorithm by setting `sample_seed`, which controls which random subsamples are chosen.
+    pub fn with_sample_seed(mut self, seed : u64) -> Self {
+        self.sample_seed = seed;
+        self
+    }
+
     /// Find the characteristic clustering distance, the linkage distance, along with some other useful results. 
     /// 
     ///   - points - Points to analyze. As a side effect, these points will be sorted in Hilbert Curve order
-    ///     if `need_to_sort_by_hilbert_curve` is true.
+    ///     if `need_to_sort_by_hilbert_curve` is true, unless subsamples are analyzed instead.
     pub fn find(&self, points : &mut Vec<Point>) -> LinkageResult {
+        self.find_weighted(points, &PointWeights::new())
+    }
+
+    /// Find the linkage distance and estimate the cluster counts for points that each stand for one or more items. 
+    /// 
+    /// Create the `SingleLinkage` using the total weight of the points as `num_points`, so that its thresholds 
+    /// count items, not points. Outlier runs are judged by their total weight. 
+    /// Weights are ignored when analyzing random subsamples (see `with_sampling`).
+    /// 
+    ///   - points - Points to analyze. As a side effect, these points will be sorted in Hilbert Curve order
+    ///     if `need_to_sort_by_hilbert_curve` is true, unless subsamples are analyzed instead.
+    ///   - weights - Weight of each point, by point id.
+    pub fn find_weighted(&self, points : &mut Vec<Point>, weights : &PointWeights) -> LinkageResult {
+        if self.sample_size > 1 && (self.sample_size as usize) < points.len() {
+            return self.find_by_sampling(points);
+        }
         if self.need_to_sort_by_hilbert_curve {
-            Point::hilbert_sort(points, self.bits_per_dimension as usize);
+            self.sort_by_hilbert_curve(points);
         }
-        let mut distances = AdjacentPairDistance::all_pairs(points);
+        let finder = self.adjust_for_weights(points, weights);
+        let distances = AdjacentPairDistance::all_pairs(points);
+        if finder.permutation_count > 1 {
+            return finder.find_with_permutations(points, &distances, weights);
+        }
+
+        let dimensions = points.first().map_or(1, |p| p.dimensions());
+        let square_distances : Vec<u64> = distances.iter().map(|pair| pair.square_distance).collect();
+        let linkage_square_distance = finder.find_linkage_square_distance(points.len(), dimensions, &square_distances);
+        let mut linkage = finder.estimate_weighted_cluster_counts(&distances, linkage_square_distance, weights);
+        linkage.permutation_count = 1;
+        linkage.lowest_permuted_square_distance = linkage_square_distance;
+        linkage.highest_permuted_square_distance = linkage_square_distance;
+        linkage
+    }
+
+    /// Lower `lowest_index_for_checking_growth_ratio` to account for the distances of zero between the items 
+    /// that each weighted point stands for, which are not among the distances between the points.
+    fn adjust_for_weights(&self, points : &[Point], weights : &PointWeights) -> Self {
+        if weights.is_uniform() { return *self; }
+        let total_weight = weights.total(points.iter().map(|p| p.get_id()));
+        let uncomputed_distances = total_weight.saturating_sub(points.len() as u64);
+        let mut finder = *self;
+        finder.lowest_index_for_checking_growth_ratio = 
+            (self.lowest_index_for_checking_growth_ratio as u64).saturating_sub(uncomputed_distances) as u32;
+        finder
+    }
 
+    /// Find the linkage distance by analyzing random subsamples of the points. 
+    /// 
+    /// A subsample of m points out of N is sparser than the full set, so the distances between neighbors are larger. 
+    /// The distance from a point to its nearest neighbor scales as N^(-1/D) for D dimensions, 
+    /// so each subsample's `linkage_square_distance` is multiplied by (m/N)^(2/D). 
+    /// The median of the corrected values is reported, along with their standard deviation as the `sampling_error`.
+    /// The cluster and outlier counts are those of the median subsample, so they describe its m points, not all N.
+    /// 
+    /// The correction uses the number of coordinates as D. If the data lie on a lower dimensional surface, 
+    /// the correction will be too weak and the result too large.
+    fn find_by_sampling(&self, points : &[Point]) -> LinkageResult {
+        let point_count = points.len();
+        let sample_size = self.sample_size as usize;
+        let dimensions = points.first().map_or(1, |p| p.dimensions());
+        let correction = (sample_size as f64 / point_count as f64).powf(2.0 / dimensions as f64);
+
+        // Configure for a smaller number of points, preserving all explicitly chosen settings.
+        let sample_defaults = SingleLinkage::new(self.sample_size, self.bits_per_dimension);
+        let sample_finder = SingleLinkage {
+            need_to_sort_by_hilbert_curve : true,
+            sample_size : 0,
+            // A sample smaller than the usual minimum cluster count must still leave distances to search.
+            minimum_cluster_count : min(min(self.minimum_cluster_count, sample_defaults.minimum_cluster_count), (sample_size / 2) as u16),
+            lowest_index_for_checking_growth_ratio : 
+                (self.lowest_index_for_checking_growth_ratio as u64 * sample_size as u64 / point_count as u64) as u32,
+            ..*self
+        };
+
+        // A deserialized SingleLinkage may carry a sample_count of zero, which with_sampling would have raised to one.
+        let sample_count = max(self.sample_count, 1);
+        let mut rng = StdRng::seed_from_u64(self.sample_seed);
+        let mut sample_results = Vec::with_capacity(sample_count as usize);
+        for _ in 0..sample_count {
+            let mut sample : Vec<Point> = index::sample(&mut rng, point_count, sample_size).iter()
+                .map(|i| points[i].clone())
+                .collect();
+            let sample_result = sample_finder.find(&mut sample);
+            sample_results.push((sample_result.linkage_square_distance as f64 * correction, sample_result));
+        }
+
+        let count = sample_results.len() as f64;
+        let mean = sample_results.iter().map(|(d, _)| d).sum::<f64>() / count;
+        let variance = sample_results.iter().map(|(d, _)| (d - mean) * (d - mean)).sum::<f64>() / count;
+        sample_results.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        // Report the cluster counts of the sample whose corrected distance is the median, so they agree with the distance.
+        let (median, mut linkage) = sample_results.swap_remove((sample_results.len() - 1) / 2);
+        linkage.linkage_square_distance = max(1, median.round() as u64);
+        linkage.sample_size = self.sample_size;
+        linkage.sample_count = sample_count as u32;
+        linkage.sampling_error = variance.sqrt();
+        linkage
+    }
+
+    /// Sort the points in Hilbert Curve order, in parallel if the `parallel` feature is enabled.
+    pub(crate) fn sort_by_hilbert_curve(&self, points : &mut Vec<Point>) {
+        #[cfg(feature = "parallel")]
+        parallel::hilbert_sort(points, self.bits_per_dimension as usize);
+        #[cfg(not(feature = "parallel"))]
+        Point::hilbert_sort(points, self.bits_per_dimension as usize);
+    }
+
+    /// Choose between `find_by_sorting` and `find_by_binning` according to `sort_distances_completely`.
+    pub(crate) fn find_linkage_square_distance(&self, point_count : usize, dimensions : usize, square_distances : &[u64]) -> u64 {
         if self.sort_distances_completely {
-            self.find_by_sorting(points, &mut distances)
+            self.find_by_sorting(point_count, square_distances)
         }
         else {
-            self.find_by_binning(points, &mut distances)
+            self.find_by_binning(point_count, dimensions, square_distances)
+        }
+    }
+
+    /// Find the linkage distance by analyzing several Hilbert curves. 
+    /// 
+    /// On each curve, every point's nearest neighbor distance is the smaller of the square distances to the points 
+    /// before and after it. Each point's distances from all the curves are combined into one value 
+    /// according to `permutation_combination`, and the combined distances are analyzed as if they came from a single Hilbert curve. 
+    /// A point whose true neighbors one curve carried far away is unlikely to be separated from them on the other curves, 
+    /// so the combination deflates its distance. The nearest neighbor distances of each curve are also analyzed alone, 
+    /// giving the range reported as the lowest and highest permuted square distances, and the combined result is kept within that range.
+    /// The cluster counts are estimated using the runs along the unpermuted curve, in which the points are left sorted.
+    /// 
+    ///   - points - Points to analyze, already sorted in Hilbert Curve order.
+    ///   - distances - Distances between successive pairs of points.
+    ///   - weights - Weight of each point, used when estimating the cluster counts.
+    fn find_with_permutations(&self, points : &[Point], distances : &[AdjacentPairDistance], weights : &PointWeights) -> LinkageResult {
+        let point_count = points.len();
+        let dimensions = points.first().map_or(1, |p| p.dimensions());
+        let slot_of_id : HashMap<usize, usize> = points.iter().enumerate().map(|(slot, point)| (point.get_id(), slot)).collect();
+        let mut distances_by_point : Vec<Vec<u64>> = vec![Vec::with_capacity(self.permutation_count as usize); point_count];
+        let mut lowest_permuted_square_distance = u64::MAX;
+        let mut highest_permuted_square_distance = 0;
+        let mut rng = StdRng::seed_from_u64(self.permutation_seed);
+
+        for i_permutation in 0..self.permutation_count {
+            let permuted_distances = 
+                if i_permutation == 0 { None }
+                else {
+                    let mut moves : Vec<usize> = (0..dimensions).collect();
+                    moves.shuffle(&mut rng);
+                    let permutation = Permutation::new(&moves);
+                    // Permuting the coordinates changes the curve but not the distances between points.
+                    let mut permuted_points : Vec<Point> = points.iter().map(|point| point.permute(&permutation)).collect();
+                    self.sort_by_hilbert_curve(&mut permuted_points);
+                    Some(AdjacentPairDistance::all_pairs(&permuted_points))
+                };
+            let curve_distances = permuted_distances.as_deref().unwrap_or(distances);
+            let mut nearest = vec![u64::MAX; point_count];
+            for pair in curve_distances {
+                for id in [pair.first_id, pair.second_id].iter() {
+                    let slot = slot_of_id[id];
+                    nearest[slot] = min(nearest[slot], pair.square_distance);
+                }
+            }
+            // Analyze each curve's nearest neighbor distances by themselves, so the spread among curves measures the same thing as the combination.
+            let permuted_square_distance = self.find_linkage_square_distance(point_count, dimensions, &nearest);
+            lowest_permuted_square_distance = min(lowest_permuted_square_distance, permuted_square_distance);
+            highest_permuted_square_distance = max(highest_permuted_square_distance, permuted_square_distance);
+            for (point_distances, distance) in distances_by_point.iter_mut().zip(nearest) {
+                point_distances.push(distance);
+            }
         }
+
+        let combined_square_distances : Vec<u64> = distances_by_point.iter_mut()
+            .map(|point_distances| self.permutation_combination.combine(point_distances))
+            .collect();
+        // Combining deflates the distances, so the knee of the combined distances can fall below every single curve's knee. 
+        // Keep it within the range of the single curves, so that the reported spread brackets it.
+        let linkage_square_distance = self.find_linkage_square_distance(point_count, dimensions, &combined_square_distances)
+            .clamp(lowest_permuted_square_distance, highest_permuted_square_distance);
+        let mut linkage = self.estimate_weighted_cluster_counts(distances, linkage_square_distance, weights);
+        linkage.permutation_count = self.permutation_count as u32;
+        linkage.lowest_permuted_square_distance = lowest_permuted_square_distance;
+        linkage.highest_permuted_square_distance = highest_permuted_square_distance;
+        linkage
     }
 
     /// Using a default sort, find the linkage distance by analyzing the distribution of distances between consecutive points 
-    /// after they are arranged in Hilbert Curve order. Also set several other measures in the structure.
+    /// after they are arranged in Hilbert Curve order. 
     /// 
     /// The default sort is O(N Log N) in performance and will yield a more accurate answer at the expense of a slower computation. 
     /// 
-    ///   - points - Points to analyze. 
-    ///   - distances - Distances between successive pairs of points.
-    ///     This collection should have exactly one fewer entry than `points`.
+    ///   - point_count - Number of points analyzed. 
+    ///   - square_distances - Square distances between successive pairs of points (or their combination over several curves).
+    ///     This collection should have no fewer than one fewer entry than there are points.
+    ///   - returns - The `linkage_square_distance`.
     /// 
     /// NOTE: In my earlier C# code, this was called `FindMaximumSquareDistance`. 
-    fn find_by_sorting(&self, points : &mut Vec<Point>, distances : &Vec<AdjacentPairDistance>) -> LinkageResult {
+    fn find_by_sorting(&self, point_count : usize, square_distances : &[u64]) -> u64 {
         // NOTE: This is a port of a C# method named `FindMaximumSquareDistance`, with mods.
 
-        // Why clone? We will later need the unsorted distances to estimate the potential effect of a clustering. 
-        let mut sorted_distances = distances.clone();
+        let mut sorted_distances = square_distances.to_vec();
         
         // Use the default sort. 
+        #[cfg(feature = "parallel")]
+        parallel::sort_distances(&mut sorted_distances);
+        #[cfg(not(feature = "parallel"))]
         sorted_distances.sort();
 
         // Uncomment this to log sorted distances. Paste into a spreadsheet and graph it 
@@ -264,7 +783,7 @@ impl SingleLinkage {
         /*
         println!("===== SORTED DISTANCES =======");
         for dist in sorted_distances.iter() {
-            println!("{}", dist.square_distance);
+            println!("{}", dist);
         }
         println!("==============================");
         */
@@ -275,51 +794,68 @@ impl SingleLinkage {
         // typically from just past halfway through just before the end. 
         // This guarantees that we do not over-cluster and honor minimum_cluster_count.
         let start_index = 1 + self.noise_skip_by as usize + self.lowest_index_for_checking_growth_ratio as usize;
-        let conservative_high_index = points.len() - self.minimum_cluster_count as usize;
+        let conservative_high_index = point_count.saturating_sub(self.minimum_cluster_count as usize);
         for i_distance in start_index..conservative_high_index
         {
-            let distance = sorted_distances[i_distance].square_distance;
+            let distance = sorted_distances[i_distance];
             let previous_index = i_distance - 1 - self.noise_skip_by as usize;
-            let previous_distance = sorted_distances[previous_index].square_distance;
+            let previous_distance = sorted_distances[previous_index];
             stats.accumulate(i_distance, previous_distance, distance);
         }
 
         // get_index_after_max_change returns the index on the high side of the largest change. 
         // Subtract off 1 + noise_skip_by to get to the index before the big change. 
+        // A tiny set of points may leave no room to search, so stay within the sorted distances.
         let index_to_use = stats.get_index_after_max_change(
             self.lowest_index_for_checking_growth_ratio as usize, 
             conservative_high_index
-        ) - 1 - self.noise_skip_by as usize;
+        ).saturating_sub(1 + self.noise_skip_by as usize);
 
-        let maximum_square_distance = sorted_distances[index_to_use].square_distance;
-        self.estimate_cluster_counts(distances, maximum_square_distance)
+        sorted_distances[min(index_to_use, sorted_distances.len() - 1)]
     }
 
     /// Find the linkage distance using logarithmic bins to perform a bin sort instead of the standard (likely iterative merge sort similar to Timsort). 
     /// 
-    ///  - Use logarithmic bins to store the items initially
+    ///  - Count the items in a fine-grained logarithmic `DistanceHistogram`, which does not store the items themselves
+    ///  - Tally the histogram into coarser logarithmic bins
     ///  - Consolidate empty or sparse bins to guarantee a minimum bin size
     ///  - Find the bin where the average spread grows the most between successive bins (ruling out edge cases near the low end of the distances)
-    ///  - Sort the values in that one bin to home in on the actual largest jump. 
-    /// 
-    ///   - `points` - Points whose distances
+    ///  - Search the histogram buckets within that one bin to home in on the actual largest jump. 
     /// 
-    fn find_by_binning(&self, points : &mut Vec<Point>, hilbert_sorted_distances : &mut Vec<AdjacentPairDistance>) -> LinkageResult {
+    ///   - point_count - Number of points analyzed. 
+    ///   - dimensions - Number of dimensions of each point, needed to bound the largest possible distance.
+    ///   - square_distances - Square distances between successive pairs of points (or their combination over several curves).
+    ///   - returns - The `linkage_square_distance`.
+    fn find_by_binning(&self, point_count : usize, dimensions : usize, square_distances : &[u64]) -> u64 {
+
+        // Part 1: Gather the distances between points into a logarithmic histogram, not the slower default sort. 
+        #[cfg(feature = "parallel")]
+        let histogram = parallel::fill_histogram(square_distances);
+        #[cfg(not(feature = "parallel"))]
+        let histogram = {
+            let mut histogram = DistanceHistogram::default();
+            for square_distance in square_distances.iter() {
+                histogram.add(*square_distance);
+            }
+            histogram
+        };
+        self.find_in_histogram(point_count, dimensions, &histogram)
+    }
 
-        // Part 1: Create bins for a logarithmic bucket sort, not the slower default sort 
-        //         and gather the distances between points into bins. 
-        let largest_possible_square_distance = 1_u64 << (2 * self.bits_per_dimension);
-        let mut bins = DistanceBin::make_bins(20, largest_possible_square_distance, 20, 1.05);
-        
-        for pair in hilbert_sorted_distances.iter() {
-            let bin_index = DistanceBin::find_bin(pair.square_distance, &mut bins);
-            let bin = &mut bins[bin_index];
-            bin.add(pair.square_distance);
-        }
+    /// Tally the histogram into the bins used by `find_by_binning`, which span every possible square distance for the given number of dimensions.
+    fn make_bins(&self, dimensions : usize, histogram : &DistanceHistogram) -> Vec<DistanceBin> {
+        let largest_possible_square_distance = largest_possible_square_distance(dimensions, self.bits_per_dimension);
+        DistanceBin::make_bins(histogram, 20, largest_possible_square_distance, 20, 1.05)
+    }
 
-        // Part 2: Consolidate the buckets to combine consecutive empty or nearly empty buckets into one bucket. 
+    /// Find the linkage distance from a histogram already filled with the square distances between successive pairs of points. 
+    /// 
+    /// This is the second half of `find_by_binning`, shared with the streaming analysis, which fills its histogram as it goes.
+    pub(crate) fn find_in_histogram(&self, point_count : usize, dimensions : usize, histogram : &DistanceHistogram) -> u64 {
+        // Part 2: Tally the histogram into coarse bins, then consolidate the bins to combine consecutive empty 
+        //         or nearly empty bins into one bin. 
         let minimum_size = if self.noise_skip_by < 5 { 5 } else { self.noise_skip_by };
-        bins = DistanceBin::consolidate(bins, minimum_size as usize);
+        let bins = DistanceBin::consolidate(histogram, self.make_bins(dimensions, histogram), minimum_size as usize);
 
         let mut index_of_maximum_increase = 0;
         let mut index_of_maximum_ratio = 0;
@@ -327,10 +863,10 @@ impl SingleLinkage {
         let mut i_bin_of_maximum_ratio = 0;
         let mut max_increase = 0;
         let mut max_ratio = 0.0;
-        let num_points = points.len();
+        let num_points = point_count;
         let mut cume_points = 0_usize;
 
-        let conservative_high_index = num_points - self.minimum_cluster_count as usize;
+        let conservative_high_index = num_points.saturating_sub(self.minimum_cluster_count as usize);
 
         // Part 3: Narrow the focus to a single bin likely to have the largest jump in distance.
         for (i_bin, bin) in bins.iter().enumerate() {
@@ -343,7 +879,7 @@ impl SingleLinkage {
 
                 let spread = bin.average_spread();
                 let previous_spread = if i_bin == 0 { 0 } else { bins[i_bin - 1].average_spread() };
-                let diff = spread - previous_spread;
+                let diff = spread.saturating_sub(previous_spread);
                 if diff > max_increase
                 {
                     max_increase = diff;
@@ -372,13 +908,10 @@ impl SingleLinkage {
 
         let i_bin_to_use =
             // If the two measures agree, we have an unambiguous choice.
-            if index_of_maximum_increase == index_of_maximum_ratio {
-                i_bin_of_maximum_increase
-            }
             // If the highest ratio in length between one distance and the next is at an early index,
             // it is likely because we skipped from a really low value (like 1) to another really low value (like 10)
             // which only looks like a large jump because the values are so small.
-            else if index_of_maximum_ratio < num_points / 2 {
+            if index_of_maximum_increase == index_of_maximum_ratio || index_of_maximum_ratio < num_points / 2 {
                 i_bin_of_maximum_increase
             }
             // Once we get near the end of the series of distances, the jumps between successive
@@ -390,18 +923,14 @@ impl SingleLinkage {
 
         // Part 5: Analyze the selected bin to find the place where the distance grew the fastest. 
         // Do not use noise_skip_by to adjust index_to_use in this method, because the binning already smooths the curve. 
-        // Sort the selected bin and find the place of the biggest jump with it. 
-        //TODO: Sort and analyze selected bin. 
-        let highest_value_from_previous_bin = if i_bin_to_use == 0 || bins[i_bin_to_use - 1].len() == 0 {
+        // Search the histogram buckets that make up the selected bin for the place of the biggest jump. 
+        let highest_value_from_previous_bin = if i_bin_to_use == 0 || bins[i_bin_to_use - 1].is_empty() {
             bins[i_bin_to_use].bounds.start
         }
         else {
             bins[i_bin_to_use - 1].highest_value_added
         };
-        let maximum_square_distance = bins[i_bin_to_use].find_square_distance_before_jump(highest_value_from_previous_bin); 
-
-        let result = self.estimate_cluster_counts(hilbert_sorted_distances, maximum_square_distance);
-        result
+        bins[i_bin_to_use].find_square_distance_before_jump(histogram, highest_value_from_previous_bin)
     }
 
     /// Estimate how many large clusters and outliers would be formed if we cluster using the
@@ -420,51 +949,162 @@ impl SingleLinkage {
     /// that deserve to be merged into one. 
     /// 
     ///   - hilbert_sorted_distances - Distances between consecutive pairs of points that are 
-    ///     sorted in Hilbert order (not by ascending distance)
+    ///     sorted in Hilbert order (not by ascending distance). A slice or any other sequence of pairs will do, 
+    ///     so the pairs may be streamed from disk.
     ///   - linkage_square_distance - Upper limit on distance between two points that permits them to be clustered together. 
-    pub fn estimate_cluster_counts(&self, hilbert_sorted_distances : &Vec<AdjacentPairDistance>, linkage_square_distance : u64) -> LinkageResult {
+    pub fn estimate_cluster_counts<I>(&self, hilbert_sorted_distances : I, linkage_square_distance : u64) -> LinkageResult
+    where I : IntoIterator, I::Item : Borrow<AdjacentPairDistance>
+    {
+        self.estimate_weighted_cluster_counts(hilbert_sorted_distances, linkage_square_distance, &PointWeights::new())
+    }
+
+    /// Estimate the cluster counts as `estimate_cluster_counts` does, except that each point counts as many times as its weight. 
+    /// 
+    /// A run whose total weight does not exceed `outlier_cluster_size` is an outlier, and `outlier_count` is the total weight of such runs.
+    pub fn estimate_weighted_cluster_counts<I>(&self, hilbert_sorted_distances : I, linkage_square_distance : u64, weights : &PointWeights) -> LinkageResult
+    where I : IntoIterator, I::Item : Borrow<AdjacentPairDistance>
+    {
         if linkage_square_distance == 0 { panic!("linkage_square_distance must be greater than zero."); }
         let mut linkage = LinkageResult::new();
         linkage.linkage_square_distance = linkage_square_distance;
-        linkage.outlier_cluster_count = 0;
-        linkage.outlier_count = 0;
-        linkage.count_of_too_large_distances = 0;
-        linkage.large_cluster_count = 0;
-
-        let mut start_index_for_cluster = 0;
-        let final_id = hilbert_sorted_distances.last().unwrap().second_id;
-        for pair in hilbert_sorted_distances {
+
+        let mut run_count = 0_u32;
+        let outlier_cluster_size = self.outlier_cluster_size as u64;
+        Self::walk_runs(hilbert_sorted_distances, linkage_square_distance, weights, |_, run_weight| {
+            run_count += 1;
+            if run_weight <= outlier_cluster_size {
+                linkage.outlier_cluster_count += 1;
+                linkage.outlier_count = linkage.outlier_count.saturating_add(run_weight.min(u32::MAX as u64) as u32);
+            }
+            else {
+                linkage.large_cluster_count += 1;
+            }
+        });
+        // Every run after the first was started by a distance that was too large.
+        linkage.count_of_too_large_distances = run_count.saturating_sub(1);
+        linkage
+    }
+
+    /// Segment the points into runs of consecutive points in Hilbert Curve order 
+    /// where each point is separated from the next by no more than the `linkage_square_distance`. 
+    /// 
+    /// These are the same runs counted by `estimate_cluster_counts`, and form the first pass 
+    /// of a single-link clustering. Runs whose size does not exceed `outlier_cluster_size` are flagged as outliers.
+    /// 
+    ///   - hilbert_sorted_distances - Distances between consecutive pairs of points that are 
+    ///     sorted in Hilbert order (not by ascending distance), as generated by `AdjacentPairDistance::all_pairs`.
+    ///   - linkage_square_distance - Upper limit on distance between two points that permits them to be clustered together. 
+    ///   - returns - The runs in Hilbert Curve order. 
+    pub fn find_runs(&self, hilbert_sorted_distances : &[AdjacentPairDistance], linkage_square_distance : u64) -> Vec<HilbertRun> {
+        self.find_weighted_runs(hilbert_sorted_distances, linkage_square_distance, &PointWeights::new())
+    }
+
+    /// Segment the points into runs as `find_runs` does, except that runs are flagged as outliers 
+    /// if their total weight does not exceed `outlier_cluster_size`.
+    pub fn find_weighted_runs(&self, hilbert_sorted_distances : &[AdjacentPairDistance], linkage_square_distance : u64, weights : &PointWeights) -> Vec<HilbertRun> {
+        let mut runs = Vec::new();
+        let first_index = match hilbert_sorted_distances.first() {
+            Some(pair) => pair.first_index,
+            None => return runs
+        };
+        Self::walk_runs(hilbert_sorted_distances, linkage_square_distance, weights, |run, weight| {
+            // The point at index i is the first point of the pair at offset i, except for the very last point.
+            let point_ids = run.clone()
+                .map(|i| {
+                    let offset = i - first_index;
+                    if offset == 0 { hilbert_sorted_distances[0].first_id }
+                    else { hilbert_sorted_distances[offset - 1].second_id }
+                })
+                .collect();
+            runs.push(HilbertRun {
+                is_outlier : weight <= self.outlier_cluster_size as u64,
+                index_range : run,
+                point_ids,
+                weight
+            });
+        });
+        runs
+    }
+
+    /// Form a rough `Clustering` from the runs found by `find_runs`, one `Cluster` per run (including outlier runs).
+    /// 
+    /// Merging some of these clusters is the job of subsequent passes of a full single-link clustering.
+    pub fn seed_clustering(&self, hilbert_sorted_distances : &[AdjacentPairDistance], linkage_square_distance : u64) -> Clustering<usize,usize,Range<usize>> {
+        HilbertRun::to_clustering(&self.find_runs(hilbert_sorted_distances, linkage_square_distance))
+    }
+
+    /// Walk the distances between consecutive points in Hilbert order and report the range of point indices
+    /// and the total weight of each run of points separated by no more than the `linkage_square_distance`. 
+    fn walk_runs<I, F>(hilbert_sorted_distances : I, linkage_square_distance : u64, weights : &PointWeights, mut visit_run : F)
+    where I : IntoIterator, I::Item : Borrow<AdjacentPairDistance>, F : FnMut(Range<usize>, u64) 
+    {
+        let mut start_index_for_cluster : Option<usize> = None;
+        let mut run_weight = 0_u64;
+        let mut last_index = 0;
+        for item in hilbert_sorted_distances {
+            let pair = item.borrow();
+            let start_index = start_index_for_cluster.get_or_insert_with(|| {
+                run_weight = weights.get(pair.first_id);
+                pair.first_index
+            });
             if pair.square_distance > linkage_square_distance {
                 // Close out current cluster and start a new one.
-                let cluster_size = pair.second_index - start_index_for_cluster;
-                if cluster_size <= self.outlier_cluster_size as usize {
-                    linkage.outlier_cluster_count += 1;
-                    linkage.outlier_count += cluster_size as u32;
-                }
-                else {
-                    linkage.large_cluster_count += 1;
-                }
-                linkage.count_of_too_large_distances += 1;
-
-                if pair.second_id == final_id {
-                    // Finish off the last cluster, a single point. 
-                    linkage.outlier_cluster_count += 1;
-                    linkage.outlier_count += 1;
-                }
-                start_index_for_cluster = pair.second_index;
+                visit_run(*start_index..pair.second_index, run_weight);
+                *start_index = pair.second_index;
+                run_weight = 0;
             }
-            else if pair.second_id == final_id {
-                let cluster_size = pair.second_index - start_index_for_cluster + 1; // +1 to Include last point.
-                if cluster_size <= self.outlier_cluster_size as usize {
-                    linkage.outlier_cluster_count += 1;
-                    linkage.outlier_count += cluster_size as u32;
-                }
-                else {
-                    linkage.large_cluster_count += 1;
+            run_weight = run_weight.saturating_add(weights.get(pair.second_id));
+            last_index = pair.second_index;
+        }
+        // Finish off the last cluster, which may be a single point. 
+        if let Some(start_index) = start_index_for_cluster {
+            visit_run(start_index..last_index + 1, run_weight);
+        }
+    }
+}
+
+// ........................... HilbertRun ..........................................
+
+/// A run of consecutive points in Hilbert Curve order where each point is within the linkage distance of the next. 
+/// 
+/// Runs are the seed clusters for single-link clustering.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HilbertRun {
+    /// Zero-based indices of the points in Hilbert curve order (end exclusive).
+    pub index_range : Range<usize>,
+
+    /// Ids of the points in the run, in Hilbert curve order.
+    pub point_ids : Vec<usize>,
+
+    /// True if the run has no more than `outlier_cluster_size` points (or total weight, for weighted points).
+    pub is_outlier : bool,
+
+    /// Total weight of the points in the run, which is the number of points unless they are weighted.
+    pub weight : u64
+}
+
+impl HilbertRun {
+    /// Number of points in the run.
+    pub fn len(&self) -> usize { self.point_ids.len() }
+
+    /// Is the `HilbertRun` empty?
+    pub fn is_empty(&self) -> bool { self.point_ids.is_empty() }
+
+    /// Create a `Clustering` with one `Cluster` for each run, whose members are the point ids. 
+    pub fn to_clustering(runs : &[HilbertRun]) -> Clustering<usize,usize,Range<usize>> {
+        let mut clustering = integer_clustering();
+        for run in runs {
+            let mut ids = run.point_ids.iter();
+            if let Some(first_id) = ids.next() {
+                let category = clustering.add_to_new_cluster(*first_id)
+                    .unwrap_or_else(|_| panic!("Point {} is in more than one run", first_id));
+                for id in ids {
+                    clustering.add_to_cluster(*id, category)
+                        .unwrap_or_else(|_| panic!("Point {} is in more than one run", id));
                 }
             }
         }
-        linkage
+        clustering
     }
 }
 
@@ -481,6 +1121,10 @@ pub struct DistanceGrowthStats {
     max_ratio_paired : f64
 }
 
+impl Default for DistanceGrowthStats {
+    fn default() -> Self { Self::new() }
+}
+
 /// Internal struct for accumulating guesses as to where the curve formed by square distances between points grows the fastest.
 impl DistanceGrowthStats {
     pub fn new() -> Self {
@@ -529,7 +1173,7 @@ impl DistanceGrowthStats {
     ///     little larger than the linkage distance. If there is a gap in distances, this points to the high side
     ///     of the gap. The linkage distance will be on the low side of the gap.
     pub fn get_index_after_max_change(&self, i_low_paired : usize, i_high : usize) -> usize {
-        let i_conservative = i_low_paired + (i_high - i_low_paired) * 3 / 4;
+        let i_conservative = i_low_paired + i_high.saturating_sub(i_low_paired) * 3 / 4;
         if self.index_of_maximum_increase_and_ratio > i_high {
             i_high
         }
@@ -552,6 +1196,8 @@ impl DistanceGrowthStats {
 // ........................... AdjacentPairDistance ..........................................
 
 /// Measures the square distance between a pair of `Points` identified by their index into an ordered collection of `Points`.
+/// 
+/// The square distance saturates at `u64::MAX` instead of overflowing.
 #[derive(Copy, Clone, Debug)]
 pub struct AdjacentPairDistance {
     /// Square of the distance between two points.
@@ -586,7 +1232,7 @@ impl AdjacentPairDistance {
     /// Construct a new AdjacentPairSequence.
     pub fn new(p1 : &Point, p2 : &Point, index1 : usize, index2 : usize) -> Self {
         AdjacentPairDistance { 
-            square_distance : p1.square_distance(p2),
+            square_distance : saturating_square_distance(p1, p2),
             first_index : index1,
             second_index : index2,
             first_id : p1.get_id(),
@@ -595,8 +1241,19 @@ impl AdjacentPairDistance {
     }
 
     /// Generate a collection of the distances between consecutive points. 
-    pub fn all_pairs(points : &Vec<Point>) -> Vec<AdjacentPairDistance> {
+    /// 
+    /// With the `parallel` feature, the distances are computed in parallel.
+    pub fn all_pairs(points : &[Point]) -> Vec<AdjacentPairDistance> {
         if points.len() <= 1 { return Vec::new(); }
+        #[cfg(feature = "parallel")]
+        let pairs = parallel::all_pairs(points);
+        #[cfg(not(feature = "parallel"))]
+        let pairs = Self::all_pairs_serially(points);
+        pairs
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn all_pairs_serially(points : &[Point]) -> Vec<AdjacentPairDistance> {
         let mut pairs = Vec::with_capacity(points.len() - 1);
         let mut previous = &points[0];
         for (index, current) in points.iter().skip(1).enumerate() {
@@ -610,155 +1267,72 @@ impl AdjacentPairDistance {
 // ........................... DistanceBin ..........................................
 
 
-/// Bin to hold unsorted values that fall within the bounds of the bin, as part of a bin sort. 
-#[derive(Clone)]
+/// Bin to count the values that fall within the bounds of the bin, as part of a bin sort. 
+/// 
+/// The bins are coarse groupings of the buckets of a `DistanceHistogram`, which holds the counts. 
+/// A bin is a summary of the histogram over its bounds, so bins are only made from a histogram.
+#[derive(Clone, Debug, PartialEq)]
 pub struct DistanceBin {
-    /// All values falling in this range can be added to this bin. 
+    /// All values falling in this range belong to this bin. 
     pub bounds : Range<u64>,
 
-    /// Lowest of the values already added to this bin.
+    /// Lowest of the values in this bin, or the end of the bounds if empty.
     pub lowest_value_added : u64,
 
-    /// Highest of the values already added to this bin.
+    /// Highest of the values in this bin, or the start of the bounds if empty.
     pub highest_value_added : u64,
 
-    /// Values that have been added to this bin. 
-    pub values_added : Vec<u64>
+    /// Number of values in this bin. 
+    pub count : usize
 }
 
 impl DistanceBin {
-    /// Create a new DistanceBin with a defined lower and upper bound of values that can be added to it.
-    pub fn new(from : u64, to : u64) -> Self {
-        DistanceBin { 
-            bounds : from..to,
-            lowest_value_added : to,
-            highest_value_added : from,
-            values_added : Vec::new()
-        }
+    /// Summarize the values of the histogram that fall within each of the given bounds as a bin.
+    fn tally(histogram : &DistanceHistogram, bounds : Vec<Range<u64>>) -> Vec<Self> {
+        histogram.tally(&bounds).into_iter()
+            .zip(bounds)
+            .map(|(tally, bounds)| {
+                let (lowest_value_added, highest_value_added, count) = tally.unwrap_or((bounds.end, bounds.start, 0));
+                DistanceBin { bounds, lowest_value_added, highest_value_added, count : count as usize }
+            })
+            .collect()
     }
 
-    /// Make a series of bins of increasing size that goes from zero to top_of_highest_bin. 
+    /// Make a series of bins of increasing size that goes from zero to top_of_highest_bin, holding the values in the histogram. 
     /// 
+    ///   - `histogram` - Source of the counts.
     ///   - `top_of_lowest_bin` - The first bin ranges from zero (inclusive) to this number (exclusive).
-    ///   - `top_of_highest_bin` - The last bin will end with this value (exclusive). 
+    ///   - `top_of_highest_bin` - The last bin will include this value. 
     ///   - `minimum_bin_width` - No bin will be narrower than this.
     ///   - `multiplier` - The top of each bin will equal the top of the previous bin times this multiplier,
-    ///      unless that makes a bin whose width is less than `minimum_bin_width`. If this multiplier is 
-    ///      less than 1.001, it will be set to 1.001. A good value is 1.05. 
-    pub fn make_bins(top_of_lowest_bin : u64, top_of_highest_bin : u64, minimum_bin_width : u64, mut multiplier : f64) -> Vec<DistanceBin> {
-        let mut bins = Vec::with_capacity(1000);
-        if multiplier < 1.001 { multiplier = 1.001; }
-        bins.push(DistanceBin::new(0, top_of_lowest_bin));
-        let mut next_bottom = top_of_lowest_bin as f64;
-        let mut next_top = (top_of_lowest_bin as f64 * multiplier).ceil();
-        let tippy_top = top_of_highest_bin as f64;
-        let min_width = minimum_bin_width as f64;
-        while next_top < tippy_top {
-            if next_top - next_bottom < min_width {
-                next_top = next_bottom + min_width;
-            }
-            bins.push(DistanceBin::new(next_bottom as u64, next_top as u64));
-            next_bottom = next_top;
-            next_top = next_top * multiplier;
-        }
-        bins.push(DistanceBin::new(next_bottom as u64, next_top as u64));
-        bins
+    ///     unless that makes a bin whose width is less than `minimum_bin_width`. If this multiplier is 
+    ///     less than 1.001, it will be set to 1.001. A good value is 1.05. 
+    pub fn make_bins(histogram : &DistanceHistogram, top_of_lowest_bin : u64, top_of_highest_bin : u64, minimum_bin_width : u64, multiplier : f64) -> Vec<DistanceBin> {
+        Self::tally(histogram, logarithmic_bounds(top_of_lowest_bin, top_of_highest_bin, minimum_bin_width, multiplier))
     }
 
-    /// Perform a binary search for the bin that contains the given value.
-    /// Assumes that there are no gaps between bins.
-    /// 
-    ///   - value - Search for a bin that contains this value.
-    ///   - bins - bins in sorted order (ascending). 
-    pub fn find_bin(value : u64, bins : &mut Vec<DistanceBin>) -> usize {
-        if bins.last_mut().unwrap().bounds.end <= value { return bins.len() - 1; }
-
-        let bin_opt = bins.binary_search_by(
-            |probe| {
-                if value < probe.bounds.start { return Ordering::Greater; }
-                if value > probe.bounds.end { return Ordering::Less; }
-                return Ordering::Equal;
-        });
-        return bin_opt.unwrap();
-    }
-
-    /// Does the given square_distance fall within the bounds of the bin?
-    pub fn is_in_bounds(&self, square_distance : u64) -> bool {
-        self.bounds.contains(&square_distance)
-    }
-
-    /// Add an item to the bin if it falls within the bounds.
-    /// 
-    ///   - return true if able to add the value, false otherwise
-    pub fn add(&mut self, square_distance : u64) -> bool {
-        if !self.is_in_bounds(square_distance) { false }
-        else {
-            if square_distance > self.highest_value_added { self.highest_value_added = square_distance; }
-            if square_distance < self.lowest_value_added { self.lowest_value_added = square_distance; }
-            self.values_added.push(square_distance);
-            true
-        }
-    }
-
-    /// Fluently merge two bins by widening the given DistanceBin to accommodate the full range between it and higher_bin. 
-    /// 
-    ///   - `higher_bin` - Must enclose a range that starts and ends higher than the given bin's range.
-    ///   - return - The same bin, with changes.
-    pub fn merge(mut self, higher_bin : &Self) -> Self{
-        if higher_bin.bounds.start < self.bounds.end {
-            panic!("The second bin in a merge must have bounds that come after those of the first bin.");
-        }
-        self.bounds = self.bounds.start .. higher_bin.bounds.end;
-        if higher_bin.len() > 0 {
-            self.values_added.extend(higher_bin.values_added.iter());
-            self.highest_value_added = higher_bin.highest_value_added;
-        }
-        self
-    }
-
-    /// Sort the items contained in the bin. 
-    pub fn sort(&mut self) {
-        self.values_added.sort();
-    }
-
-    /// Move all bins from one vector to another, but consolidate consecutive runs of bins into a single bin if they hold too few items.
+    /// Consolidate consecutive runs of bins into a single bin if they hold too few items.
     /// 
     /// The search for the linkage distance requires that we have several items in a bin in order to decide if the contained values have jumped a lot. 
     /// 
+    ///   - `histogram` - The histogram from which the bins were made.
     ///   - `original_bins` - Bins to be consolidated. 
     ///   - `minimum_size` - If a bin does not contain at least this many items, merge it with the following bin. 
-    ///                      Continue this repeatedly until either the bin grows large enough or we reach the last bin
-    ///                      in the Vec. 
-    pub fn consolidate(original_bins : Vec<Self>, minimum_size : usize) -> Vec<Self> {
-        let mut consolidated_bins = Vec::with_capacity(original_bins.len());
-        let mut hold_bin_opt : Option<Self> = None;
-        for bin in original_bins {
-            match hold_bin_opt {
-                Some (ref hold_bin) => {
-                    let merged = bin.merge(hold_bin);
-                    if merged.len() >= minimum_size {
-                        consolidated_bins.push(merged);
-                        hold_bin_opt = None;
-                    }
-                    else { hold_bin_opt = Some(merged); }
-                },
-                None => {
-                    if bin.len() >= minimum_size { consolidated_bins.push(bin); }
-                    else { hold_bin_opt = Some(bin); }
-                }
-            }
-        }
-        if let Some(hold_bin) = hold_bin_opt {
-            consolidated_bins.push(hold_bin);
-        }
-        consolidated_bins
+    ///     Continue this repeatedly until either the bin grows large enough or we reach the last bin
+    ///     in the Vec. 
+    pub fn consolidate(histogram : &DistanceHistogram, original_bins : Vec<Self>, minimum_size : usize) -> Vec<Self> {
+        let bounds : Vec<Range<u64>> = original_bins.into_iter().map(|bin| bin.bounds).collect();
+        Self::tally(histogram, histogram.consolidate(&bounds, minimum_size as u64))
     }
 
     /// Number of values that have been added to the bin.
     pub fn len(&self) -> usize {
-        self.values_added.len()
+        self.count
     }
 
+    /// Is the `DistanceBin` empty?
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
     /// Average difference between consecutive points within the bin. 
     /// 
     ///   - If DistanceBin has zero or one points, the spread is width of the bounds. 
@@ -779,24 +1353,16 @@ impl DistanceBin {
         }
     }
 */
-    /// Sort the values_added in the bin by ascending value and find the place where the value jumps the most. 
+    /// Search the buckets of the histogram that fall within this bin for the place where the value jumps the most. 
     /// Return the value prior to the jump.
-    pub fn find_square_distance_before_jump(&mut self, highest_value_from_previous_bin : u64) -> u64 {
-        if self.values_added.len() == 0 { return self.bounds.start; }
-        if self.values_added.len() <= 2 { return self.lowest_value_added; }
-        self.sort();
-        let mut value_before_biggest_jump = highest_value_from_previous_bin;
-        let mut high_delta = self.lowest_value_added - highest_value_from_previous_bin;
-        let mut previous_value = highest_value_from_previous_bin;
-        for value in self.values_added.iter() {
-            let delta = value - previous_value;
-            if delta > high_delta {
-                value_before_biggest_jump = previous_value;
-                high_delta = delta;
-            }
-            previous_value = *value;
-        }
-        value_before_biggest_jump
+    /// 
+    ///   - `histogram` - The histogram whose buckets were tallied into this bin.
+    ///   - `highest_value_from_previous_bin` - Value to compare with the lowest value in this bin.
+    pub fn find_square_distance_before_jump(&self, histogram : &DistanceHistogram, highest_value_from_previous_bin : u64) -> u64 {
+        if self.is_empty() { return self.bounds.start; }
+        if self.len() <= 2 { return self.lowest_value_added; }
+        histogram.largest_jump(self.bounds.clone(), highest_value_from_previous_bin)
+            .map_or(self.lowest_value_added, |jump| jump.value_before_jump)
     }
 }
 
@@ -805,7 +1371,10 @@ mod tests {
     #[allow(unused_imports)]
     use std::cmp::Ordering;
     use spectral::prelude::*;
-    use super::AdjacentPairDistance;
+    use hilbert::Point;
+    use super::{AdjacentPairDistance, DistanceBin, SingleLinkage, PointError, PermutationCombination};
+    use crate::clustering::histogram::DistanceHistogram;
+    use crate::clustering::weights::PointWeights;
 
     #[test]
     fn adjacent_pair_distance_cmp(){
@@ -827,4 +1396,128 @@ mod tests {
         asserting("Should compare greater than").that(&(comparison == Ordering::Greater)).is_equal_to(true);
     }
 
+    /// Points along a line, already in Hilbert order: a run of twelve, a gap, a run of three, a gap, a lone point.
+    fn points_in_runs() -> Vec<Point> {
+        let mut points = Vec::new();
+        for x in 0..12_u32 { points.push(Point::new(points.len(), &[x, 0])); }
+        for x in 100..103_u32 { points.push(Point::new(points.len(), &[x, 0])); }
+        points.push(Point::new(points.len(), &[500, 0]));
+        points
+    }
+
+    #[test]
+    fn find_runs() {
+        let points = points_in_runs();
+        let distances = AdjacentPairDistance::all_pairs(&points);
+        let finder = SingleLinkage::new(points.len() as u32, 10);
+        let runs = finder.find_runs(&distances, 4);
+        asserting("Run count").that(&runs.len()).is_equal_to(3);
+        asserting("First run range").that(&runs[0].index_range).is_equal_to(0..12);
+        asserting("First run is large").that(&runs[0].is_outlier).is_equal_to(false);
+        asserting("Second run ids").that(&runs[1].point_ids).is_equal_to(vec![12, 13, 14]);
+        asserting("Second run is an outlier").that(&runs[1].is_outlier).is_equal_to(true);
+        asserting("Last run is a single point").that(&runs[2].point_ids).is_equal_to(vec![15]);
+
+        // The runs must agree with the counts.
+        let linkage = finder.estimate_cluster_counts(&distances, 4);
+        asserting("Large cluster count").that(&linkage.large_cluster_count).is_equal_to(1);
+        asserting("Outlier cluster count").that(&linkage.outlier_cluster_count).is_equal_to(2);
+        asserting("Outlier count").that(&linkage.outlier_count).is_equal_to(4);
+        asserting("Too large distances").that(&linkage.count_of_too_large_distances).is_equal_to(2);
+    }
+
+    #[test]
+    fn find_weighted_runs() {
+        let points = points_in_runs();
+        let distances = AdjacentPairDistance::all_pairs(&points);
+        let finder = SingleLinkage::new(points.len() as u32, 10);
+        // The lone point at the end stands for many items, so its run is no longer an outlier.
+        let weights = PointWeights::from_pairs(vec![(15, 50), (12, 2)]);
+        let runs = finder.find_weighted_runs(&distances, 4, &weights);
+        asserting("Run weights").that(&runs.iter().map(|run| run.weight).collect::<Vec<u64>>()).is_equal_to(vec![12, 4, 50]);
+        asserting("Heavy single point is not an outlier").that(&runs[2].is_outlier).is_equal_to(false);
+
+        let linkage = finder.estimate_weighted_cluster_counts(&distances, 4, &weights);
+        asserting("Large cluster count").that(&linkage.large_cluster_count).is_equal_to(2);
+        asserting("Outlier count is weighted").that(&linkage.outlier_count).is_equal_to(4);
+    }
+
+    #[test]
+    fn seed_clustering() {
+        let points = points_in_runs();
+        let distances = AdjacentPairDistance::all_pairs(&points);
+        let finder = SingleLinkage::new(points.len() as u32, 10);
+        let clustering = finder.seed_clustering(&distances, 4);
+        asserting("Cluster count").that(&clustering.cluster_count()).is_equal_to(3);
+        asserting("Member count").that(&clustering.member_count()).is_equal_to(points.len());
+        asserting("Same run together").that(&clustering.are_together(0, 11)).is_equal_to(true);
+        asserting("Different runs apart").that(&clustering.are_together(11, 12)).is_equal_to(false);
+    }
+
+    #[test]
+    fn from_points_derives_bits() {
+        let points = vec![Point::new(0, &[0, 5]), Point::new(1, &[1023, 7]), Point::new(2, &[3, 1024])];
+        asserting("Bits for 1024").that(&SingleLinkage::bits_required(&points)).is_equal_to(Ok(11));
+        asserting("Bits for all zeros").that(&SingleLinkage::bits_required(&[Point::new(0, &[0, 0])])).is_equal_to(Ok(1));
+        let finder = SingleLinkage::from_points(&points).unwrap();
+        asserting("Derived bits validate").that(&finder.validate(&points)).is_equal_to(Ok(()));
+    }
+
+    #[test]
+    fn from_points_rejects_bad_points() {
+        let mismatched = vec![Point::new(0, &[0, 5]), Point::new(1, &[1, 2, 3])];
+        asserting("Mismatched dimensions").that(&SingleLinkage::from_points(&mismatched).err())
+            .is_equal_to(Some(PointError::MismatchedDimensions { point_id : 1, expected_dimensions : 2, actual_dimensions : 3 }));
+        asserting("No points").that(&SingleLinkage::from_points(&[]).err()).is_equal_to(Some(PointError::NoPoints));
+
+        let too_large = vec![Point::new(0, &[0, 5]), Point::new(7, &[256, 2])];
+        asserting("Coordinate too large").that(&SingleLinkage::new(2, 8).validate(&too_large))
+            .is_equal_to(Err(PointError::CoordinateTooLarge { point_id : 7, coordinate : 256, bits_per_dimension : 8 }));
+    }
+
+    /// A bin too small to stand alone is held and merged with the bins that follow it, in ascending order.
+    #[test]
+    fn consolidate_merges_small_bins_upward() {
+        let bin_count = DistanceBin::make_bins(&DistanceHistogram::default(), 20, 200, 20, 1.05).len();
+        let mut histogram = DistanceHistogram::default();
+        for value in [5, 25, 30, 61, 62].iter() { histogram.add(*value); }
+        let bins = DistanceBin::make_bins(&histogram, 20, 200, 20, 1.05);
+        asserting("Same bins when filled").that(&bins.len()).is_equal_to(bin_count);
+        asserting("Value counted").that(&bins[1].len()).is_equal_to(2);
+        let consolidated = DistanceBin::consolidate(&histogram, bins, 2);
+        asserting("Fewer bins").that(&(consolidated.len() < bin_count)).is_equal_to(true);
+        asserting("First bin absorbs its neighbor").that(&consolidated[0].len()).is_equal_to(3);
+        asserting("First bin keeps its start").that(&consolidated[0].bounds.start).is_equal_to(0);
+        asserting("First bin ends at the neighbor's end").that(&consolidated[0].bounds.end).is_equal_to(40);
+        asserting("No values lost").that(&consolidated.iter().map(|bin| bin.len()).sum::<usize>()).is_equal_to(5);
+    }
+
+    #[test]
+    fn saturating_square_distance() {
+        let p1 = Point::new(0, &[3, 10, 0]);
+        let p2 = Point::new(1, &[0, 14, 0]);
+        asserting("Small distance").that(&super::saturating_square_distance(&p1, &p2)).is_equal_to(25);
+        asserting("Agrees with Point").that(&super::saturating_square_distance(&p1, &p2)).is_equal_to(p1.square_distance(&p2));
+
+        // Two dimensions at full 32-bit range sum to about 3.7 x 10^19, which exceeds u64::MAX.
+        let far1 = Point::new(0, &[u32::MAX, 0]);
+        let far2 = Point::new(1, &[0, u32::MAX]);
+        asserting("Saturated distance").that(&super::saturating_square_distance(&far1, &far2)).is_equal_to(u64::MAX);
+    }
+
+    #[test]
+    fn largest_possible_square_distance() {
+        asserting("Two dimensions, 20 bits").that(&super::largest_possible_square_distance(2, 20)).is_equal_to(2 * 1_048_575 * 1_048_575);
+        asserting("10,000 dimensions, 20 bits").that(&super::largest_possible_square_distance(10_000, 20)).is_equal_to(10_000 * 1_048_575 * 1_048_575);
+        asserting("One dimension, 32 bits").that(&super::largest_possible_square_distance(1, 32)).is_equal_to(18_446_744_065_119_617_025);
+        asserting("10,000 dimensions, 32 bits").that(&super::largest_possible_square_distance(10_000, 32)).is_equal_to(u64::MAX);
+    }
+
+    #[test]
+    fn permutation_combination() {
+        asserting("Median of odd count").that(&PermutationCombination::Median.combine(&mut [9, 1, 5])).is_equal_to(5);
+        asserting("Median of even count is lower middle").that(&PermutationCombination::Median.combine(&mut [9, 1, 5, 7])).is_equal_to(5);
+        asserting("Minimum").that(&PermutationCombination::Minimum.combine(&mut [9, 1, 5, 7])).is_equal_to(1);
+    }
+
 }
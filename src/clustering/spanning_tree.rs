@@ -0,0 +1,361 @@
+use std::ops::Range;
+use std::collections::HashMap;
+use hilbert::Point;
+use super::{Clustering, integer_clustering};
+use super::single_linkage::{AdjacentPairDistance, saturating_square_distance};
+use super::weights::PointWeights;
+
+/// Above this many points, `MinimumSpanningTree::new` switches from the exact, quadratic algorithm
+/// to the approximate algorithm that only considers edges between points near each other on the Hilbert curve.
+pub const EXACT_POINT_LIMIT : usize = 5_000;
+
+/// Default number of following points in Hilbert order to which each point is joined by a candidate edge
+/// when approximating the spanning tree.
+pub const DEFAULT_HILBERT_WINDOW : usize = 10;
+
+// ........................... MinimumSpanningTree ..........................................
+
+/// Euclidean Minimum Spanning Tree (EMST) of a set of `Points`, the backbone of **exact single-link clustering**.
+///
+/// Removing every edge of the tree longer than the linkage distance leaves connected components
+/// that are exactly the clusters formed by single-link agglomerative clustering at that distance.
+/// This makes the tree a reference against which the faster Hilbert curve heuristics
+/// (such as the runs from `SingleLinkage::find_runs`) can be judged.
+///
+/// Two algorithms are provided:
+///
+///   - `exact` - **Prim's algorithm** over the complete graph, which is **O(N²)** and only suitable for small N.
+///   - `approximate` - **Kruskal's algorithm** over candidate edges joining each point to the next few points
+///     in Hilbert curve order, which is **O(N Log N)**. Every edge it selects is at least as long as the
+///     exact edge it stands in for, so its clusters never merge points that the exact clusters keep apart.
+#[derive(Clone, Debug)]
+pub struct MinimumSpanningTree {
+    /// Ids of the points, in the order used by the `first_index` and `second_index` of the edges.
+    point_ids : Vec<usize>,
+
+    /// The N - 1 edges of the tree, sorted by ascending square distance.
+    edges : Vec<AdjacentPairDistance>
+}
+
+impl MinimumSpanningTree {
+    /// Build the tree using the exact algorithm if there are no more than `EXACT_POINT_LIMIT` points,
+    /// otherwise use the approximate algorithm with a window of `DEFAULT_HILBERT_WINDOW`.
+    ///
+    ///   - `points` - Points to connect.
+    ///   - `bits_per_dimension` - Number of bits needed to encode the largest coordinate value, used by the Hilbert sort.
+    pub fn new(points : &[Point], bits_per_dimension : u8) -> Self {
+        if points.len() <= EXACT_POINT_LIMIT { Self::exact(points) }
+        else { Self::approximate(points, bits_per_dimension, DEFAULT_HILBERT_WINDOW) }
+    }
+
+    /// Build the exact Euclidean Minimum Spanning Tree using Prim's algorithm on the complete graph.
+    ///
+    /// This requires O(N²) distance computations but only O(N) memory.
+    pub fn exact(points : &[Point]) -> Self {
+        let n = points.len();
+        let point_ids = points.iter().map(|p| p.get_id()).collect();
+        let mut edges = Vec::with_capacity(n.saturating_sub(1));
+        if n <= 1 { return MinimumSpanningTree { point_ids, edges }; }
+
+        // For each point not yet in the tree, the shortest known square distance to the tree and the tree point at the other end.
+        let mut in_tree = vec![false; n];
+        let mut best_square_distance = vec![u64::MAX; n];
+        let mut best_neighbor = vec![0_usize; n];
+        let mut newest = 0_usize;
+        in_tree[0] = true;
+        for _ in 1..n {
+            let mut closest = n;
+            for i in 0..n {
+                if in_tree[i] { continue; }
+                let square_distance = saturating_square_distance(&points[newest], &points[i]);
+                if square_distance < best_square_distance[i] {
+                    best_square_distance[i] = square_distance;
+                    best_neighbor[i] = newest;
+                }
+                if closest == n || best_square_distance[i] < best_square_distance[closest] { closest = i; }
+            }
+            in_tree[closest] = true;
+            let neighbor = best_neighbor[closest];
+            edges.push(AdjacentPairDistance::new(&points[neighbor], &points[closest], neighbor, closest));
+            newest = closest;
+        }
+        edges.sort();
+        MinimumSpanningTree { point_ids, edges }
+    }
+
+    /// Build an approximate Euclidean Minimum Spanning Tree using Kruskal's algorithm on candidate edges
+    /// that join each point to the `window` points that follow it in Hilbert curve order.
+    ///
+    ///   - `points` - Points to connect. They are not modified; a copy is sorted in Hilbert order.
+    ///   - `bits_per_dimension` - Number of bits needed to encode the largest coordinate value.
+    ///   - `window` - How many following points to consider for each point. A value of zero is treated as one.
+    pub fn approximate(points : &[Point], bits_per_dimension : u8, window : usize) -> Self {
+        let mut sorted_points = points.to_vec();
+        Point::hilbert_sort(&mut sorted_points, bits_per_dimension as usize);
+        let n = sorted_points.len();
+        let window = window.max(1);
+
+        let mut candidates = Vec::with_capacity(n * window);
+        for i in 0..n {
+            for j in (i + 1)..n.min(i + 1 + window) {
+                candidates.push(AdjacentPairDistance::new(&sorted_points[i], &sorted_points[j], i, j));
+            }
+        }
+        candidates.sort();
+
+        // Consecutive points are always candidates, so the candidate graph is connected and the result is a true tree.
+        let mut components = DisjointSet::new(n);
+        let mut edges = Vec::with_capacity(n.saturating_sub(1));
+        for candidate in candidates {
+            if components.union(candidate.first_index, candidate.second_index) {
+                edges.push(candidate);
+                if edges.len() + 1 == n { break; }
+            }
+        }
+        MinimumSpanningTree {
+            point_ids : sorted_points.iter().map(|p| p.get_id()).collect(),
+            edges
+        }
+    }
+
+    /// Number of points spanned by the tree.
+    pub fn len(&self) -> usize { self.point_ids.len() }
+
+    /// Does the tree span no points?
+    pub fn is_empty(&self) -> bool { self.point_ids.is_empty() }
+
+    /// Ids of the points, in the order referenced by the `first_index` and `second_index` of the edges.
+    pub fn get_point_ids(&self) -> &[usize] { &self.point_ids }
+
+    /// Edges of the tree, sorted by ascending square distance.
+    pub fn get_edges(&self) -> &[AdjacentPairDistance] { &self.edges }
+
+    /// Perform single-link clustering by cutting all edges longer than the linkage distance.
+    ///
+    ///   - `linkage_square_distance` - Points whose square distance does not exceed this are linked into the same `Cluster`.
+    ///   - returns - A `Clustering` holding every point, where each `Cluster` is a connected component of the pruned tree.
+    pub fn clustering(&self, linkage_square_distance : u64) -> Clustering<usize,usize,Range<usize>> {
+        let mut components = DisjointSet::new(self.len());
+        for edge in self.edges.iter().take_while(|edge| edge.square_distance <= linkage_square_distance) {
+            components.union(edge.first_index, edge.second_index);
+        }
+        let mut clustering = integer_clustering();
+        let mut root_to_category : HashMap<usize,usize> = HashMap::new();
+        for (index, id) in self.point_ids.iter().enumerate() {
+            let root = components.find(index);
+            match root_to_category.get(&root) {
+                Some(category) => {
+                    clustering.add_to_cluster(*id, *category).unwrap_or_else(|_| panic!("Point id {} is not unique", id));
+                },
+                None => {
+                    let category = clustering.add_to_new_cluster(*id).unwrap_or_else(|_| panic!("Point id {} is not unique", id));
+                    root_to_category.insert(root, category);
+                }
+            }
+        }
+        clustering
+    }
+
+    /// Derive the full single-link hierarchy from the tree.
+    pub fn dendrogram(&self) -> Dendrogram {
+        self.weighted_dendrogram(&PointWeights::new())
+    }
+
+    /// Derive the full single-link hierarchy from the tree, recording the total weight of each merged cluster.
+    pub fn weighted_dendrogram(&self, weights : &PointWeights) -> Dendrogram {
+        let n = self.len();
+        // Total weight of the points beneath each node, leaves first.
+        let mut node_weights : Vec<u64> = self.point_ids.iter().map(|id| weights.get(*id)).collect();
+        let mut components = DisjointSet::new(n);
+        // Node number of the dendrogram node currently representing each root in the DisjointSet.
+        let mut root_to_node : Vec<usize> = (0..n).collect();
+        let mut merges = Vec::with_capacity(self.edges.len());
+        for edge in self.edges.iter() {
+            let first_root = components.find(edge.first_index);
+            let second_root = components.find(edge.second_index);
+            let first_node = root_to_node[first_root];
+            let second_node = root_to_node[second_root];
+            components.union(first_root, second_root);
+            let merged_root = components.find(first_root);
+            root_to_node[merged_root] = n + merges.len();
+            let weight = node_weights[first_node].saturating_add(node_weights[second_node]);
+            node_weights.push(weight);
+            merges.push(DendrogramMerge {
+                first_node : first_node.min(second_node),
+                second_node : first_node.max(second_node),
+                square_distance : edge.square_distance,
+                size : components.size(merged_root),
+                weight
+            });
+        }
+        Dendrogram { leaf_ids : self.point_ids.clone(), merges }
+    }
+}
+
+// ........................... Dendrogram ..........................................
+
+/// A single merge of two clusters in a `Dendrogram`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DendrogramMerge {
+    /// Lower numbered of the two nodes merged.
+    pub first_node : usize,
+
+    /// Higher numbered of the two nodes merged.
+    pub second_node : usize,
+
+    /// Square of the single-link distance between the two clusters when they were merged.
+    pub square_distance : u64,
+
+    /// Number of points in the merged cluster.
+    pub size : usize,
+
+    /// Total weight of the points in the merged cluster, which equals `size` unless the points are weighted.
+    pub weight : u64
+}
+
+/// The full hierarchy of merges performed by single-link agglomerative clustering, in order of ascending distance.
+///
+/// Nodes are numbered the same way as a SciPy linkage matrix:
+///
+///   - Nodes zero to N - 1 are the leaves, one per point, with the point id given by `get_leaf_ids`.
+///   - Node N + i is the cluster formed by the ith merge.
+#[derive(Clone, Debug)]
+pub struct Dendrogram {
+    /// Point id for each leaf node.
+    leaf_ids : Vec<usize>,
+
+    /// Merges in order of ascending distance.
+    merges : Vec<DendrogramMerge>
+}
+
+impl Dendrogram {
+    /// Point ids of the leaf nodes.
+    pub fn get_leaf_ids(&self) -> &[usize] { &self.leaf_ids }
+
+    /// The merges, in order of ascending distance.
+    pub fn get_merges(&self) -> &[DendrogramMerge] { &self.merges }
+
+    /// Cut the tree at the given linkage distance, applying only those merges whose square distance does not exceed it.
+    pub fn cut(&self, linkage_square_distance : u64) -> Clustering<usize,usize,Range<usize>> {
+        let merge_count = self.merges.iter().take_while(|merge| merge.square_distance <= linkage_square_distance).count();
+        self.cut_after(merge_count)
+    }
+
+    /// Cut the tree after the given number of merges, yielding N - `merge_count` clusters.
+    pub fn cut_after(&self, merge_count : usize) -> Clustering<usize,usize,Range<usize>> {
+        let mut clustering = integer_clustering();
+        for id in self.leaf_ids.iter() {
+            clustering.add_to_new_cluster(*id).unwrap_or_else(|_| panic!("Point id {} is not unique", id));
+        }
+        // Any leaf beneath a node will do as a representative, since merges are transitive.
+        let mut representative : Vec<usize> = self.leaf_ids.clone();
+        for merge in self.merges.iter().take(merge_count) {
+            let first = representative[merge.first_node];
+            let second = representative[merge.second_node];
+            clustering.merge(first, second);
+            representative.push(first);
+        }
+        clustering
+    }
+}
+
+// ........................... DisjointSet ..........................................
+
+/// Union-Find structure with path compression and union by size.
+#[derive(Clone, Debug)]
+pub(crate) struct DisjointSet {
+    parent : Vec<usize>,
+    sizes : Vec<usize>
+}
+
+impl DisjointSet {
+    pub(crate) fn new(n : usize) -> Self {
+        DisjointSet { parent : (0..n).collect(), sizes : vec![1; n] }
+    }
+
+    /// Find the root of the set holding the given element.
+    pub(crate) fn find(&mut self, element : usize) -> usize {
+        let mut root = element;
+        while self.parent[root] != root { root = self.parent[root]; }
+        let mut current = element;
+        while self.parent[current] != root {
+            let next = self.parent[current];
+            self.parent[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    /// Join the sets holding the two elements.
+    ///
+    ///   - returns true if they were in different sets, false if they were already joined.
+    pub(crate) fn union(&mut self, first : usize, second : usize) -> bool {
+        let (first_root, second_root) = (self.find(first), self.find(second));
+        if first_root == second_root { return false; }
+        let (big, small) = if self.sizes[first_root] >= self.sizes[second_root] { (first_root, second_root) } else { (second_root, first_root) };
+        self.parent[small] = big;
+        self.sizes[big] += self.sizes[small];
+        true
+    }
+
+    /// Number of elements in the set whose root is given.
+    pub(crate) fn size(&self, root : usize) -> usize { self.sizes[root] }
+}
+
+#[cfg(test)]
+/// Tests of the MinimumSpanningTree and Dendrogram methods.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use hilbert::Point;
+    use super::MinimumSpanningTree;
+
+    /// Two tight groups of three points on a line, and a lone point far away.
+    fn line_points() -> Vec<Point> {
+        [0_u32, 1, 3, 20, 21, 23, 100].iter().enumerate()
+            .map(|(id, x)| Point::new(id * 10, &[*x, 0]))
+            .collect()
+    }
+
+    #[test]
+    fn exact_tree_edges() {
+        let tree = MinimumSpanningTree::exact(&line_points());
+        let square_distances : Vec<u64> = tree.get_edges().iter().map(|e| e.square_distance).collect();
+        asserting("Edges sorted ascending").that(&square_distances).is_equal_to(vec![1, 1, 4, 4, 289, 5929]);
+    }
+
+    #[test]
+    fn approximate_matches_exact_on_a_line() {
+        let exact = MinimumSpanningTree::exact(&line_points());
+        let approximate = MinimumSpanningTree::approximate(&line_points(), 7, 2);
+        let exact_total : u64 = exact.get_edges().iter().map(|e| e.square_distance).sum();
+        let approximate_total : u64 = approximate.get_edges().iter().map(|e| e.square_distance).sum();
+        asserting("Same total length").that(&approximate_total).is_equal_to(exact_total);
+    }
+
+    #[test]
+    fn clustering_at_distance() {
+        let tree = MinimumSpanningTree::exact(&line_points());
+        let clustering = tree.clustering(4);
+        asserting("Cluster count").that(&clustering.cluster_count()).is_equal_to(3);
+        asserting("Together").that(&clustering.are_together(30, 50)).is_equal_to(true);
+        asserting("Apart").that(&clustering.are_together(20, 30)).is_equal_to(false);
+    }
+
+    #[test]
+    fn dendrogram_cut_matches_clustering() {
+        let tree = MinimumSpanningTree::exact(&line_points());
+        let dendrogram = tree.dendrogram();
+        let merges = dendrogram.get_merges();
+        asserting("Merge count").that(&merges.len()).is_equal_to(6);
+        asserting("Final merge holds everything").that(&merges[5].size).is_equal_to(7);
+        asserting("Final merge joins the lone point to the rest").that(&(merges[5].first_node, merges[5].second_node)).is_equal_to((6, 11));
+        for linkage_square_distance in &[0_u64, 1, 4, 289, 10_000] {
+            let from_tree = tree.clustering(*linkage_square_distance);
+            let from_dendrogram = dendrogram.cut(*linkage_square_distance);
+            asserting(&format!("Cluster count at {}", linkage_square_distance))
+                .that(&from_dendrogram.cluster_count())
+                .is_equal_to(from_tree.cluster_count());
+        }
+    }
+}
@@ -0,0 +1,389 @@
+//! Out-of-core linkage analysis, for data sets with more points than fit in memory.
+//!
+//! `SingleLinkage::find` needs every `Point` in one `Vec`. `StreamingLinkage` instead reads the points
+//! a chunk at a time, sorts each chunk in Hilbert order and writes it to disk as a sorted run,
+//! then merges the runs. While merging, it measures the distance between each point and the one before it,
+//! adds that distance to the `DistanceHistogram` used by `SingleLinkage` for its binning analysis and writes it to disk.
+//! Once the linkage distance is known, a second pass over the distances estimates the cluster counts.
+//!
+//! Only one chunk of points, one point per run being merged and the histogram (whose size does not depend on the number of points)
+//! are held in memory at once. To stay within limits on open files, no more than `merge_fan_in` runs are merged at a time;
+//! when there are more, they are first merged in groups into longer runs, over as many rounds as needed.
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use hilbert::Point;
+use super::single_linkage::{SingleLinkage, LinkageResult, AdjacentPairDistance, saturating_square_distance};
+use super::histogram::DistanceHistogram;
+
+/// Default number of points to sort in memory at a time.
+pub const DEFAULT_CHUNK_SIZE : usize = 100_000;
+
+/// Default largest number of runs merged at once, each of which holds a file open.
+pub const DEFAULT_MERGE_FAN_IN : usize = 64;
+
+/// Finds the linkage distance for points supplied as a stream, using temporary files to sort them in Hilbert order.
+///
+/// The result matches what `SingleLinkage::find` yields for the same points (in the same order)
+/// when it sorts by Hilbert curve and finds the linkage distance by binning,
+/// since the merge keeps points with identical Hilbert indices in their original order.
+/// The settings of `SingleLinkage` for sorting distances completely, permutations and sampling are ignored.
+pub struct StreamingLinkage {
+    /// Settings for the analysis. Create it using the expected number of points.
+    finder : SingleLinkage,
+
+    /// Number of points to read, sort and write to disk as a single run.
+    chunk_size : usize,
+
+    /// Largest number of runs to merge at once. At least two.
+    merge_fan_in : usize,
+
+    /// A temporary directory for the runs and distances is created inside this directory and deleted afterwards.
+    work_directory : PathBuf
+}
+
+impl StreamingLinkage {
+    /// Create a `StreamingLinkage` that writes its temporary files under the given directory.
+    ///
+    ///   - finder - Settings for the analysis, such as `bits_per_dimension` and `noise_skip_by`.
+    ///   - work_directory - Directory that will hold the temporary files. It needs room for roughly
+    ///     twice the size of the points plus 32 bytes per point.
+    pub fn new(finder : SingleLinkage, work_directory : &Path) -> Self {
+        StreamingLinkage {
+            finder,
+            chunk_size : DEFAULT_CHUNK_SIZE,
+            merge_fan_in : DEFAULT_MERGE_FAN_IN,
+            work_directory : work_directory.to_path_buf()
+        }
+    }
+
+    /// Configure how many points are sorted in memory at a time. Larger chunks mean fewer runs to merge.
+    pub fn with_chunk_size(mut self, chunk_size : usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Configure how many runs are merged at once, which bounds the number of files held open. The minimum is two.
+    pub fn with_merge_fan_in(mut self, merge_fan_in : usize) -> Self {
+        self.merge_fan_in = merge_fan_in.max(2);
+        self
+    }
+
+    /// Find the characteristic clustering distance, the linkage distance, along with the estimated cluster counts.
+    ///
+    ///   - points - Points to analyze. They must all have the same number of dimensions.
+    ///   - returns - The `LinkageResult`, or the first error encountered while reading or writing the temporary files.
+    ///     If there are no points, the result is empty.
+    pub fn find<I>(&self, points : I) -> io::Result<LinkageResult>
+    where I : IntoIterator<Item = Point>
+    {
+        let temporary_directory = tempfile::tempdir_in(&self.work_directory)?;
+        let mut run_paths = self.write_sorted_runs(points, temporary_directory.path())?;
+        if run_paths.is_empty() { return Ok(LinkageResult::new()); }
+        let mut round = 0;
+        while run_paths.len() > self.merge_fan_in {
+            run_paths = self.merge_round(&run_paths, temporary_directory.path(), round)?;
+            round += 1;
+        }
+        let distances_path = temporary_directory.path().join("distances.bin");
+        let (point_count, dimensions, histogram) = self.merge_runs(&run_paths, &distances_path)?;
+        for run_path in run_paths.iter() {
+            std::fs::remove_file(run_path)?;
+        }
+        let linkage_square_distance = self.finder.find_in_histogram(point_count, dimensions, &histogram);
+
+        let mut distances = PairReader::open(&distances_path)?;
+        let mut linkage = self.finder.estimate_cluster_counts(&mut distances, linkage_square_distance);
+        if let Some(error) = distances.error.take() {
+            return Err(error);
+        }
+        linkage.permutation_count = 1;
+        linkage.lowest_permuted_square_distance = linkage_square_distance;
+        linkage.highest_permuted_square_distance = linkage_square_distance;
+        Ok(linkage)
+    }
+
+    /// Read the points a chunk at a time, sort each chunk in Hilbert order and write it to its own file.
+    ///
+    ///   - returns - The paths to the sorted runs, in the order that the chunks were read.
+    fn write_sorted_runs<I>(&self, points : I, directory : &Path) -> io::Result<Vec<PathBuf>>
+    where I : IntoIterator<Item = Point>
+    {
+        let bits_per_dimension = self.finder.get_bits_per_dimension() as usize;
+        let mut run_paths = Vec::new();
+        let mut chunk : Vec<KeyedPoint> = Vec::with_capacity(self.chunk_size);
+        let mut points = points.into_iter().enumerate().peekable();
+        while points.peek().is_some() {
+            chunk.clear();
+            chunk.extend(points.by_ref().take(self.chunk_size).map(|(position, point)| KeyedPoint {
+                key : point.hilbert_transform(bits_per_dimension).to_bytes_be(),
+                position : position as u64,
+                point
+            }));
+            chunk.sort_unstable();
+            let run_path = directory.join(format!("run-{}.bin", run_paths.len()));
+            let mut writer = BufWriter::new(File::create(&run_path)?);
+            for keyed_point in chunk.iter() {
+                keyed_point.write(&mut writer)?;
+            }
+            writer.flush()?;
+            run_paths.push(run_path);
+        }
+        Ok(run_paths)
+    }
+
+    /// Merge the runs in groups of up to `merge_fan_in` into fewer, longer runs, deleting the merged runs.
+    ///
+    ///   - round - Number of earlier rounds, used to give the new runs distinct names.
+    ///   - returns - The paths to the new runs, in the same order as the groups they came from.
+    fn merge_round(&self, run_paths : &[PathBuf], directory : &Path, round : usize) -> io::Result<Vec<PathBuf>> {
+        let mut merged_paths = Vec::with_capacity(run_paths.len() / self.merge_fan_in + 1);
+        for group in run_paths.chunks(self.merge_fan_in) {
+            let merged_path = directory.join(format!("run-{}-{}.bin", round + 1, merged_paths.len()));
+            let mut writer = BufWriter::new(File::create(&merged_path)?);
+            merge_sorted_runs(group, |keyed_point| keyed_point.write(&mut writer))?;
+            writer.flush()?;
+            for run_path in group.iter() {
+                std::fs::remove_file(run_path)?;
+            }
+            merged_paths.push(merged_path);
+        }
+        Ok(merged_paths)
+    }
+
+    /// Merge the sorted runs into one Hilbert ordered sequence, counting the square distance between each pair of
+    /// consecutive points in a histogram and writing the pairs to a file.
+    ///
+    ///   - returns - The number of points, their number of dimensions and the filled histogram.
+    fn merge_runs(&self, run_paths : &[PathBuf], distances_path : &Path) -> io::Result<(usize, usize, DistanceHistogram)> {
+        let mut writer = BufWriter::new(File::create(distances_path)?);
+        let mut histogram = DistanceHistogram::default();
+        let mut dimensions = 1;
+        let mut previous_point : Option<Point> = None;
+        let mut point_count = 0_usize;
+        merge_sorted_runs(run_paths, |keyed_point| {
+            let point = keyed_point.point;
+            match previous_point {
+                Some(ref previous) => {
+                    let pair = AdjacentPairDistance {
+                        square_distance : saturating_square_distance(previous, &point),
+                        first_index : point_count - 1,
+                        second_index : point_count,
+                        first_id : previous.get_id(),
+                        second_id : point.get_id()
+                    };
+                    histogram.add(pair.square_distance);
+                    write_pair(&mut writer, &pair)?;
+                },
+                None => { dimensions = point.dimensions(); }
+            }
+            previous_point = Some(point);
+            point_count += 1;
+            Ok(())
+        })?;
+        writer.flush()?;
+        Ok((point_count, dimensions, histogram))
+    }
+}
+
+/// Merge sorted runs, passing each point to `visit` in Hilbert order. 
+/// Points with the same Hilbert index are visited in their original order in the stream.
+fn merge_sorted_runs<F>(run_paths : &[PathBuf], mut visit : F) -> io::Result<()>
+where F : FnMut(KeyedPoint) -> io::Result<()>
+{
+    let mut readers = Vec::with_capacity(run_paths.len());
+    let mut heap = BinaryHeap::with_capacity(run_paths.len());
+    for (i_run, run_path) in run_paths.iter().enumerate() {
+        let mut reader = BufReader::new(File::open(run_path)?);
+        if let Some(keyed_point) = KeyedPoint::read(&mut reader)? {
+            heap.push(Reverse(MergeEntry { keyed_point, i_run }));
+        }
+        readers.push(reader);
+    }
+    while let Some(Reverse(MergeEntry { keyed_point, i_run })) = heap.pop() {
+        if let Some(next_keyed_point) = KeyedPoint::read(&mut readers[i_run])? {
+            heap.push(Reverse(MergeEntry { keyed_point : next_keyed_point, i_run }));
+        }
+        visit(keyed_point)?;
+    }
+    Ok(())
+}
+
+// ........................... KeyedPoint ..........................................
+
+/// A `Point` together with its Hilbert index and its position in the original stream, which breaks ties
+/// between points with the same Hilbert index.
+struct KeyedPoint {
+    /// Hilbert index as big-endian bytes, without leading zeroes.
+    key : Vec<u8>,
+
+    /// Zero-based position of the point in the stream.
+    position : u64,
+
+    point : Point
+}
+
+impl KeyedPoint {
+    /// Write the point to a run file.
+    ///
+    /// Layout: key length (u32), key bytes, position (u64), id (u64), dimensions (u32), coordinates (u32 each), all little-endian.
+    fn write<W : Write>(&self, writer : &mut W) -> io::Result<()> {
+        writer.write_all(&(self.key.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.key)?;
+        writer.write_all(&self.position.to_le_bytes())?;
+        writer.write_all(&(self.point.get_id() as u64).to_le_bytes())?;
+        let coordinates = self.point.get_coordinates();
+        writer.write_all(&(coordinates.len() as u32).to_le_bytes())?;
+        for coordinate in coordinates.iter() {
+            writer.write_all(&coordinate.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read the next point from a run file, or `None` at the end of the file.
+    fn read<R : Read>(reader : &mut R) -> io::Result<Option<Self>> {
+        let key_length = match read_u32(reader) {
+            Ok(key_length) => key_length as usize,
+            Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error)
+        };
+        let mut key = vec![0_u8; key_length];
+        reader.read_exact(&mut key)?;
+        let position = read_u64(reader)?;
+        let id = read_u64(reader)? as usize;
+        let dimensions = read_u32(reader)? as usize;
+        let mut coordinates = Vec::with_capacity(dimensions);
+        for _ in 0..dimensions {
+            coordinates.push(read_u32(reader)?);
+        }
+        Ok(Some(KeyedPoint { key, position, point : Point::new(id, &coordinates) }))
+    }
+}
+
+impl Ord for KeyedPoint {
+    /// Order by Hilbert index, then by position in the stream.
+    ///
+    /// The keys have no leading zeroes, so a shorter key is a smaller number.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.len().cmp(&other.key.len())
+            .then_with(|| self.key.cmp(&other.key))
+            .then_with(|| self.position.cmp(&other.position))
+    }
+}
+
+impl PartialOrd for KeyedPoint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for KeyedPoint {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for KeyedPoint {}
+
+/// The next point from one of the runs being merged.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct MergeEntry {
+    keyed_point : KeyedPoint,
+    i_run : usize
+}
+
+// ........................... PairReader ..........................................
+
+/// Write a pair of consecutive points to the distances file.
+///
+/// Layout: square distance, index of the first point, id of the first point, id of the second point (u64 each), all little-endian.
+fn write_pair<W : Write>(writer : &mut W, pair : &AdjacentPairDistance) -> io::Result<()> {
+    writer.write_all(&pair.square_distance.to_le_bytes())?;
+    writer.write_all(&(pair.first_index as u64).to_le_bytes())?;
+    writer.write_all(&(pair.first_id as u64).to_le_bytes())?;
+    writer.write_all(&(pair.second_id as u64).to_le_bytes())?;
+    Ok(())
+}
+
+/// Iterates over the pairs in the distances file.
+///
+/// Iteration stops at the first error, which is kept so that the caller can report it.
+struct PairReader {
+    reader : BufReader<File>,
+    error : Option<io::Error>
+}
+
+impl PairReader {
+    fn open(path : &Path) -> io::Result<Self> {
+        Ok(PairReader { reader : BufReader::new(File::open(path)?), error : None })
+    }
+
+    fn read_pair(&mut self) -> io::Result<AdjacentPairDistance> {
+        let square_distance = read_u64(&mut self.reader)?;
+        let first_index = read_u64(&mut self.reader)? as usize;
+        let first_id = read_u64(&mut self.reader)? as usize;
+        let second_id = read_u64(&mut self.reader)? as usize;
+        Ok(AdjacentPairDistance { square_distance, first_index, second_index : first_index + 1, first_id, second_id })
+    }
+}
+
+impl Iterator for PairReader {
+    type Item = AdjacentPairDistance;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() { return None; }
+        match self.read_pair() {
+            Ok(pair) => Some(pair),
+            Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => None,
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
+    }
+}
+
+fn read_u32<R : Read>(reader : &mut R) -> io::Result<u32> {
+    let mut bytes = [0_u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R : Read>(reader : &mut R) -> io::Result<u64> {
+    let mut bytes = [0_u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+/// Tests of the run file format and merge order.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use hilbert::Point;
+    use super::KeyedPoint;
+
+    #[test]
+    fn keyed_point_round_trip() {
+        let original = KeyedPoint { key : vec![1, 255, 7], position : 42, point : Point::new(17, &[3, 1_000_000, 0]) };
+        let mut bytes = Vec::new();
+        original.write(&mut bytes).unwrap();
+        let mut reader = &bytes[..];
+        let copy = KeyedPoint::read(&mut reader).unwrap().unwrap();
+        asserting("Same key").that(&copy.key).is_equal_to(original.key.clone());
+        asserting("Same position").that(&copy.position).is_equal_to(42);
+        asserting("Same id").that(&copy.point.get_id()).is_equal_to(17);
+        asserting("Same coordinates").that(copy.point.get_coordinates()).is_equal_to(original.point.get_coordinates());
+        asserting("End of file").that(&KeyedPoint::read(&mut reader).unwrap().is_none()).is_equal_to(true);
+    }
+
+    #[test]
+    fn keyed_point_order() {
+        let keyed = |key : Vec<u8>, position : u64| KeyedPoint { key, position, point : Point::new(0, &[0]) };
+        asserting("Shorter key is smaller").that(&(keyed(vec![255], 0) < keyed(vec![1, 0], 0))).is_equal_to(true);
+        asserting("Same length compares bytes").that(&(keyed(vec![1, 2], 5) < keyed(vec![1, 3], 0))).is_equal_to(true);
+        asserting("Ties broken by position").that(&(keyed(vec![9], 1) < keyed(vec![9], 2))).is_equal_to(true);
+    }
+}
@@ -0,0 +1,200 @@
+use std::cmp::max;
+use hilbert::Point;
+use super::single_linkage::{SingleLinkage, LinkageResult, AdjacentPairDistance};
+use super::spanning_tree::{MinimumSpanningTree, DisjointSet};
+
+// ........................... ClusterCounting ..........................................
+
+/// How `SingleLinkage::find_for_cluster_count` counts the clusters formed at a given linkage distance.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClusterCounting {
+    /// Count the runs of points in Hilbert order that exceed `outlier_cluster_size`, as in the `large_cluster_count`
+    /// of a `LinkageResult`. This is fast, but runs split some clusters, so it overstates the true count.
+    HilbertRuns,
+
+    /// Count the single-link clusters that exceed `outlier_cluster_size`, by cutting the minimum spanning tree
+    /// from `MinimumSpanningTree::new`. This is the actual result of clustering, but building the tree
+    /// is quadratic for up to `EXACT_POINT_LIMIT` points.
+    SpanningTree
+}
+
+// ........................... ClusterCountResult ..........................................
+
+/// The linkage distance chosen to yield a requested number of clusters, and how close it came.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ClusterCountResult {
+    /// Number of clusters requested.
+    pub target_cluster_count : u32,
+
+    /// Number of clusters formed at the chosen `linkage_square_distance`, counted as directed by `counting`.
+    /// Outliers are not counted.
+    pub achieved_cluster_count : u32,
+
+    /// Number of clusters no larger than `outlier_cluster_size`, counted as directed by `counting`.
+    pub outlier_cluster_count : u32,
+
+    /// How the clusters were counted.
+    pub counting : ClusterCounting,
+
+    /// Linkage distance and estimated counts from the Hilbert runs, as `find` would report them for this distance.
+    pub linkage : LinkageResult
+}
+
+impl SingleLinkage {
+    /// Find the linkage distance that yields the number of clusters closest to `cluster_count`,
+    /// for when the number of clusters is fixed in advance instead of discovered.
+    ///
+    /// Every distinct distance between linked points is a candidate. Raising the linkage distance from one candidate
+    /// to the next merges clusters, and a running tally of the clusters larger than `outlier_cluster_size` gives
+    /// the count at every candidate in a single O(N Log N) pass. The count need not fall steadily, because outliers
+    /// that merge can grow into a cluster. Of the candidates whose count is closest to the target,
+    /// the largest is chosen, linking as many points as possible without changing the count.
+    ///
+    /// The `noise_skip_by`, `lowest_index_for_checking_growth_ratio` and `minimum_cluster_count` settings play no part.
+    ///
+    ///   - points - Points to analyze. As a side effect, these points will be sorted in Hilbert Curve order
+    ///     if `need_to_sort_by_hilbert_curve` is true.
+    ///   - cluster_count - Number of clusters desired, not counting outliers.
+    ///   - counting - Whether to count Hilbert runs or actual single-link clusters.
+    pub fn find_for_cluster_count(&self, points : &mut Vec<Point>, cluster_count : u32, counting : ClusterCounting) -> ClusterCountResult {
+        if self.get_need_to_sort_by_hilbert_curve() {
+            self.sort_by_hilbert_curve(points);
+        }
+        let distances = AdjacentPairDistance::all_pairs(points);
+        let outlier_cluster_size = self.get_outlier_cluster_size() as usize;
+        let (linkage_square_distance, achieved_cluster_count, outlier_cluster_count) = match counting {
+            ClusterCounting::HilbertRuns => {
+                let mut edges = distances.clone();
+                edges.sort();
+                closest_to_cluster_count(points.len(), &edges, outlier_cluster_size, cluster_count)
+            },
+            ClusterCounting::SpanningTree => {
+                let tree = MinimumSpanningTree::new(points, self.get_bits_per_dimension());
+                closest_to_cluster_count(points.len(), tree.get_edges(), outlier_cluster_size, cluster_count)
+            }
+        };
+        let mut linkage = self.estimate_cluster_counts(&distances, linkage_square_distance);
+        linkage.permutation_count = 1;
+        linkage.lowest_permuted_square_distance = linkage_square_distance;
+        linkage.highest_permuted_square_distance = linkage_square_distance;
+        ClusterCountResult {
+            target_cluster_count : cluster_count,
+            achieved_cluster_count,
+            outlier_cluster_count,
+            counting,
+            linkage
+        }
+    }
+}
+
+/// Link the points along the edges in ascending order of distance, tallying the clusters larger than `outlier_cluster_size`
+/// after all the edges of each distinct distance are linked, and keep the distance whose tally is closest to the target.
+///
+///   - point_count - Number of points, which the edges reference by index.
+///   - sorted_edges - Edges sorted by ascending square distance.
+///   - returns - The chosen square distance (at least one), the number of large clusters and the number of outlier clusters.
+fn closest_to_cluster_count(point_count : usize, sorted_edges : &[AdjacentPairDistance], outlier_cluster_size : usize, target : u32) -> (u64, u32, u32) {
+    let is_large = |size : usize| (size > outlier_cluster_size) as u32;
+    let mut components = DisjointSet::new(point_count);
+    let mut large_count = point_count as u32 * is_large(1);
+    let mut cluster_count = point_count as u32;
+    let miss = |count : u32| (count as i64 - target as i64).abs();
+
+    // A linkage distance must be positive, so the points as they stand before linking (or after linking only coincident points)
+    // are reported at a distance of one. That is only a fair candidate if no points lie one apart, since a distance of one would link them.
+    let has_unit_edges = sorted_edges.iter().map(|edge| edge.square_distance).find(|square_distance| *square_distance > 0) == Some(1);
+    let mut best = if has_unit_edges { None } else { Some((1, large_count, cluster_count - large_count)) };
+    let mut position = 0;
+    while position < sorted_edges.len() {
+        let square_distance = sorted_edges[position].square_distance;
+        while position < sorted_edges.len() && sorted_edges[position].square_distance == square_distance {
+            let edge = &sorted_edges[position];
+            let (first_root, second_root) = (components.find(edge.first_index), components.find(edge.second_index));
+            if first_root != second_root {
+                let (first_size, second_size) = (components.size(first_root), components.size(second_root));
+                components.union(first_root, second_root);
+                large_count = large_count + is_large(first_size + second_size) - is_large(first_size) - is_large(second_size);
+                cluster_count -= 1;
+            }
+            position += 1;
+        }
+        let is_closer = match best {
+            Some((_, best_large_count, _)) => miss(large_count) <= miss(best_large_count),
+            None => true
+        };
+        if (square_distance == 0 && !has_unit_edges) || (square_distance > 0 && is_closer) {
+            best = Some((max(square_distance, 1), large_count, cluster_count - large_count));
+        }
+    }
+    best.unwrap_or((1, large_count, cluster_count - large_count))
+}
+
+#[cfg(test)]
+/// Tests of searching for a linkage distance by cluster count.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use hilbert::Point;
+    use crate::clustering::single_linkage::SingleLinkage;
+    use super::ClusterCounting;
+
+    /// Groups of four points on a line spaced one apart, with the gaps between groups growing from 10 to 50, and two lone points farther away,
+    /// spaced so that each joins the groups before joining the other.
+    fn widening_groups() -> Vec<Point> {
+        let mut xs = Vec::new();
+        let mut start = 0;
+        for group in 0..6_u32 {
+            for offset in 0..4 { xs.push(start + offset); }
+            start += 3 + 10 * (group + 1);
+        }
+        xs.push(5000);
+        xs.push(12000);
+        xs.iter().enumerate().map(|(id, x)| Point::new(id, &[*x])).collect()
+    }
+
+    #[test]
+    fn find_for_cluster_count() {
+        let finder = SingleLinkage::new(26, 14).with_need_to_sort_by_hilbert_curve().with_outlier_cluster_size(1);
+        for counting in [ClusterCounting::HilbertRuns, ClusterCounting::SpanningTree].iter() {
+            for target in 1..=6_u32 {
+                let mut points = widening_groups();
+                let result = finder.find_for_cluster_count(&mut points, target, *counting);
+                asserting(&format!("{:?} target {}", counting, target)).that(&result.achieved_cluster_count).is_equal_to(target);
+                // Once all the groups have merged, the largest distance giving one cluster absorbs the lone points too.
+                let expected_outliers = if target == 1 { 0 } else { 2 };
+                asserting(&format!("{:?} target {} lone points are outliers", counting, target)).that(&result.outlier_cluster_count).is_equal_to(expected_outliers);
+            }
+        }
+        let mut points = widening_groups();
+        let result = finder.find_for_cluster_count(&mut points, 4, ClusterCounting::HilbertRuns);
+        asserting("Largest distance giving four clusters").that(&result.linkage.linkage_square_distance).is_equal_to(20 * 20);
+        asserting("Hilbert run count agrees").that(&result.linkage.large_cluster_count).is_equal_to(4);
+    }
+
+    #[test]
+    fn closest_count_when_target_unreachable() {
+        let finder = SingleLinkage::new(26, 14).with_need_to_sort_by_hilbert_curve().with_outlier_cluster_size(4);
+        let mut points = widening_groups();
+        let result = finder.find_for_cluster_count(&mut points, 20, ClusterCounting::SpanningTree);
+        asserting("Groups of four are outliers until they merge into one chain").that(&result.achieved_cluster_count).is_equal_to(1);
+    }
+
+    /// Asking for at least as many clusters as points is best met before linking anything,
+    /// which must still be reported as a positive linkage distance.
+    #[test]
+    fn target_at_least_point_count() {
+        let finder = SingleLinkage::new(200, 12).with_outlier_cluster_size(0);
+        for counting in [ClusterCounting::HilbertRuns, ClusterCounting::SpanningTree].iter() {
+            let mut points : Vec<Point> = (0..200_u32).map(|i| Point::new(i as usize, &[(i % 20) * 10, (i / 20) * 10])).collect();
+            let result = finder.find_for_cluster_count(&mut points, 200, *counting);
+            asserting(&format!("{:?} every point its own cluster", counting)).that(&result.achieved_cluster_count).is_equal_to(200);
+            asserting(&format!("{:?} positive distance", counting)).that(&result.linkage.linkage_square_distance).is_equal_to(1);
+
+            // Points one apart are linked at any positive distance, so the groups of four are the closest achievable.
+            let mut points = widening_groups();
+            let result = finder.find_for_cluster_count(&mut points, 30, *counting);
+            asserting(&format!("{:?} groups of four linked", counting)).that(&result.achieved_cluster_count).is_equal_to(8);
+            asserting(&format!("{:?} distance links neighbors", counting)).that(&result.linkage.linkage_square_distance).is_equal_to(1);
+        }
+    }
+}
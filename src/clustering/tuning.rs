@@ -0,0 +1,434 @@
+use std::ops::Range;
+use std::fmt::{Display, Formatter};
+use hilbert::Point;
+use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
+use super::Clustering;
+use super::bcubed::BCubed;
+use super::single_linkage::{SingleLinkage, AdjacentPairDistance};
+use super::spanning_tree::MinimumSpanningTree;
+
+/// Score a solution against a gold standard, where higher is better.
+pub type Metric = fn(&Clustering<usize,usize,Range<usize>>, &Clustering<usize,usize,Range<usize>>) -> f64;
+
+/// The default `Metric`: the B-Cubed similarity, weighting precision and recall equally.
+pub fn bcubed_similarity(solution : &Clustering<usize,usize,Range<usize>>, gold_standard : &Clustering<usize,usize,Range<usize>>) -> f64 {
+    BCubed::compare(solution, gold_standard, 0.5).similarity()
+}
+
+// ........................... LabelledDataset ..........................................
+
+/// Points together with the gold standard `Clustering` of their ids, used to judge trial configurations.
+pub struct LabelledDataset {
+    /// Name used to identify the data set in reports.
+    pub name : String,
+
+    /// Points to cluster.
+    pub points : Vec<Point>,
+
+    /// Correct clustering of the point ids.
+    pub gold_standard : Clustering<usize,usize,Range<usize>>,
+
+    /// Number of bits needed to encode the largest coordinate value.
+    pub bits_per_dimension : u8
+}
+
+impl LabelledDataset {
+    /// Create a `LabelledDataset`.
+    pub fn new(name : &str, points : Vec<Point>, gold_standard : Clustering<usize,usize,Range<usize>>, bits_per_dimension : u8) -> Self {
+        LabelledDataset { name : name.to_string(), points, gold_standard, bits_per_dimension }
+    }
+}
+
+// ........................... ParameterSpace ..........................................
+
+/// Candidate values for each `SingleLinkage` parameter that the `Tuner` varies.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterSpace {
+    pub noise_skip_by : Vec<u16>,
+    pub minimum_cluster_count : Vec<u16>,
+    pub outlier_cluster_size : Vec<u16>,
+    pub sort_distances_completely : Vec<bool>
+}
+
+impl Default for ParameterSpace {
+    /// A grid of 72 combinations, spanning the values that have worked well in practice.
+    fn default() -> Self {
+        ParameterSpace {
+            noise_skip_by : vec![1, 3, 5, 7, 9, 12],
+            minimum_cluster_count : vec![10, 20, 35],
+            outlier_cluster_size : vec![0, 10],
+            sort_distances_completely : vec![true, false]
+        }
+    }
+}
+
+impl ParameterSpace {
+    /// Every combination of the candidate values.
+    fn grid(&self) -> Vec<TrialSettings> {
+        let mut settings = Vec::new();
+        for noise_skip_by in self.noise_skip_by.iter() {
+            for minimum_cluster_count in self.minimum_cluster_count.iter() {
+                for outlier_cluster_size in self.outlier_cluster_size.iter() {
+                    for sort_distances_completely in self.sort_distances_completely.iter() {
+                        settings.push(TrialSettings {
+                            noise_skip_by : *noise_skip_by,
+                            minimum_cluster_count : *minimum_cluster_count,
+                            outlier_cluster_size : *outlier_cluster_size,
+                            sort_distances_completely : *sort_distances_completely
+                        });
+                    }
+                }
+            }
+        }
+        settings
+    }
+
+    /// Combinations formed by choosing each value at random from its candidates.
+    fn random(&self, trial_count : usize, seed : u64) -> Vec<TrialSettings> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..trial_count)
+            .map(|_| TrialSettings {
+                noise_skip_by : *self.noise_skip_by.choose(&mut rng).unwrap(),
+                minimum_cluster_count : *self.minimum_cluster_count.choose(&mut rng).unwrap(),
+                outlier_cluster_size : *self.outlier_cluster_size.choose(&mut rng).unwrap(),
+                sort_distances_completely : *self.sort_distances_completely.choose(&mut rng).unwrap()
+            })
+            .collect()
+    }
+}
+
+// ........................... Trials ..........................................
+
+/// One combination of parameter values tried by the `Tuner`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TrialSettings {
+    pub noise_skip_by : u16,
+    pub minimum_cluster_count : u16,
+    pub outlier_cluster_size : u16,
+    pub sort_distances_completely : bool
+}
+
+impl TrialSettings {
+    /// Create a `SingleLinkage` with these settings that sorts the points by Hilbert curve.
+    pub fn configure(&self, num_points : u32, bits_per_dimension : u8) -> SingleLinkage {
+        let finder = SingleLinkage::new(num_points, bits_per_dimension)
+            .with_need_to_sort_by_hilbert_curve()
+            .with_noise_skip_by(self.noise_skip_by)
+            .with_minimum_cluster_count(self.minimum_cluster_count)
+            .with_outlier_cluster_size(self.outlier_cluster_size);
+        if self.sort_distances_completely { finder.with_sort_distances_completely() }
+        else { finder.without_sort_distances_completely() }
+    }
+}
+
+/// The outcome of one trial: its settings, and the linkage distance and score obtained for each data set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trial {
+    pub settings : TrialSettings,
+
+    /// `linkage_square_distance` found for each data set, in the order the data sets were given.
+    pub linkage_square_distances : Vec<u64>,
+
+    /// Score for each data set, in the order the data sets were given.
+    pub scores : Vec<f64>
+}
+
+impl Trial {
+    /// Average score over all data sets.
+    pub fn mean_score(&self) -> f64 {
+        self.scores.iter().sum::<f64>() / self.scores.len() as f64
+    }
+
+    /// Average score over all data sets except the one at the given position.
+    fn mean_score_without(&self, excluded : usize) -> f64 {
+        let total : f64 = self.scores.iter().enumerate().filter(|(i, _)| *i != excluded).map(|(_, score)| score).sum();
+        total / (self.scores.len() - 1) as f64
+    }
+}
+
+/// Every trial performed by `Tuner::tune`, and which was best.
+#[derive(Clone, Debug)]
+pub struct TuningResult {
+    /// Names of the data sets, in the order of the scores of each trial.
+    pub dataset_names : Vec<String>,
+
+    /// Trials in the order performed.
+    pub trials : Vec<Trial>,
+
+    /// Position in `trials` of the trial with the highest mean score. The earliest wins a tie.
+    pub best_index : usize
+}
+
+impl TuningResult {
+    /// The trial with the highest mean score.
+    pub fn best(&self) -> &Trial { &self.trials[self.best_index] }
+}
+
+impl Display for TuningResult {
+    /// A table with one row per trial, in the order performed, and a column of scores for each data set.
+    /// The best trial is marked with an asterisk.
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "  # noise_skip_by min_clusters outlier_size sort   mean")?;
+        for name in self.dataset_names.iter() {
+            write!(f, " {:>10}", name)?;
+        }
+        writeln!(f)?;
+        for (i, trial) in self.trials.iter().enumerate() {
+            let settings = trial.settings;
+            write!(f, "{:>3}{} {:>13} {:>12} {:>12} {:<5} {:.4}",
+                i, if i == self.best_index { "*" } else { " " },
+                settings.noise_skip_by, settings.minimum_cluster_count, settings.outlier_cluster_size,
+                settings.sort_distances_completely, trial.mean_score())?;
+            for score in trial.scores.iter() {
+                write!(f, " {:>10.4}", score)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of holding out one data set during cross-validation.
+#[derive(Clone, Debug)]
+pub struct CrossValidationFold {
+    /// Name of the data set held out.
+    pub held_out : String,
+
+    /// The trial with the best mean score on the other data sets.
+    pub trial : Trial,
+
+    /// Mean score of that trial on the other data sets.
+    pub training_score : f64,
+
+    /// Score of that trial on the data set held out.
+    pub held_out_score : f64
+}
+
+// ........................... Tuner ..........................................
+
+/// How a `Tuner` chooses which combinations of parameters to try.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SearchStrategy {
+    /// Try every combination.
+    Grid,
+
+    /// Try the given number of combinations chosen at random, using the seed so that the search is repeatable.
+    Random { trial_count : usize, seed : u64 }
+}
+
+/// How a `Tuner` clusters the points once the linkage distance is found.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TuningClusterer {
+    /// One cluster per run of points in Hilbert order (see `SingleLinkage::seed_clustering`).
+    /// This is fast and, since it splits clusters rather than chaining them together, usually scores better on noisy data.
+    HilbertRuns,
+
+    /// Exact single-link clustering, by cutting the minimum spanning tree at the linkage distance.
+    /// The tree is built once per data set and reused by every trial. A few points in the gaps between
+    /// clusters are enough for this to chain clusters together.
+    SpanningTree
+}
+
+/// Searches for the `SingleLinkage` parameters that best reproduce the gold standard clusterings of one or more labelled data sets.
+///
+/// For each combination of parameters, the tuner finds the linkage distance for each data set, clusters the points at
+/// that distance, gathers all clusters no larger than `outlier_cluster_size` into a single rag bag cluster,
+/// and scores the result against the gold standard using the `Metric` (B-Cubed similarity by default).
+/// The best combination has the highest mean score over all the data sets.
+#[derive(Copy, Clone, Debug)]
+pub struct Tuner {
+    strategy : SearchStrategy,
+    clusterer : TuningClusterer,
+    metric : Metric
+}
+
+impl Default for Tuner {
+    fn default() -> Self { Self::new() }
+}
+
+impl Tuner {
+    /// Create a `Tuner` that performs a grid search, clusters using Hilbert runs and scores using B-Cubed similarity.
+    pub fn new() -> Self {
+        Tuner {
+            strategy : SearchStrategy::Grid,
+            clusterer : TuningClusterer::HilbertRuns,
+            metric : bcubed_similarity
+        }
+    }
+
+    /// Configure how combinations of parameters are chosen.
+    pub fn with_search_strategy(mut self, strategy : SearchStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Configure how the points are clustered once the linkage distance is found.
+    pub fn with_clusterer(mut self, clusterer : TuningClusterer) -> Self {
+        self.clusterer = clusterer;
+        self
+    }
+
+    /// Configure how a clustering is scored against the gold standard. Higher scores must be better.
+    pub fn with_metric(mut self, metric : Metric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// Try the combinations of parameters drawn from the space on every data set.
+    ///
+    ///   - space - Candidate values for each parameter. Every list must have at least one value.
+    ///   - datasets - Labelled data sets. There must be at least one.
+    pub fn tune(&self, space : &ParameterSpace, datasets : &[LabelledDataset]) -> TuningResult {
+        if datasets.is_empty() { panic!("At least one labelled data set is required for tuning."); }
+        let all_settings = match self.strategy {
+            SearchStrategy::Grid => space.grid(),
+            SearchStrategy::Random { trial_count, seed } => space.random(trial_count, seed)
+        };
+        let trees : Vec<Option<MinimumSpanningTree>> = datasets.iter()
+            .map(|dataset| match self.clusterer {
+                TuningClusterer::SpanningTree => Some(MinimumSpanningTree::new(&dataset.points, dataset.bits_per_dimension)),
+                TuningClusterer::HilbertRuns => None
+            })
+            .collect();
+
+        let trials : Vec<Trial> = all_settings.iter()
+            .map(|settings| {
+                let (linkage_square_distances, scores) = datasets.iter()
+                    .zip(trees.iter())
+                    .map(|(dataset, tree)| self.score(settings, dataset, tree.as_ref()))
+                    .unzip();
+                Trial { settings : *settings, linkage_square_distances, scores }
+            })
+            .collect();
+        let best_index = Self::best_by(&trials, |trial| trial.mean_score());
+        TuningResult {
+            dataset_names : datasets.iter().map(|dataset| dataset.name.clone()).collect(),
+            trials,
+            best_index
+        }
+    }
+
+    /// Perform leave-one-out cross-validation: for each data set, choose the trial that scores best on the other
+    /// data sets, then see how well it scores on the one held out.
+    ///
+    /// A held out score far below the training score suggests that the parameters do not generalize.
+    ///
+    ///   - datasets - At least two labelled data sets.
+    pub fn cross_validate(&self, space : &ParameterSpace, datasets : &[LabelledDataset]) -> Vec<CrossValidationFold> {
+        if datasets.len() < 2 { panic!("Cross-validation requires at least two labelled data sets."); }
+        let result = self.tune(space, datasets);
+        (0..datasets.len())
+            .map(|held_out| {
+                let best_index = Self::best_by(&result.trials, |trial| trial.mean_score_without(held_out));
+                let trial = result.trials[best_index].clone();
+                CrossValidationFold {
+                    held_out : datasets[held_out].name.clone(),
+                    training_score : trial.mean_score_without(held_out),
+                    held_out_score : trial.scores[held_out],
+                    trial
+                }
+            })
+            .collect()
+    }
+
+    /// Position of the trial with the highest score, the earliest winning any tie.
+    fn best_by<F : Fn(&Trial) -> f64>(trials : &[Trial], score : F) -> usize {
+        let mut best_index = 0;
+        for (i, trial) in trials.iter().enumerate() {
+            if score(trial) > score(&trials[best_index]) { best_index = i; }
+        }
+        best_index
+    }
+
+    /// Find the linkage distance for one data set using the given settings, cluster and score the result.
+    fn score(&self, settings : &TrialSettings, dataset : &LabelledDataset, tree : Option<&MinimumSpanningTree>) -> (u64, f64) {
+        let mut points = dataset.points.clone();
+        let finder = settings.configure(points.len() as u32, dataset.bits_per_dimension);
+        let linkage_square_distance = finder.find(&mut points).linkage_square_distance;
+        let mut clustering = match tree {
+            Some(tree) => tree.clustering(linkage_square_distance),
+            None => finder.seed_clustering(&AdjacentPairDistance::all_pairs(&points), linkage_square_distance)
+        };
+        gather_outliers(&mut clustering, settings.outlier_cluster_size as usize);
+        (linkage_square_distance, (self.metric)(&clustering, &dataset.gold_standard))
+    }
+}
+
+/// Merge every cluster with no more than `outlier_cluster_size` members into one rag bag cluster.
+fn gather_outliers(clustering : &mut Clustering<usize,usize,Range<usize>>, outlier_cluster_size : usize) {
+    let mut outlier_representatives : Vec<usize> = clustering.get_clusters()
+        .values()
+        .filter(|cluster| cluster.len() <= outlier_cluster_size)
+        .filter_map(|cluster| cluster.get_members().iter().min().copied())
+        .collect();
+    outlier_representatives.sort_unstable();
+    if let Some((rag_bag, others)) = outlier_representatives.split_first() {
+        for other in others {
+            clustering.merge(*rag_bag, *other);
+        }
+    }
+}
+
+#[cfg(test)]
+/// Tests of the search and scoring.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use hilbert::Point;
+    use crate::clustering::{self, integer_clustering};
+    use super::{Tuner, ParameterSpace, LabelledDataset, SearchStrategy, TuningClusterer};
+
+    /// Three tight groups of points along a line, plus a distant outlier.
+    fn three_groups() -> LabelledDataset {
+        let mut points = Vec::new();
+        let mut gold = integer_clustering();
+        for (group, start) in [0_u32, 1000, 2000].iter().enumerate() {
+            let category = gold.add_to_new_cluster(points.len()).unwrap();
+            for offset in 0..40_u32 {
+                if offset > 0 { gold.add_to_cluster(points.len(), category).unwrap(); }
+                points.push(Point::new(points.len(), &[start + offset * 3 + (group as u32 % 2), 50]));
+            }
+        }
+        gold.add_to_new_cluster(points.len()).unwrap();
+        points.push(Point::new(points.len(), &[4000, 4000]));
+        LabelledDataset::new("three groups", points, gold, 12)
+    }
+
+    fn small_space() -> ParameterSpace {
+        ParameterSpace {
+            noise_skip_by : vec![0, 2],
+            minimum_cluster_count : vec![6],
+            outlier_cluster_size : vec![0, 2],
+            sort_distances_completely : vec![true]
+        }
+    }
+
+    #[test]
+    fn grid_search_tries_every_combination() {
+        let result = Tuner::new().tune(&small_space(), &[three_groups()]);
+        asserting("Trial count").that(&result.trials.len()).is_equal_to(4);
+        let best_score = result.best().mean_score();
+        asserting("Best is best").that(&result.trials.iter().all(|trial| trial.mean_score() <= best_score)).is_equal_to(true);
+        asserting("One row per trial plus a header").that(&result.to_string().lines().count()).is_equal_to(5);
+    }
+
+    #[test]
+    fn random_search_is_repeatable() {
+        let tuner = Tuner::new()
+            .with_search_strategy(SearchStrategy::Random { trial_count : 3, seed : 11 })
+            .with_clusterer(TuningClusterer::SpanningTree);
+        let first = tuner.tune(&small_space(), &[three_groups()]);
+        let second = tuner.tune(&small_space(), &[three_groups()]);
+        asserting("Trial count").that(&first.trials.len()).is_equal_to(3);
+        let settings = |result : &super::TuningResult| result.trials.iter().map(|trial| trial.settings).collect::<Vec<_>>();
+        asserting("Same settings").that(&settings(&first)).is_equal_to(settings(&second));
+    }
+
+    #[test]
+    fn gather_outliers() {
+        let mut clustering = clustering::from_delimited_string("1,2,3;4;5;6,7");
+        super::gather_outliers(&mut clustering, 1);
+        asserting("Singletons gathered").that(&clustering.are_together(4, 5)).is_equal_to(true);
+        asserting("Cluster count").that(&clustering.cluster_count()).is_equal_to(3);
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use super::Clustering;
+use super::cluster::{Cluster, Chopped};
+
+// ........................... PointWeights ..........................................
+
+/// Weights of points, looked up by point id.
+///
+/// A weight says how many items a point stands for, such as a record that aggregates 500 identical transactions,
+/// or a representative of several duplicate points (see `CollapsedPoints`).
+/// Any point whose weight was never set has a weight of one, so an empty `PointWeights` treats every point equally.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PointWeights {
+    /// Weight of each point whose weight is not one.
+    weight_of_id : HashMap<usize, u64>
+}
+
+impl PointWeights {
+    /// Create weights in which every point has a weight of one.
+    pub fn new() -> Self {
+        PointWeights { weight_of_id : HashMap::new() }
+    }
+
+    /// Create weights from pairs of point id and weight.
+    /// 
+    /// Panics if any weight is zero, as `set` does.
+    pub fn from_pairs<I : IntoIterator<Item = (usize, u64)>>(pairs : I) -> Self {
+        let mut weights = Self::new();
+        for (id, weight) in pairs {
+            weights.set(id, weight);
+        }
+        weights
+    }
+
+    /// Set the weight of the point with the given id.
+    /// 
+    /// Panics if the weight is zero, since a point stands for at least one item, and a cluster 
+    /// of weightless points would have no weight to divide by when measuring it.
+    pub fn set(&mut self, id : usize, weight : u64) {
+        if weight == 0 { panic!("Point {} cannot have a weight of zero", id); }
+        if weight == 1 { self.weight_of_id.remove(&id); }
+        else { self.weight_of_id.insert(id, weight); }
+    }
+
+    /// Get the weight of the point with the given id, which is one unless set otherwise.
+    pub fn get(&self, id : usize) -> u64 {
+        self.weight_of_id.get(&id).copied().unwrap_or(1)
+    }
+
+    /// Does every point have a weight of one?
+    pub fn is_uniform(&self) -> bool { self.weight_of_id.is_empty() }
+
+    /// Sum the weights of the points with the given ids.
+    pub fn total<I : IntoIterator<Item = usize>>(&self, ids : I) -> u64 {
+        ids.into_iter().map(|id| self.get(id)).sum()
+    }
+
+    /// Sum the weights of the members of a `Cluster`.
+    pub fn cluster_weight<C : Chopped>(&self, cluster : &Cluster<C,usize>) -> u64 {
+        self.total(cluster.get_members().iter().copied())
+    }
+
+    /// Summarize a `Clustering` by the total weight of each of its clusters, heaviest first.
+    ///
+    ///   - returns - Pairs of category and cluster weight, sorted by descending weight, then by category.
+    pub fn cluster_weights<C : Chopped, G : Iterator<Item = C>>(&self, clustering : &Clustering<C,usize,G>) -> Vec<(C, u64)> {
+        let mut summary : Vec<(C, u64)> = clustering.get_clusters()
+            .iter()
+            .map(|(category, cluster)| (*category, self.cluster_weight(cluster)))
+            .collect();
+        summary.sort_by(|(category1, weight1), (category2, weight2)| weight2.cmp(weight1).then(category1.cmp(category2)));
+        summary
+    }
+}
+
+#[cfg(test)]
+/// Tests of weight lookup and cluster summaries.
+mod tests {
+    #[allow(unused_imports)]
+    use spectral::prelude::*;
+    use crate::clustering;
+    use super::PointWeights;
+
+    #[test]
+    fn cluster_weights() {
+        let clustering = clustering::from_delimited_string("1,2,3;4;5,6");
+        let weights = PointWeights::from_pairs(vec![(4, 500), (5, 2), (1, 1)]);
+        asserting("Default weight").that(&weights.get(2)).is_equal_to(1);
+        asserting("Not uniform").that(&weights.is_uniform()).is_equal_to(false);
+        let summary : Vec<u64> = weights.cluster_weights(&clustering).iter().map(|(_, weight)| *weight).collect();
+        asserting("Heaviest first").that(&summary).is_equal_to(vec![500, 3, 3]);
+        let categories : Vec<usize> = weights.cluster_weights(&clustering).iter().map(|(category, _)| *category).collect();
+        asserting("Heaviest is the cluster of 4").that(&categories[0]).is_equal_to(clustering.get_category(4).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "Point 2 cannot have a weight of zero")]
+    fn zero_weight_rejected() {
+        PointWeights::from_pairs(vec![(1, 3), (2, 0)]);
+    }
+}
@@ -0,0 +1,111 @@
+#[allow(unused_imports)]
+mod data;
+use spectral::prelude::*;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use hilbert::Point;
+use crate::data::{s1_points, s2_points, load_s1, load_s2, answer_key_2d};
+use clusterphobia::clustering::integer_clustering;
+use clusterphobia::clustering::single_linkage::SingleLinkage;
+use clusterphobia::clustering::tuning::LabelledDataset;
+use clusterphobia::clustering::count_estimate::{CountCalibration, DEFAULT_CURVE_COUNT};
+
+fn s1() -> LabelledDataset { LabelledDataset::new("S1", s1_points(), answer_key_2d(load_s1()), 20) }
+fn s2() -> LabelledDataset { LabelledDataset::new("S2", s2_points(), answer_key_2d(load_s2()), 20) }
+
+fn configure(dataset : &LabelledDataset) -> SingleLinkage {
+    SingleLinkage::new(dataset.points.len() as u32, dataset.bits_per_dimension).with_need_to_sort_by_hilbert_curve()
+}
+
+/// With the default calibration, the estimate for S1 and S2 should be near their 15 clusters
+/// and the interval should contain 15, much tighter than the 15 to 45 range allowed for the raw run count.
+#[test]
+fn default_calibration_on_s1_and_s2() {
+    for dataset in [s1(), s2()].iter() {
+        let finder = configure(dataset);
+        let mut points = dataset.points.clone();
+        let linkage_result = finder.find(&mut points);
+        let estimate = finder.estimate_true_cluster_count(&dataset.points, linkage_result.linkage_square_distance, DEFAULT_CURVE_COUNT, &CountCalibration::default());
+        asserting(&format!("{}: {} should contain 15", dataset.name, estimate)).that(&estimate.contains(15)).is_equal_to(true);
+        asserting(&format!("{}: {} should be near 15", dataset.name, estimate)).that(&(13..=17).contains(&estimate.rounded())).is_equal_to(true);
+        asserting(&format!("{}: interval {} to {} should be narrower than 15 to 45", dataset.name, estimate.lowest, estimate.highest))
+          .that(&(estimate.highest - estimate.lowest < 15)).is_equal_to(true);
+    }
+}
+
+/// Calibrating on S1 alone should carry over to the noisier S2.
+#[test]
+fn calibration_on_s1_carries_over_to_s2() {
+    let calibration = CountCalibration::fit(&[s1()], configure);
+    let dataset = s2();
+    let finder = configure(&dataset);
+    let mut points = dataset.points.clone();
+    let linkage_result = finder.find(&mut points);
+    let estimate = finder.estimate_true_cluster_count(&dataset.points, linkage_result.linkage_square_distance, DEFAULT_CURVE_COUNT, &calibration);
+    asserting(&format!("{} should contain 15", estimate)).that(&estimate.contains(15)).is_equal_to(true);
+}
+
+/// Generate Gaussian clusters in the style of the S data sets: 5,000 points in two dimensions around centers
+/// at least 150,000 apart, spread by the given standard deviation.
+fn gaussian_clusters(seed : u64, cluster_count : usize, standard_deviation : f64) -> LabelledDataset {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut points = Vec::new();
+    let mut gold_standard = integer_clustering();
+    let mut centers : Vec<(f64, f64)> = Vec::new();
+    for _ in 0..cluster_count {
+        let center = loop {
+            let (x, y) : (f64, f64) = (rng.gen_range(100_000.0..900_000.0), rng.gen_range(100_000.0..900_000.0));
+            if centers.iter().all(|(cx, cy)| (cx - x).hypot(cy - y) > 150_000.0) { break (x, y); }
+        };
+        centers.push(center);
+        let first_id = points.len();
+        for _ in 0..5000 / cluster_count {
+            // Box-Muller transform of two uniform values into a normally distributed offset.
+            let (u1, u2) : (f64, f64) = (rng.gen_range(1e-12..1.0), rng.gen());
+            let radius = (-2.0 * u1.ln()).sqrt() * standard_deviation;
+            let angle = std::f64::consts::TAU * u2;
+            let x = (center.0 + radius * angle.cos()).clamp(0.0, 1_048_575.0);
+            let y = (center.1 + radius * angle.sin()).clamp(0.0, 1_048_575.0);
+            let id = points.len();
+            points.push(Point::new(id, &[x as u32, y as u32]));
+            if id == first_id { let _ = gold_standard.add_to_new_cluster(id); }
+            else { gold_standard.merge(first_id, id); }
+        }
+    }
+    LabelledDataset::new(&format!("Gaussian {} clusters, seed {}", cluster_count, seed), points, gold_standard, 20)
+}
+
+/// The default calibration was derived from S1 and S2, so check it on generated data sets that played no part,
+/// varying the number of clusters and how much they overlap. The runs per cluster range from 1.5 to 3.7 on these,
+/// so the interval cannot always hold the true count, but the estimate must always beat the raw run count.
+#[test]
+fn default_calibration_on_held_out_data() {
+    let held_out = [(21_u64, 15_usize, 25_000.0), (22, 15, 35_000.0), (23, 15, 45_000.0), (24, 10, 35_000.0), (25, 20, 30_000.0), (26, 12, 40_000.0)];
+    let mut contained = 0;
+    for (seed, cluster_count, standard_deviation) in held_out.iter() {
+        let dataset = gaussian_clusters(*seed, *cluster_count, *standard_deviation);
+        let finder = configure(&dataset);
+        let mut points = dataset.points.clone();
+        let linkage_result = finder.find(&mut points);
+        let estimate = finder.estimate_true_cluster_count(&dataset.points, linkage_result.linkage_square_distance, DEFAULT_CURVE_COUNT, &CountCalibration::default());
+        let estimate_error = (estimate.estimate - *cluster_count as f64).abs();
+        let run_count_error = (linkage_result.large_cluster_count as f64 - *cluster_count as f64).abs();
+        asserting(&format!("{}: {} should be nearer {} than {} runs", dataset.name, estimate, cluster_count, linkage_result.large_cluster_count))
+          .that(&(estimate_error < run_count_error)).is_equal_to(true);
+        if estimate.contains(*cluster_count as u32) { contained += 1; }
+    }
+    asserting("Interval holds the true count for at least five of six").that(&(contained >= 5)).is_equal_to(true);
+}
+
+/// Joining the runs across curves heals the splits of any one curve, but noise chains the true clusters of S1 together.
+#[test]
+fn linked_runs_on_s1() {
+    let dataset = s1();
+    let finder = configure(&dataset);
+    let mut points = dataset.points.clone();
+    let linkage_square_distance = finder.find(&mut points).linkage_square_distance;
+    let one_curve = finder.estimate_true_cluster_count(&dataset.points, linkage_square_distance, 1, &CountCalibration::default());
+    asserting("One curve links only its own runs").that(&one_curve.linked_run_count).is_equal_to(one_curve.run_counts[0]);
+    let estimate = finder.estimate_true_cluster_count(&dataset.points, linkage_square_distance, DEFAULT_CURVE_COUNT, &CountCalibration::default());
+    asserting(&format!("{} runs link into fewer than 15 sets", estimate.linked_run_count))
+      .that(&(estimate.linked_run_count < 15)).is_equal_to(true);
+}
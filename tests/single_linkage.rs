@@ -2,8 +2,13 @@
 mod data;
 use spectral::prelude::*;
 // use crate::data::{load_s2, answer_key_2d, s2_points};
-use crate::data::{s1_points, s2_points};
-use clusterphobia::clustering::single_linkage::SingleLinkage;
+use crate::data::{s1_points, s2_points, load_s1, answer_key_2d};
+use clusterphobia::clustering::single_linkage::{SingleLinkage, LinkageResult, AdjacentPairDistance, PermutationCombination};
+use clusterphobia::clustering::bcubed::BCubed;
+use clusterphobia::clustering::duplicates::CollapsedPoints;
+use clusterphobia::clustering::config::LinkageConfig;
+use clusterphobia::clustering::target_count::ClusterCounting;
+use hilbert::Point;
 
 /// Test SingleLinkage::find_by_sorting against the S1 data set. 
 /// 
@@ -58,3 +63,235 @@ fn s2_single_linkage_find_by_sorting() {
 
     asserting(&format!("Estimated number of clusters {}", linkage_result.large_cluster_count)).that(&is_cluster_count_reasonable).is_equal_to(true);
 }
+
+/// Form seed clusters from the Hilbert runs for the S1 data set and compare them to the answer key.
+/// 
+/// The runs split true clusters into several pieces, so completeness suffers, but few runs 
+/// should straddle two true clusters, so homogeneity should be high.
+#[test]
+fn s1_seed_clustering() {
+    let mut points = s1_points();
+    let finder = SingleLinkage::new(points.len() as u32, 20)
+      .with_need_to_sort_by_hilbert_curve()
+      .with_noise_skip_by(9);
+    let linkage_result = finder.find(&mut points);
+    let distances = AdjacentPairDistance::all_pairs(&points);
+    let runs = finder.find_runs(&distances, linkage_result.linkage_square_distance);
+    let large_runs = runs.iter().filter(|run| !run.is_outlier).count() as u32;
+    asserting("Large runs match the estimate").that(&large_runs).is_equal_to(linkage_result.large_cluster_count);
+
+    let seeds = finder.seed_clustering(&distances, linkage_result.linkage_square_distance);
+    asserting("Every point is seeded").that(&seeds.member_count()).is_equal_to(points.len());
+    let comparison = BCubed::compare(&seeds, &answer_key_2d(load_s1()), 0.5);
+    asserting(&format!("Seed clusters should be homogeneous, got {:?}", comparison)).that(&(comparison.get_precision() > 0.9)).is_equal_to(true);
+}
+
+/// The largest coordinate value in S1 is just shy of one million, so 20 bits are needed. 
+#[test]
+fn s1_single_linkage_from_points() {
+    let mut points = s1_points();
+    let bits_per_dimension = SingleLinkage::bits_required(&points).unwrap();
+    asserting("Bits per dimension for S1").that(&bits_per_dimension).is_equal_to(20);
+
+    let finder = SingleLinkage::from_points(&points).unwrap()
+      .with_need_to_sort_by_hilbert_curve()
+      .with_noise_skip_by(9);
+    let linkage_result = finder.find(&mut points);
+    let expected_range = 3_500_000_000..3_800_000_000_u64;
+    asserting(&format!("Actual Linkage square distance {} should be in expected range", linkage_result.linkage_square_distance))
+        .that(&expected_range.contains(&linkage_result.linkage_square_distance)).is_equal_to(true);
+}
+
+/// Binning with 32 bits per dimension once overflowed while computing the largest possible square distance. 
+#[test]
+fn s1_single_linkage_find_by_binning_with_32_bits() {
+    let mut points = s1_points();
+    let finder = SingleLinkage::new(points.len() as u32, 32)
+      .without_sort_distances_completely() // this forces use of find_by_binning method
+      .with_need_to_sort_by_hilbert_curve()
+      .with_noise_skip_by(9);
+    let linkage_result = finder.find(&mut points);
+    asserting("Binning should find a linkage distance").that(&(linkage_result.linkage_square_distance > 0)).is_equal_to(true);
+}
+
+/// Combining each point's nearest neighbor distances from several Hilbert curves should give a linkage distance 
+/// within the range found by analyzing each curve's nearest neighbor distances alone, 
+/// report the spread among the curves, and the same seed must reproduce the same result. 
+#[test]
+fn s1_single_linkage_with_permutations() {
+    let finder = SingleLinkage::new(5000, 20)
+      .with_need_to_sort_by_hilbert_curve()
+      .with_noise_skip_by(9)
+      .with_permutations(4, PermutationCombination::Median)
+      .with_permutation_seed(42);
+    let linkage_result = finder.find(&mut s1_points());
+    let repeated_result = finder.find(&mut s1_points());
+    asserting("Same seed, same result").that(&repeated_result).is_equal_to(linkage_result);
+    asserting("Permutation count").that(&linkage_result.permutation_count).is_equal_to(4);
+
+    let actual_distance = linkage_result.linkage_square_distance;
+    // Exact single-link clustering of S1 already merges many of the fifteen true clusters at a square distance of 1.5 billion.
+    let expected_range = 400_000_000..1_200_000_000_u64;
+    asserting(&format!("Combined Linkage square distance {} should be in expected range", actual_distance)).that(&expected_range.contains(&actual_distance)).is_equal_to(true);
+    asserting("Combined distance within the range of the curves")
+      .that(&(linkage_result.lowest_permuted_square_distance <= actual_distance && actual_distance <= linkage_result.highest_permuted_square_distance)).is_equal_to(true);
+    asserting("Curves disagree somewhat").that(&(linkage_result.relative_spread() > 0.0)).is_equal_to(true);
+    asserting("Spread is ordered").that(&(linkage_result.lowest_permuted_square_distance <= linkage_result.highest_permuted_square_distance)).is_equal_to(true);
+
+    // The minimum never exceeds the median.
+    let minimum_result = finder.with_permutations(4, PermutationCombination::Minimum).find(&mut s1_points());
+    asserting("Minimum no larger than median").that(&(minimum_result.linkage_square_distance <= actual_distance)).is_equal_to(true);
+}
+
+#[test]
+fn s1_single_linkage_with_sampling() {
+    let full_result = SingleLinkage::new(5000, 20)
+      .with_need_to_sort_by_hilbert_curve()
+      .with_noise_skip_by(9)
+      .find(&mut s1_points());
+    let finder = SingleLinkage::new(5000, 20)
+      .with_noise_skip_by(9)
+      .with_sampling(2500, 5)
+      .with_sample_seed(7);
+    let linkage_result = finder.find(&mut s1_points());
+    let repeated_result = finder.find(&mut s1_points());
+    asserting("Same seed, same result").that(&repeated_result).is_equal_to(linkage_result);
+    asserting("Sample size").that(&linkage_result.sample_size).is_equal_to(2500);
+    asserting("Sample count").that(&linkage_result.sample_count).is_equal_to(5);
+    asserting("Subsamples disagree somewhat").that(&(linkage_result.sampling_error > 0.0)).is_equal_to(true);
+
+    // After correcting for density, the sampled estimate should be near the full estimate.
+    let ratio = linkage_result.linkage_square_distance as f64 / full_result.linkage_square_distance as f64;
+    asserting(&format!("Sampled to full ratio {} should be near one", ratio)).that(&(0.5..2.0).contains(&ratio)).is_equal_to(true);
+}
+
+/// A `SingleLinkage` restored from JSON can carry a `sample_count` of zero, which `with_sampling` never allows. 
+/// It must be treated as a single subsample rather than panic.
+#[test]
+fn s1_sampling_restored_with_zero_sample_count() {
+    let finder = SingleLinkage::new(5000, 20)
+      .with_noise_skip_by(9)
+      .with_sampling(2500, 1)
+      .with_sample_seed(7);
+    let json = serde_json::to_string(&finder).unwrap().replace("\"sample_count\":1", "\"sample_count\":0");
+    let restored : SingleLinkage = serde_json::from_str(&json).unwrap();
+    asserting("Zero sample count restored").that(&restored.get_sample_count()).is_equal_to(0);
+    let linkage_result = restored.find(&mut s1_points());
+    asserting("Analyzed as one sample").that(&linkage_result.sample_count).is_equal_to(1);
+    asserting("Same as one sample").that(&linkage_result).is_equal_to(finder.find(&mut s1_points()));
+}
+
+/// Samples smaller than the usual minimum cluster count of ten must still be analyzed, not panic.
+#[test]
+fn s1_sampling_with_tiny_samples() {
+    for sample_size in 2..10 {
+        let linkage_result = SingleLinkage::new(5000, 20)
+          .with_sampling(sample_size, 3)
+          .find(&mut s1_points());
+        asserting(&format!("Sample of {}", sample_size)).that(&linkage_result.sample_size).is_equal_to(sample_size);
+        asserting(&format!("Sample of {} gives a positive distance", sample_size)).that(&(linkage_result.linkage_square_distance > 0)).is_equal_to(true);
+    }
+    let single_point = SingleLinkage::new(5000, 20).with_sampling(1, 3).find(&mut s1_points());
+    asserting("A sample of one point is not taken").that(&single_point.sample_size).is_equal_to(0);
+}
+
+/// Analyzing weighted representatives of duplicate points must give the same result as analyzing all the duplicates.
+#[test]
+fn s1_weighted_duplicates_match_expanded() {
+    // Give each point from zero to three extra copies of itself.
+    let mut expanded_points = Vec::new();
+    for point in s1_points() {
+        let copies = point.get_id() % 4;
+        for copy in 0..=copies {
+            expanded_points.push(Point::new(point.get_id() + copy * 1_000_000, point.get_coordinates()));
+        }
+    }
+    let total_weight = expanded_points.len() as u32;
+    let collapsed = CollapsedPoints::collapse_duplicates(&expanded_points);
+    asserting("One representative per original point").that(&collapsed.len()).is_equal_to(5000);
+
+    let finder = SingleLinkage::new(total_weight, 20)
+      .with_need_to_sort_by_hilbert_curve()
+      .with_noise_skip_by(9);
+    let expanded_result = finder.find(&mut expanded_points);
+    let mut representatives = collapsed.get_representatives().to_vec();
+    let weighted_result = finder.find_weighted(&mut representatives, &collapsed.get_point_weights());
+    asserting("Same result as expanded").that(&weighted_result).is_equal_to(expanded_result);
+}
+
+/// Without hand tuning, the automatic configuration should find a linkage distance for S1 near the one found
+/// with the hand-tuned `noise_skip_by(9)`, and seed clusters that are just as homogeneous.
+#[test]
+fn s1_auto_configuration() {
+    let points = s1_points();
+    let configuration = SingleLinkage::new(points.len() as u32, 20)
+      .with_need_to_sort_by_hilbert_curve()
+      .auto(&points);
+    let expected_range = 3_000_000_000..4_000_000_000_u64;
+    asserting(&format!("Linkage square distance {} should be in expected range", configuration.linkage_square_distance))
+      .that(&expected_range.contains(&configuration.linkage_square_distance)).is_equal_to(true);
+    asserting("No duplicates in S1").that(&configuration.duplicate_fraction).is_equal_to(0.0);
+
+    let mut sorted_points = points.clone();
+    let finder = configuration.finder;
+    let linkage_result = finder.find(&mut sorted_points);
+    asserting("Same linkage distance from find").that(&linkage_result.linkage_square_distance).is_equal_to(configuration.linkage_square_distance);
+    let seeds = finder.seed_clustering(&AdjacentPairDistance::all_pairs(&sorted_points), linkage_result.linkage_square_distance);
+    let comparison = BCubed::compare(&seeds, &answer_key_2d(load_s1()), 0.5);
+    asserting(&format!("Seed clusters should be homogeneous, got {:?}", comparison)).that(&(comparison.get_precision() > 0.9)).is_equal_to(true);
+}
+
+/// S2 is noisier than S1, so the automatic configuration should still land in the hand-checked range.
+#[test]
+fn s2_auto_configuration() {
+    let points = s2_points();
+    let configuration = SingleLinkage::new(points.len() as u32, 20)
+      .with_need_to_sort_by_hilbert_curve()
+      .auto(&points);
+    let expected_range = 2_500_000_000..3_500_000_000_u64;
+    asserting(&format!("Linkage square distance {} should be in expected range", configuration.linkage_square_distance))
+      .that(&expected_range.contains(&configuration.linkage_square_distance)).is_equal_to(true);
+}
+
+/// A configuration loaded from a TOML file naming the "noisy-2d" preset should reproduce the hand-tuned S1 result,
+/// which should survive a round trip through JSON and produce a readable report.
+#[test]
+fn s1_configured_from_toml_preset() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("s1.toml");
+    std::fs::write(&path, "preset = \"noisy-2d\"\n").unwrap();
+    let mut points = s1_points();
+    let finder = LinkageConfig::load(&path).unwrap().configure(points.len() as u32, 20).unwrap();
+    let linkage_result = finder.find(&mut points);
+
+    let mut hand_tuned_points = s1_points();
+    let hand_tuned = SingleLinkage::new(hand_tuned_points.len() as u32, 20)
+      .with_sort_distances_completely()
+      .with_need_to_sort_by_hilbert_curve()
+      .with_noise_skip_by(9);
+    asserting("Preset matches hand tuning").that(&linkage_result).is_equal_to(hand_tuned.find(&mut hand_tuned_points));
+
+    let json = serde_json::to_string(&linkage_result).unwrap();
+    let restored : LinkageResult = serde_json::from_str(&json).unwrap();
+    asserting("Result survives JSON").that(&restored).is_equal_to(linkage_result);
+    let report = linkage_result.to_string();
+    asserting(&format!("Report explains the cluster count:\n{}", report))
+      .that(&report.contains(&format!("at most {}", linkage_result.large_cluster_count))).is_equal_to(true);
+}
+
+/// S1 has 15 clusters. Asking for 15 should find a linkage distance yielding them, both by counting 
+/// Hilbert runs and by counting single-link clusters, with the runs requiring a larger distance because they split clusters.
+#[test]
+fn s1_find_for_cluster_count() {
+    let finder = SingleLinkage::new(5000, 20)
+      .with_need_to_sort_by_hilbert_curve()
+      .with_outlier_cluster_size(10);
+    let mut points = s1_points();
+    let by_runs = finder.find_for_cluster_count(&mut points, 15, ClusterCounting::HilbertRuns);
+    asserting(&format!("Runs count {:?}", by_runs)).that(&by_runs.achieved_cluster_count).is_equal_to(15);
+    asserting("Runs count matches the estimate").that(&by_runs.linkage.large_cluster_count).is_equal_to(15);
+
+    let by_tree = finder.find_for_cluster_count(&mut points, 15, ClusterCounting::SpanningTree);
+    asserting(&format!("Single-link count {:?}", by_tree)).that(&by_tree.achieved_cluster_count).is_equal_to(15);
+    asserting("Runs need a larger distance").that(&(by_runs.linkage.linkage_square_distance > by_tree.linkage.linkage_square_distance)).is_equal_to(true);
+}
@@ -0,0 +1,60 @@
+#[allow(unused_imports)]
+mod data;
+use spectral::prelude::*;
+use crate::data::{s1_points, s2_points, load_s1, answer_key_2d};
+use clusterphobia::clustering::single_linkage::{SingleLinkage, AdjacentPairDistance};
+use clusterphobia::clustering::spanning_tree::MinimumSpanningTree;
+use clusterphobia::clustering::bcubed::BCubed;
+
+/// Every Hilbert run consists of points linked by distances no larger than the linkage distance, 
+/// so each run must lie wholly inside one exact single-link cluster. 
+/// Comparing the runs to the exact clusters should therefore yield perfect precision, while the recall
+/// shows how much completeness the Hilbert approximation loses.
+#[test]
+fn s1_hilbert_runs_versus_exact_single_link() {
+    let mut points = s1_points();
+    let finder = SingleLinkage::new(points.len() as u32, 20)
+      .with_need_to_sort_by_hilbert_curve()
+      .with_noise_skip_by(9);
+    let linkage_result = finder.find(&mut points);
+    let distances = AdjacentPairDistance::all_pairs(&points);
+    let seeds = finder.seed_clustering(&distances, linkage_result.linkage_square_distance);
+
+    let exact = MinimumSpanningTree::exact(&points).clustering(linkage_result.linkage_square_distance);
+    let comparison = BCubed::compare(&seeds, &exact, 0.5);
+    asserting(&format!("Hilbert runs should never straddle exact clusters, got {:?}", comparison)).that(&comparison.get_precision()).is_equal_to(1.0);
+    asserting(&format!("Hilbert runs should be less complete than exact clusters, got {:?}", comparison)).that(&(comparison.get_recall() < 1.0)).is_equal_to(true);
+}
+
+/// The approximate tree never chooses an edge shorter than the exact one, so its clusters 
+/// at a given distance are subsets of the exact clusters.
+#[test]
+fn s2_approximate_versus_exact_spanning_tree() {
+    let points = s2_points();
+    let exact_tree = MinimumSpanningTree::exact(&points);
+    let approximate_tree = MinimumSpanningTree::approximate(&points, 20, 10);
+    asserting("Same number of edges").that(&approximate_tree.get_edges().len()).is_equal_to(exact_tree.get_edges().len());
+
+    let exact_total : u128 = exact_tree.get_edges().iter().map(|e| e.square_distance as u128).sum();
+    let approximate_total : u128 = approximate_tree.get_edges().iter().map(|e| e.square_distance as u128).sum();
+    asserting("Approximate tree cannot be shorter than the exact tree").that(&(approximate_total >= exact_total)).is_equal_to(true);
+
+    let linkage_square_distance = 2_700_000_000;
+    let comparison = BCubed::compare(&approximate_tree.clustering(linkage_square_distance), &exact_tree.clustering(linkage_square_distance), 0.5);
+    asserting(&format!("Approximate clusters should be subsets of exact clusters, got {:?}", comparison)).that(&comparison.get_precision()).is_equal_to(1.0);
+}
+
+/// Cutting the exact dendrogram to yield the true number of clusters should agree well with the answer key for S1.
+#[test]
+fn s1_exact_dendrogram_versus_answer_key() {
+    let points = s1_points();
+    let dendrogram = MinimumSpanningTree::exact(&points).dendrogram();
+    let merges = dendrogram.get_merges();
+    asserting("One merge per edge").that(&merges.len()).is_equal_to(points.len() - 1);
+    asserting("Last merge holds every point").that(&merges.last().unwrap().size).is_equal_to(points.len());
+
+    let clustering = dendrogram.cut_after(points.len() - 15);
+    asserting("Cut yields fifteen clusters").that(&clustering.cluster_count()).is_equal_to(15);
+    let comparison = BCubed::compare(&clustering, &answer_key_2d(load_s1()), 0.5);
+    asserting(&format!("Exact single link has good completeness on S1, got {:?}", comparison)).that(&(comparison.get_recall() > 0.5)).is_equal_to(true);
+}
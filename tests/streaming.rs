@@ -0,0 +1,45 @@
+#[allow(dead_code)]
+mod data;
+use spectral::prelude::*;
+use crate::data::s1_points;
+use clusterphobia::clustering::single_linkage::{SingleLinkage, LinkageResult};
+use clusterphobia::clustering::streaming::StreamingLinkage;
+
+/// Streaming the points through sorted runs on disk must reproduce the in-memory analysis exactly, 
+/// since the merge preserves the stable Hilbert order and the same bins are filled.
+#[test]
+fn s1_streaming_matches_in_memory_binning() {
+    let finder = SingleLinkage::new(5000, 20)
+      .with_need_to_sort_by_hilbert_curve()
+      .without_sort_distances_completely()
+      .with_noise_skip_by(9);
+    let in_memory_result = finder.find(&mut s1_points());
+
+    let work_directory = tempfile::tempdir().unwrap();
+    let streaming_result = StreamingLinkage::new(finder, work_directory.path())
+      .with_chunk_size(700)
+      .find(s1_points())
+      .unwrap();
+    asserting("Streaming result same as in memory").that(&streaming_result).is_equal_to(in_memory_result);
+    let leftover_files = std::fs::read_dir(work_directory.path()).unwrap().count();
+    asserting("Temporary files removed").that(&leftover_files).is_equal_to(0);
+
+    // Merging the eight runs three at a time leaves three runs for the final merge.
+    let bounded_result = StreamingLinkage::new(finder, work_directory.path())
+      .with_chunk_size(700)
+      .with_merge_fan_in(3)
+      .find(s1_points())
+      .unwrap();
+    asserting("Merging in rounds changes nothing").that(&bounded_result).is_equal_to(in_memory_result);
+    let leftover_files = std::fs::read_dir(work_directory.path()).unwrap().count();
+    asserting("Temporary files from every round removed").that(&leftover_files).is_equal_to(0);
+}
+
+#[test]
+fn empty_stream() {
+    let work_directory = tempfile::tempdir().unwrap();
+    let result = StreamingLinkage::new(SingleLinkage::new(0, 20), work_directory.path())
+      .find(Vec::new())
+      .unwrap();
+    asserting("No clusters").that(&result).is_equal_to(LinkageResult::new());
+}
@@ -0,0 +1,42 @@
+#[allow(unused_imports)]
+mod data;
+use spectral::prelude::*;
+use crate::data::{s1_points, s2_points, load_s1, load_s2, answer_key_2d};
+use clusterphobia::clustering::tuning::{Tuner, ParameterSpace, LabelledDataset};
+
+fn s1_and_s2() -> Vec<LabelledDataset> {
+    vec![
+        LabelledDataset::new("S1", s1_points(), answer_key_2d(load_s1()), 20),
+        LabelledDataset::new("S2", s2_points(), answer_key_2d(load_s2()), 20)
+    ]
+}
+
+fn small_space() -> ParameterSpace {
+    ParameterSpace {
+        noise_skip_by : vec![3, 9],
+        minimum_cluster_count : vec![20, 35],
+        outlier_cluster_size : vec![0, 10],
+        sort_distances_completely : vec![true]
+    }
+}
+
+/// The tuner should find settings that reproduce the S1 and S2 answer keys reasonably well,
+/// and report a score for every trial on every data set.
+#[test]
+fn tune_on_s1_and_s2() {
+    let result = Tuner::new().tune(&small_space(), &s1_and_s2());
+    asserting("Trial count").that(&result.trials.len()).is_equal_to(8);
+    asserting("Scores per trial").that(&result.trials.iter().all(|trial| trial.scores.len() == 2)).is_equal_to(true);
+    let best = result.best();
+    asserting(&format!("Best mean score should be high, got {:.4}", best.mean_score())).that(&(best.mean_score() > 0.7)).is_equal_to(true);
+}
+
+/// Holding out either data set, the settings chosen on the other should still do well on it.
+#[test]
+fn cross_validate_on_s1_and_s2() {
+    let folds = Tuner::new().cross_validate(&small_space(), &s1_and_s2());
+    asserting("One fold per data set").that(&folds.len()).is_equal_to(2);
+    for fold in folds {
+        asserting(&format!("Held out {} should score well, got {:.4}", fold.held_out, fold.held_out_score)).that(&(fold.held_out_score > 0.6)).is_equal_to(true);
+    }
+}